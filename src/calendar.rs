@@ -0,0 +1,863 @@
+//! Conversion of course events into an iCalendar
+
+use chrono::{DateTime, Datelike, Duration, offset::Utc};
+use ics::{Alarm, ICalendar, Event, FreeBusy, Journal, ToDo, escape_text, parameters, properties};
+use ics::components::{Component, Parameter, Property};
+
+use crate::clashes;
+use crate::course::{Course, Event as CourseEvent, Geo, Transparency};
+use crate::filter::Filter;
+use crate::ical;
+use crate::markdown;
+use crate::program::Program;
+use crate::state::{self, Revision, State};
+use crate::study;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+
+/// Generate a calendar of the events in a course that match a filter
+///
+/// Events with a revision in the state are emitted with its sequence number
+/// and modification time.
+pub fn generate<'c>(course: &'c Course, name: String, filter: &Filter, state: &State) -> ICalendar<'c> {
+    let mut calendar = generate_only(course, name, filter, state, |_| true);
+    for todo in prep(course, filter) {
+        calendar.add_todo(todo);
+    }
+    for journal in journals(course, filter) {
+        calendar.add_journal(journal);
+    }
+    calendar
+}
+
+/// The topics and notes of sessions as journal entries related to their events
+fn journals<'c>(course: &'c Course, filter: &Filter) -> Vec<Journal<'c>> {
+    let mut journals = Vec::new();
+    for (uid, event) in identified(course).into_iter().filter(|(_, event)| filter.matches(event)) {
+        if event.topic().is_none() && event.notes().is_none() {
+            continue;
+        }
+        let identity = format!("{}\0journal", uid);
+        let mut journal = Journal::new(format!("{:016x}@course-calendar", state::hash(&identity)), time_format(Utc::now()));
+        let title = event.topic().map(str::to_owned).unwrap_or_else(|| event.title());
+        journal.push(properties::Summary::new(text_format(&format!("{} {}", course.code(), title))));
+        journal.push(properties::DtStart::new(time_format(event.start())));
+        if let Some(notes) = event.notes() {
+            journal.push(properties::Description::new(text_format(&markdown::to_plain(notes))));
+        }
+        journal.push(properties::RelatedTo::new(uid));
+        journal.push(properties::Status::final_());
+        journal.push(properties::Categories::new(text_format(event.kind())));
+        journals.push(journal);
+    }
+    journals
+}
+
+/// The preparation tasks of each week as to-dos due when the first session of the week starts
+fn prep<'c>(course: &'c Course, filter: &Filter) -> Vec<ToDo<'c>> {
+    let mut todos = Vec::new();
+    for (count, (week, task)) in course.prep().into_iter().enumerate() {
+        let start = match course.week_start(week) {
+            Some(start) if filter.matches_week(course.week_number(week), start, "prep") => start,
+            _ => continue,
+        };
+        let due = course
+            .events()
+            .filter(|event| event.week() == Some(week) && !event.is_assignment())
+            .map(|event| event.start())
+            .min()
+            .unwrap_or(start);
+
+        let identity = format!("{}\0prep\0{}\0{}\0{}", course.code(), week, task, count);
+        let mut todo = ToDo::new(format!("{:016x}@course-calendar", state::hash(&identity)), time_format(Utc::now()));
+        todo.push(properties::Summary::new(text_format(&format!("{} {}", course.code(), task))));
+        todo.push(properties::DtStart::new(time_format(start)));
+        todo.push(properties::Due::new(time_format(due)));
+        todo.push(properties::Status::needs_action());
+        todo.push(properties::Categories::new("prep"));
+        todos.push(todo);
+    }
+    todos
+}
+
+/// Add blocks of study before each submission that matches a filter
+pub fn add_study(calendar: &mut ICalendar, course: &Course, filter: &Filter, hours: f64) {
+    let mut counts = BTreeMap::new();
+    for block in study::schedule(course, filter, hours) {
+        let identity = format!("{}\0study\0{}", course.code(), block.submission.title());
+        let count = counts.entry(identity.clone()).or_insert(0);
+        *count += 1;
+
+        let mut event = Event::new(format!("{:016x}@course-calendar", state::hash(&format!("{}\0{}", identity, count))), time_format(Utc::now()));
+        event.push(properties::Summary::new(text_format(&format!("{} Work on {}", course.code(), block.submission.name()))));
+        event.push(properties::DtStart::new(time_format(block.start)));
+        event.push(properties::DtEnd::new(time_format(block.end)));
+        if let Some(link) = block.submission.link() {
+            event.push(properties::URL::new(link.to_string()));
+        }
+        event.push(properties::Categories::new("study"));
+        calendar.add_event(event);
+    }
+}
+
+/// Add an event for the time spent moving between buildings after sessions with little time to spare
+pub fn add_travel(calendar: &mut ICalendar, course: &Course, filter: &Filter) {
+    for (first, second) in clashes::tight_transfers(course) {
+        if !filter.matches(&first) || !filter.matches(&second) || second.start() == first.end() {
+            continue;
+        }
+        let building = second.building().unwrap_or_default();
+        let identity = format!("{}\0travel\0{}\0{}", course.code(), first.start(), second.start());
+        let mut event = Event::new(format!("{:016x}@course-calendar", state::hash(&identity)), time_format(Utc::now()));
+        event.push(properties::Summary::new(text_format(&format!("{} Travel to {}", course.code(), building))));
+        event.push(properties::DtStart::new(time_format(first.end())));
+        event.push(properties::DtEnd::new(time_format(second.start())));
+        event.push(properties::Categories::new("travel"));
+        calendar.add_event(event);
+    }
+}
+
+/// Add an all-day event on the Monday of each week listing its topic, sessions, and deadlines
+pub fn add_summaries(calendar: &mut ICalendar, course: &Course, filter: &Filter) {
+    for week in 0..course.week_count() {
+        let start = match course.week_start(week) {
+            Some(start) if filter.matches_week(course.week_number(week), start, "summary") => start,
+            _ => continue,
+        };
+        let monday = start.date().naive_local() - Duration::days(start.weekday().num_days_from_monday() as i64);
+        let events = course.events().filter(|event| event.week() == Some(week) && filter.matches(event)).collect::<Vec<_>>();
+
+        let mut description = String::new();
+        if let Some(topic) = course.week_topic(week) {
+            description.push_str(&format!("{}\n\n", topic));
+        }
+        for (heading, kind) in &[("Sessions", None), ("Deadlines", Some("submission"))] {
+            let listed = events
+                .iter()
+                .filter(|event| match kind {
+                    Some(kind) => event.kind() == *kind,
+                    None => !event.is_assignment(),
+                })
+                .collect::<Vec<_>>();
+            if listed.is_empty() {
+                continue;
+            }
+            description.push_str(&format!("{}:\n", course.text().get(heading)));
+            for event in listed {
+                let location = event.location().map(|location| format!(", {}", location)).unwrap_or_default();
+                description.push_str(&format!("- {} {}{}\n", event.start().format("%a %H:%M"), event.title(), location));
+            }
+        }
+
+        let title = match course.week_topic(week) {
+            Some(topic) => format!("{} {} {}: {}", course.code(), course.text().get("Week"), course.week_number(week), topic),
+            None => format!("{} {} {}", course.code(), course.text().get("Week"), course.week_number(week)),
+        };
+        let identity = format!("{}\0summary\0{}", course.code(), week);
+        let mut event = Event::new(format!("{:016x}@course-calendar", state::hash(&identity)), time_format(Utc::now()));
+        event.push(properties::Summary::new(text_format(&title)));
+        let mut date = Property::new("DTSTART", monday.format("%Y%m%d").to_string());
+        date.add(Parameter::new("VALUE", "DATE"));
+        event.push(date);
+        if !description.is_empty() {
+            event.push(properties::Description::new(text_format(description.trim_end())));
+        }
+        event.push(properties::Transp::transparent());
+        event.push(properties::Categories::new("summary"));
+        calendar.add_event(event);
+    }
+}
+
+/// Generate a calendar of only the events changed since the last generation
+///
+/// The calendar is an iTIP request which updates the events in the calendars
+/// of anyone who receives it.
+pub fn generate_changes<'c>(
+    course: &'c Course,
+    name: String,
+    filter: &Filter,
+    state: &State,
+    changes: &Changes,
+) -> ICalendar<'c> {
+    let mut calendar = generate_only(course, name, filter, state, |uid| changes.changed.iter().any(|changed| changed == uid));
+    calendar.push(properties::Method::new("REQUEST"));
+    calendar
+}
+
+/// Generate a combined calendar for the courses of a program along with its key dates
+///
+/// Every event is categorised by the code of its course, so that clients can
+/// show or hide each course. Sessions shared by cross-listed courses are only
+/// included once.
+pub fn generate_program<'c>(program: &'c Program, courses: &'c [Course], filter: &Filter) -> ICalendar<'c> {
+    let mut calendar = ICalendar::new("2.0", "ics-rs");
+    calendar.push(properties::Name::new(text_format(program.name())));
+    calendar.push(properties::CalScale::new("GREGORIAN"));
+
+    // Shared sessions appear once, categorised by every course they belong to
+    let mut combined: Vec<(Event, Vec<&str>)> = Vec::new();
+    let mut indices: BTreeMap<String, usize> = BTreeMap::new();
+    for course in courses {
+        for (uid, event) in events(course, filter, &State::default()) {
+            match indices.get(&uid) {
+                Some(index) => combined[*index].1.push(course.code()),
+                None => {
+                    indices.insert(uid, combined.len());
+                    combined.push((event, vec![course.code()]));
+                }
+            }
+        }
+    }
+    for (mut event, codes) in combined {
+        let codes = codes.iter().map(|code| text_format(code)).collect::<Vec<_>>();
+        event.push(properties::Categories::new(codes.join(",")));
+        calendar.add_event(event);
+    }
+
+    for date in program.dates() {
+        let identity = format!("{}\0date\0{}\0{}", program.name(), date.name(), date.start());
+        let mut event = Event::new(format!("{:016x}@course-calendar", state::hash(&identity)), time_format(Utc::now()));
+        event.push(properties::Summary::new(text_format(date.name())));
+        let mut start = Property::new("DTSTART", date.start().format("%Y%m%d").to_string());
+        start.add(Parameter::new("VALUE", "DATE"));
+        event.push(start);
+        let mut end = Property::new("DTEND", (date.end() + Duration::days(1)).format("%Y%m%d").to_string());
+        end.add(Parameter::new("VALUE", "DATE"));
+        event.push(end);
+        event.push(properties::Transp::transparent());
+        event.push(properties::Categories::new("key-date"));
+        calendar.add_event(event);
+    }
+    calendar
+}
+
+/// Generate a calendar with a single free/busy schedule of the times occupied by the course
+///
+/// Overlapping and adjacent sessions are joined into one busy period, and
+/// nothing is revealed about what happens in each period. Deadlines and other
+/// moments take up no time, and kinds of events configured as transparent are
+/// left out.
+pub fn freebusy<'c>(course: &'c Course, filter: &Filter) -> ICalendar<'c> {
+    let mut periods = course
+        .events()
+        .filter(|event| !event.is_point_in_time() && filter.matches(event))
+        .filter(|event| course.transparency(event.kind()) == Transparency::Opaque)
+        .map(|event| (event.start(), event.end()))
+        .collect::<Vec<_>>();
+    periods.sort();
+
+    let mut busy: Vec<(DateTime<_>, DateTime<_>)> = Vec::new();
+    for (start, end) in periods {
+        match busy.last_mut() {
+            Some(last) if start <= last.1 => last.1 = last.1.max(end),
+            _ => busy.push((start, end)),
+        }
+    }
+
+    let mut calendar = ICalendar::new("2.0", "ics-rs");
+    let identity = format!("{}\0freebusy", course.code());
+    let mut freebusy = FreeBusy::new(format!("{:016x}@course-calendar", state::hash(&identity)), time_format(Utc::now()));
+    freebusy.push(properties::Comment::new(text_format(&format!("{} {}", course.code(), course.name()))));
+    if let (Some(first), Some(last)) = (busy.first(), busy.last()) {
+        freebusy.push(properties::DtStart::new(time_format(first.0)));
+        freebusy.push(properties::DtEnd::new(time_format(last.1)));
+    }
+    for (start, end) in busy {
+        let mut period = properties::FreeBusyTime::new(format!("{}/{}", time_format(start), time_format(end)));
+        period.add(parameters::FBType::BUSY);
+        freebusy.push(period);
+    }
+    calendar.add_freebusy(freebusy);
+    calendar
+}
+
+/// Generate an iTIP cancellation of events that have been removed
+pub fn generate_cancellations(name: String, changes: &Changes) -> ICalendar<'static> {
+    let mut calendar = ICalendar::new("2.0", "ics-rs");
+    calendar.push(properties::Name::new(text_format(&name)));
+    calendar.push(properties::Method::new("CANCEL"));
+
+    for (uid, revision) in &changes.removed {
+        let mut event = Event::new(uid.clone(), time_format(Utc::now()));
+        event.push(properties::Sequence::new((revision.sequence + 1).to_string()));
+        event.push(properties::Status::cancelled());
+        if !revision.summary.is_empty() {
+            event.push(properties::Summary::new(text_format(&revision.summary)));
+        }
+        if !revision.start.is_empty() {
+            event.push(properties::DtStart::new(revision.start.clone()));
+        }
+        calendar.add_event(event);
+    }
+
+    calendar
+}
+
+/// Generate a calendar containing a single event
+pub fn generate_single<'c>(course: &'c Course, name: String, event: Event<'c>) -> ICalendar<'c> {
+    let mut calendar = header(course, name);
+    calendar.add_event(event);
+    calendar
+}
+
+fn generate_only<'c, F>(course: &'c Course, name: String, filter: &Filter, state: &State, include: F) -> ICalendar<'c>
+where
+    F: Fn(&str) -> bool,
+{
+    let mut calendar = header(course, name);
+    for (uid, event) in events(course, filter, state) {
+        if include(&uid) {
+            calendar.add_event(event);
+        }
+    }
+    calendar
+}
+
+fn header<'c>(course: &'c Course, name: String) -> ICalendar<'c> {
+    let mut calendar = ICalendar::new("2.0", "ics-rs");
+    calendar.push(properties::Name::new(text_format(&name)));
+    calendar.push(properties::CalScale::new("GREGORIAN"));
+    for (name, value) in course.extra() {
+        calendar.push(Property::new(name, value));
+    }
+    calendar
+}
+
+/// The events in a course that match a filter, keyed by UID
+pub fn events<'c>(course: &'c Course, filter: &Filter, state: &State) -> Vec<(String, Event<'c>)> {
+    let mut events = Vec::new();
+
+    for (uid, event) in identified(course).into_iter().filter(|(_, event)| filter.matches(event)) {
+        // Events are stamped with their last revision, so unchanged events read the same in each generation
+        let cal_event = match state.revision(&uid) {
+            Some(revision) => {
+                let mut cal_event = render(course, &uid, &event, revision.modified.clone());
+                cal_event.push(properties::Sequence::new(revision.sequence.to_string()));
+                cal_event.push(properties::LastModified::new(revision.modified.clone()));
+                cal_event
+            }
+            None => render(course, &uid, &event, time_format(Utc::now())),
+        };
+        events.push((uid, cal_event));
+    }
+
+    events
+}
+
+/// Render an event, stamped at a given time, without the properties recorded in its revision
+fn render<'c>(course: &'c Course, uid: &str, event: &CourseEvent<'c>, stamp: String) -> Event<'c> {
+    let mut cal_event = Event::new(uid.to_owned(), stamp);
+    let mut summary = format!("{} {}", course.code().to_owned(), event.title());
+    if event.is_tentative() {
+        summary.push_str(&format!(" [{}]", course.text().get("TBC")));
+        cal_event.push(properties::Status::tentative());
+    }
+    cal_event.push(properties::Summary::new(text_format(&summary)));
+    cal_event.push(properties::DtStart::new(time_format(event.start())));
+    cal_event.push(properties::DtEnd::new(time_format(event.end())));
+    //cal_event.push(properties::Duration::new(duration_format(event.duration())));
+    if let Some(location) = event.location() {
+        cal_event.push(properties::Location::new(text_format(location)));
+    }
+    if let Some(Geo(latitude, longitude)) = event.geo() {
+        cal_event.push(properties::Geo::new(format!("{};{}", latitude, longitude)));
+        let mut structured = Property::new("X-APPLE-STRUCTURED-LOCATION", format!("geo:{},{}", latitude, longitude));
+        structured.add(Parameter::new("VALUE", "URI"));
+        if let Some(location) = event.location() {
+            structured.add(Parameter::new("X-TITLE", format!("\"{}\"", location.replace('"', "'"))));
+        }
+        cal_event.push(structured);
+    }
+    for presenter in event.presenters() {
+        cal_event.push(properties::Contact::new(text_format(presenter)));
+    }
+    if let Some(link) = event.link() {
+        cal_event.push(properties::URL::new(link.as_str()));
+    }
+    if let Some(meeting) = event.meeting_url() {
+        let mut conference = Property::new("CONFERENCE", meeting.as_str());
+        conference.add(Parameter::new("VALUE", "URI"));
+        conference.add(Parameter::new("FEATURE", "VIDEO"));
+        conference.add(Parameter::new("LABEL", course.text().get("Join online")));
+        cal_event.push(conference);
+        cal_event.push(Property::new("X-GOOGLE-CONFERENCE", meeting.as_str()));
+    }
+    for attachment in event.attachments() {
+        cal_event.push(properties::Attach::new(attachment.as_str()));
+    }
+    if let Some(description) = description(course, event) {
+        let plain = markdown::to_plain(&description);
+        cal_event.push(properties::Description::new(text_format(&plain)));
+        let mut html = Property::new("X-ALT-DESC", text_format(&markdown::to_html(&description)));
+        html.add(parameters::FmtType::new("text/html"));
+        cal_event.push(html);
+    }
+    if course.is_redacted() {
+        cal_event.push(properties::Class::public());
+    } else if event.is_private() {
+        cal_event.push(properties::Class::private());
+    } else if course.withholds(event) {
+        cal_event.push(properties::Class::confidential());
+    }
+    if let Some(priority) = event.priority() {
+        cal_event.push(properties::Priority::new(priority.to_string()));
+    }
+    cal_event.push(match course.transparency(event.kind()) {
+        Transparency::Opaque => properties::Transp::opaque(),
+        Transparency::Transparent => properties::Transp::transparent(),
+    });
+    let resources = event.resources().map(text_format).collect::<Vec<_>>();
+    if !resources.is_empty() {
+        cal_event.push(properties::Resources::new(resources.join(",")));
+    }
+    let categories = event.tags().collect::<Vec<_>>();
+    if !categories.is_empty() {
+        let categories = categories.iter().map(|tag| text_format(tag)).collect::<Vec<_>>();
+        cal_event.push(properties::Categories::new(categories.join(",")));
+    }
+    if let Some(week) = event.week() {
+        cal_event.push(Property::new("X-WEEK", course.week_number(week).to_string()));
+    }
+    for (name, value) in event.extra() {
+        cal_event.push(Property::new(name, value));
+    }
+    for reminder in event.reminders() {
+        let trigger = properties::Trigger::new(format!("-{}", duration_format(reminder)));
+        cal_event.add_alarm(Alarm::display(trigger, properties::Description::new(text_format(&summary))));
+    }
+    cal_event
+}
+
+/// The events that changed since the state was last updated, or since a destination was last sent changes
+#[derive(Debug, Clone, Default)]
+pub struct Changes {
+    /// UIDs of events that are new or have changed
+    pub changed: Vec<String>,
+    /// Events that no longer exist, with their last revisions
+    pub removed: Vec<(String, Revision)>,
+}
+
+/// Record the current content of every event in a course
+pub fn update_state(course: &Course, filter: &Filter, state: &mut State) -> Changes {
+    let now = time_format(Utc::now());
+    let events = identified(course);
+    let mut changes = Changes::default();
+
+    // Events left out by the filter keep their revisions, so they are still seen as changed when next included
+    for (uid, event) in events.iter().filter(|(_, event)| filter.matches(event)) {
+        let revision = Revision {
+            hash: content_hash(course, uid, event),
+            sequence: 0,
+            modified: now.clone(),
+            summary: format!("{} {}", course.code(), event.title()),
+            start: time_format(event.start()),
+        };
+        if state.update(uid, revision) {
+            changes.changed.push(uid.clone());
+        }
+    }
+
+    changes.removed = state.retain(&events.into_iter().map(|(uid, _)| uid).collect::<Vec<_>>());
+    changes
+}
+
+/// The events that changed or were removed since changes were last sent to a destination
+///
+/// Each destination, such as a publishing target or incremental calendars, keeps its own record
+/// of what it was sent, so that generating other outputs from the same state doesn't hide changes
+/// from it.
+pub fn pending(course: &Course, filter: &Filter, state: &State, destination: &str) -> Changes {
+    let events = identified(course);
+    let delivered = state.delivered(destination).collect::<BTreeMap<_, _>>();
+    let changed = events
+        .iter()
+        .filter(|(_, event)| filter.matches(event))
+        .filter(|(uid, _)| state.revision(uid).map(|revision| &revision.hash) != delivered.get(uid).map(|revision| &revision.hash))
+        .map(|(uid, _)| uid.clone())
+        .collect();
+    let removed = delivered
+        .into_iter()
+        .filter(|(uid, _)| state.revision(uid).is_none())
+        .map(|(uid, revision)| (uid.clone(), revision.clone()))
+        .collect();
+    Changes { changed, removed }
+}
+
+/// Record that a destination was sent changes
+pub fn deliver(state: &mut State, destination: &str, changes: &Changes) {
+    for uid in changes.changed.iter().chain(changes.removed.iter().map(|(uid, _)| uid)) {
+        state.deliver(destination, uid);
+    }
+}
+
+/// Pair every event in a course with a UID that is stable across generations
+///
+/// The UID has the form `{code}-{kind}-{week}-{slug}@course-calendar`, so that
+/// calendars merging several courses never collide. Sessions from a shared file
+/// take the name of the file in place of the code. The slug comes from the
+/// identity of the event rather than its title, so that it survives renaming
+/// the event and changing its location. Only events that would otherwise share
+/// a UID, such as simultaneous sessions of the same kind, are numbered in the
+/// order in which they occur.
+pub fn identified(course: &Course) -> Vec<(String, CourseEvent<'_>)> {
+    let mut counts = BTreeMap::new();
+    course
+        .events()
+        .map(|event| {
+            // Weeks count from 1 regardless of how they are numbered, so changing the numbering keeps UIDs
+            let week = event.week().map(|week| week + 1).unwrap_or(0);
+            // Shared sessions are namespaced by their file, so that cross-listed courses agree on them
+            let namespace = match event.shared() {
+                Some(shared) => Path::new(shared).file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_default(),
+                None => course.code().to_owned(),
+            };
+            let mut name = format!("{}-{}-{}", slug(&namespace), slug(event.kind()), week);
+            let identity = slug(&event.identity());
+            if !identity.is_empty() {
+                name = format!("{}-{}", name, identity);
+            }
+            let count = counts.entry(name.clone()).or_insert(0);
+            *count += 1;
+            if *count > 1 {
+                name = format!("{}-{}", name, count);
+            }
+            (format!("{}@course-calendar", name), event)
+        })
+        .collect()
+}
+
+/// Lowercase letters and digits, with every other run of characters replaced by a hyphen
+fn slug(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_end_matches('-').to_owned()
+}
+
+/// The description of an event, followed by the number and label of its week
+fn description(course: &Course, event: &CourseEvent) -> Option<String> {
+    let week = event.week().map(|week| match course.week_label(week) {
+        Some(label) => format!("{} {}: {}", course.text().get("Week"), course.week_number(week), label),
+        None => format!("{} {}", course.text().get("Week"), course.week_number(week)),
+    });
+    match (event.description(), week) {
+        (Some(description), Some(week)) => Some(format!("{}\n\n{}", description, week)),
+        (None, week) => week,
+        (description, None) => description,
+    }
+}
+
+/// A hash of everything emitted for an event, used to detect changes
+fn content_hash(course: &Course, uid: &str, event: &CourseEvent) -> String {
+    // A fixed stamp keeps the time of generation out of the hash
+    format!("{:016x}", state::hash(&render(course, uid, event, String::new()).to_string()))
+}
+
+/// Merge the events of external calendars into a calendar
+///
+/// Events with a UID that has already been merged are skipped, along with
+/// duplicate time zone definitions.
+pub fn merge(calendar: &mut ICalendar, external: &[ical::Component]) {
+    let mut uids = BTreeSet::new();
+    let mut zones = BTreeSet::new();
+
+    for source in external {
+        for component in &source.components {
+            let seen = match component.name.as_str() {
+                "VEVENT" => component.text("UID").map(|uid| !uids.insert(uid)).unwrap_or(false),
+                "VTIMEZONE" => component.text("TZID").map(|zone| !zones.insert(zone)).unwrap_or(false),
+                _ => true,
+            };
+            if !seen {
+                calendar.add_component(copy_component(component));
+            }
+        }
+    }
+}
+
+fn copy_component(source: &ical::Component) -> Component<'static> {
+    let mut component = Component::new(source.name.clone());
+    for line in &source.properties {
+        let mut property = Property::new(line.name.clone(), line.value.clone());
+        for (key, value) in &line.parameters {
+            let value = if value.contains([':', ';', ',']) {
+                format!("\"{}\"", value)
+            } else {
+                value.clone()
+            };
+            property.add(Parameter::new(key.clone(), value));
+        }
+        component.add_property(property);
+    }
+    for child in &source.components {
+        component.add_component(copy_component(child));
+    }
+    component
+}
+
+fn time_format<O>(time: DateTime<O>) -> String
+where
+    O: chrono::TimeZone,
+    DateTime<Utc>: From<DateTime<O>>,
+{
+    let utc_time: DateTime<Utc> = time.into();
+    utc_time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// Escape text for use as an iCalendar TEXT value
+fn text_format(text: &str) -> String {
+    escape_text(text).replace('\n', "\\n")
+}
+
+fn duration_format(duration: Duration) -> String {
+    let days = duration.num_days();
+    let consumed = Duration::days(days);
+    let hours = (duration - consumed).num_hours();
+    let consumed = consumed + Duration::hours(hours);
+    let minutes = (duration - consumed).num_minutes();
+    let consumed = consumed + Duration::minutes(minutes);
+    let seconds = (duration - consumed).num_seconds();
+    format!("P{}DT{}H{}M{}S", days, hours, minutes, seconds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn course(tutorial_location: &str, private: &str) -> Course {
+        let spec = format!(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/"
+            private = [{}]
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-12T10:00:00+11:00
+            kind = "lecture"
+            duration = 3600
+            weeks = [1]
+
+            [[session]]
+            first = 2024-02-13T10:00:00+11:00
+            kind = "tutorial"
+            location = "{}"
+            duration = 3600
+            weeks = [1]
+            "#,
+            private, tutorial_location
+        );
+        let mut course = Course::from_value(toml::from_str(&spec).unwrap()).unwrap();
+        course.generate_repeats().unwrap();
+        course
+    }
+
+    #[test]
+    fn filtered_events_keep_their_revisions() {
+        let mut state = State::default();
+        assert_eq!(update_state(&course("Quad 1001", ""), &Filter::default(), &mut state).changed.len(), 2);
+
+        let lectures = Filter { kinds: vec!["lecture".to_owned()], ..Filter::default() };
+        let changes = update_state(&course("Quad 1002", ""), &lectures, &mut state);
+        assert!(changes.changed.is_empty());
+        assert!(changes.removed.is_empty());
+
+        let changes = update_state(&course("Quad 1002", ""), &Filter::default(), &mut state);
+        assert_eq!(changes.changed, vec!["comp1000-tutorial-1-tue-1000@course-calendar".to_owned()]);
+    }
+
+    #[test]
+    fn every_emitted_property_is_hashed() {
+        let mut state = State::default();
+        update_state(&course("Quad 1001", ""), &Filter::default(), &mut state);
+        // Withholding the location only changes the classification of the session that has one
+        let changes = update_state(&course("Quad 1001", "\"location\""), &Filter::default(), &mut state);
+        assert_eq!(changes.changed, vec!["comp1000-tutorial-1-tue-1000@course-calendar".to_owned()]);
+    }
+
+    #[test]
+    fn text_properties_are_escaped() {
+        let course = course("Quad 1001, Kensington; Level 2", "");
+        let tutorial = events(&course, &Filter::default(), &State::default()).remove(1).1.to_string();
+        assert!(tutorial.contains("LOCATION:Quad 1001\\, Kensington\\; Level 2\r\n"), "{}", tutorial);
+    }
+
+    #[test]
+    fn other_outputs_leave_changes_pending() {
+        let mut state = State::default();
+        update_state(&course("Quad 1001", ""), &Filter::default(), &mut state);
+        let changes = pending(&course("Quad 1001", ""), &Filter::default(), &state, "caldav");
+        assert_eq!(changes.changed.len(), 2);
+        deliver(&mut state, "caldav", &changes);
+
+        // Generating a calendar after the room changes updates the revisions, but not what was published
+        let moved = course("Quad 1002", "");
+        update_state(&moved, &Filter::default(), &mut state);
+        assert_eq!(pending(&moved, &Filter::default(), &state, "caldav").changed, vec!["comp1000-tutorial-1-tue-1000@course-calendar".to_owned()]);
+        assert_eq!(pending(&moved, &Filter::default(), &state, "incremental").changed.len(), 2);
+    }
+
+    const WITHHELD: &str = r#"
+        version = 2
+        code = "COMP1000"
+        name = "Testing"
+        link = "https://example.edu/"
+        private = ["location", "presenters", "description", "link", "meeting_url", "notes", "attachments", "slides", "recording", "participants"]
+
+        [[week]]
+        start = 2024-02-12T00:00:00+11:00
+
+        [[week.session]]
+        time = 2024-02-12T10:00:00+11:00
+        kind = "lecture"
+        duration = 3600
+        description = "Secret description"
+        location = "Secret room"
+        presenters = ["Secret lecturer"]
+        link = "https://secret.example.edu/link"
+        meeting_url = "https://secret.example.edu/meeting"
+        notes = "Secret notes"
+        attachments = ["https://secret.example.edu/attachment"]
+        slides = "https://secret.example.edu/slides"
+        recording = "https://secret.example.edu/recording"
+
+        [[week.session]]
+        time = 2024-02-13T10:00:00+11:00
+        kind = "lab"
+        duration = 3600
+
+        [[assignment]]
+        name = "Assignment"
+        description = "Secret specification"
+        link = "https://secret.example.edu/assignment"
+
+        [[assignment.submission]]
+        time = 2024-02-16T17:00:00+11:00
+        name = "Final"
+        description = "Secret submission"
+        attachments = ["https://secret.example.edu/template"]
+
+        [[assignment.milestone]]
+        time = 2024-02-14T17:00:00+11:00
+        name = "Review"
+        description = "Secret milestone"
+
+        [[assignment.presentation]]
+        name = "Demo"
+        session = "lab"
+        weeks = [1]
+        description = "Secret demo"
+        attachments = ["https://secret.example.edu/rubric"]
+        groups = ["Red"]
+        participants = { Red = ["Secret student"] }
+
+        [[quiz]]
+        name = "Quiz"
+        description = "Secret quiz"
+        link = "https://secret.example.edu/quiz"
+        opens = 2024-02-12T09:00:00+11:00
+        closes = 2024-02-14T17:00:00+11:00
+        weeks = [1]
+
+        [[office_hours]]
+        weekday = "Wednesday"
+        time = "14:00"
+        duration = 3600
+        presenters = ["Secret tutor"]
+        location = "Secret office"
+        link = "https://secret.example.edu/office"
+        description = "Secret consultation"
+
+        [[office_hours]]
+        title = "Staff meeting"
+        weekday = "Thursday"
+        time = "14:00"
+        duration = 3600
+        private = true
+    "#;
+
+    /// The calendar of a course, unfolded so that text can be searched for
+    fn unfolded(course: &Course) -> String {
+        generate(course, course.name().to_owned(), &Filter::default(), &State::default()).to_string().replace("\r\n ", "")
+    }
+
+    #[test]
+    fn public_calendars_leave_out_private_fields() {
+        let mut course = Course::from_value(toml::from_str(WITHHELD).unwrap()).unwrap();
+        course.generate_repeats().unwrap();
+        assert!(unfolded(&course).contains("Secret"));
+
+        course.redact();
+        let calendar = unfolded(&course);
+        assert!(!calendar.to_lowercase().contains("secret"), "{}", calendar);
+        assert!(!calendar.contains("Staff meeting"));
+        assert!(calendar.contains("URL:https://example.edu/\r\n"));
+        assert!(!calendar.contains("CLASS:CONFIDENTIAL"));
+    }
+
+    #[test]
+    fn only_events_with_private_values_are_confidential() {
+        let course = course("Quad 1001", "\"location\"");
+        let classes = events(&course, &Filter::default(), &State::default())
+            .into_iter()
+            .map(|(_, event)| event.to_string().contains("CLASS:CONFIDENTIAL"))
+            .collect::<Vec<_>>();
+        assert_eq!(classes, vec![false, true]);
+    }
+
+    #[test]
+    fn presentations_are_busy_but_deadlines_are_not() {
+        let mut course = Course::from_value(toml::from_str(WITHHELD).unwrap()).unwrap();
+        course.generate_repeats().unwrap();
+        let kinds = |kinds: &[&str]| Filter { kinds: kinds.iter().map(|kind| kind.to_string()).collect(), ..Filter::default() };
+        let busy = |filter: &Filter| freebusy(&course, filter).to_string().lines().filter(|line| line.starts_with("FREEBUSY")).count();
+        assert_eq!(busy(&kinds(&["presentation"])), 1);
+        assert_eq!(busy(&kinds(&["submission", "quiz", "milestone"])), 0);
+    }
+
+    #[test]
+    fn slugs_keep_only_letters_and_digits() {
+        assert_eq!(slug("Lab: Week 1 -- Intro!"), "lab-week-1-intro");
+        assert_eq!(slug("  Café  "), "café");
+        assert_eq!(slug("***"), "");
+    }
+
+    /// The UIDs of a course with a lecture at each of the given times in the first week
+    fn lecture_uids(times: &[&str]) -> Vec<String> {
+        let mut spec = "version = 2\ncode = \"COMP1000\"\nname = \"Testing\"\nlink = \"https://example.edu/\"\n".to_owned();
+        spec.push_str("[[week]]\nstart = 2024-02-12T00:00:00+11:00\n");
+        for time in times {
+            spec.push_str(&format!("[[session]]\nfirst = {}\nkind = \"lecture\"\nduration = 3600\nweeks = [1]\n", time));
+        }
+        let mut course = Course::from_value(toml::from_str(&spec).unwrap()).unwrap();
+        course.generate_repeats().unwrap();
+        identified(&course).into_iter().map(|(uid, _)| uid).collect()
+    }
+
+    #[test]
+    fn uids_follow_the_start_of_each_session() {
+        let uids = lecture_uids(&["2024-02-12T10:00:00+11:00", "2024-02-14T10:00:00+11:00"]);
+        assert_eq!(uids, vec!["comp1000-lecture-1-mon-1000@course-calendar", "comp1000-lecture-1-wed-1000@course-calendar"]);
+        // Removing an earlier session leaves the rest as they were
+        assert_eq!(lecture_uids(&["2024-02-14T10:00:00+11:00"]), uids[1..].to_vec());
+        // Moving a session to another room keeps its UID
+        let uids_of = |course: &Course| identified(course).into_iter().map(|(uid, _)| uid).collect::<Vec<_>>();
+        assert_eq!(uids_of(&course("Quad 1001", "")), uids_of(&course("Quad 1002", "")));
+    }
+
+    #[test]
+    fn simultaneous_sessions_are_numbered() {
+        let uids = lecture_uids(&["2024-02-12T10:00:00+11:00", "2024-02-12T10:00:00+11:00"]);
+        assert_eq!(uids, vec!["comp1000-lecture-1-mon-1000@course-calendar", "comp1000-lecture-1-mon-1000-2@course-calendar"]);
+    }
+}
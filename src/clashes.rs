@@ -0,0 +1,79 @@
+//! Detection of overlapping events
+
+use crate::course::{Course, Event};
+
+/// An event along with the course it belongs to
+pub type CourseEvent<'c> = (&'c Course, Event<'c>);
+
+/// Find all pairs of events that overlap in time
+///
+/// Assignment events are ignored as they either occur within a session or are
+/// deadlines rather than time that must be attended.
+pub fn overlapping<'e, 'c>(events: &'e [CourseEvent<'c>]) -> Vec<(&'e CourseEvent<'c>, &'e CourseEvent<'c>)> {
+    let mut sessions = events.iter().filter(|(_, event)| !event.is_assignment()).collect::<Vec<_>>();
+    sessions.sort_by_key(|(_, event)| event.start());
+
+    let mut overlaps = Vec::new();
+    for (index, first) in sessions.iter().enumerate() {
+        for second in &sessions[index + 1..] {
+            if second.1.start() >= first.1.end() {
+                break;
+            }
+            overlaps.push((*first, *second));
+        }
+    }
+    overlaps
+}
+
+/// Find overlapping events between different courses
+///
+/// A shared session appears in each of its courses, but never clashes with itself.
+pub fn between_courses<'e, 'c>(events: &'e [CourseEvent<'c>]) -> Vec<(&'e CourseEvent<'c>, &'e CourseEvent<'c>)> {
+    overlapping(events)
+        .into_iter()
+        .filter(|(first, second)| !std::ptr::eq(first.0, second.0))
+        .filter(|(first, second)| !(first.1.shared().is_some() && first.1.shared() == second.1.shared() && first.1.start() == second.1.start()))
+        .collect()
+}
+
+/// Find consecutive sessions in different buildings with too little time to travel between them
+///
+/// Alternative streams of the same kind of session are never attended together,
+/// so they aren't paired.
+pub fn tight_transfers<'c>(course: &'c Course) -> Vec<(Event<'c>, Event<'c>)> {
+    let mut sessions = course.events().filter(|event| !event.is_assignment() && event.building().is_some()).collect::<Vec<_>>();
+    sessions.sort_by_key(|event| event.start());
+
+    let mut transfers = Vec::new();
+    for (index, first) in sessions.iter().enumerate() {
+        for second in &sessions[index + 1..] {
+            if second.start() >= first.end() + course.travel_time() {
+                break;
+            }
+            let alternatives = first.kind() == second.kind() && first.stream().is_some() && second.stream().is_some();
+            if second.start() >= first.end() && second.building() != first.building() && !alternatives {
+                transfers.push((first.clone(), second.clone()));
+            }
+        }
+    }
+    transfers
+}
+
+/// Describe an event for use in a report
+pub fn describe(course: &Course, event: &Event) -> String {
+    let mut description = format!(
+        "{} {} on {} {}-{}",
+        course.code(),
+        event.title(),
+        event.start().format("%a %Y-%m-%d"),
+        event.start().format("%H:%M"),
+        event.end().format("%H:%M"),
+    );
+    if let Some(week) = event.week() {
+        description.push_str(&format!(" in week {}", course.week_number(week)));
+    }
+    if let Some(stream) = event.stream() {
+        description.push_str(&format!(" (stream {})", stream));
+    }
+    description
+}
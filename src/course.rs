@@ -1,29 +1,400 @@
 //! Events that occur for a particular course
 
-use chrono::{offset::FixedOffset, DateTime, Duration};
-use serde::{de, Deserialize, Deserializer};
+use chrono::{offset::FixedOffset, DateTime, Datelike, Duration, NaiveTime, TimeZone, Weekday};
+use serde::{de, ser, Deserialize, Deserializer, Serialize, Serializer};
 use url::Url;
 
-use failure::{Error, format_err};
+use failure::{Error, bail, format_err};
 
+use crate::fetch::{fetch, is_url};
+use crate::ical;
+use crate::migrate;
+use crate::spec;
+use crate::validate::Level;
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
 use std::fmt;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+/// The fields of events that may be withheld from public feeds
+const PRIVATE_FIELDS: &[&str] = &[
+    "location",
+    "presenters",
+    "description",
+    "link",
+    "meeting_url",
+    "notes",
+    "attachments",
+    "slides",
+    "recording",
+    "participants",
+];
 
 /// All of the events for a particular course
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Course {
+    /// The layout version, which is always current once loaded
+    version: i64,
     code: String,
+    /// Other codes under which the course is offered, such as a postgraduate version
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    aliases: Vec<String>,
     name: String,
-    #[serde(deserialize_with = "deserialize_url")]
+    #[serde(deserialize_with = "deserialize_url", serialize_with = "serialize_url")]
     link: Url,
-    #[serde(rename = "week", default)]
+    /// Calendars whose events are merged into the output
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    merge: Vec<String>,
+    /// The total that the values of the assignments should add up to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    assessment_total: Option<u64>,
+    /// The time needed to move between buildings
+    #[serde(default, deserialize_with = "deserialize_optional_duration", serialize_with = "serialize_optional_duration", skip_serializing_if = "Option::is_none")]
+    travel_time: Option<Duration>,
+    /// Fields of events withheld from public feeds
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    private: Vec<String>,
+    /// Files of sessions shared with cross-listed courses
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    shared: Vec<String>,
+    /// How weeks are numbered in the specification; written out weeks are always numbered from one
+    #[serde(default, skip_serializing)]
+    week_numbering: WeekNumbering,
+    #[serde(rename = "week", default, skip_serializing_if = "Vec::is_empty")]
     weeks: Vec<Week>,
-    #[serde(rename = "assignment", default)]
+    #[serde(rename = "assignment", default, skip_serializing_if = "Vec::is_empty")]
     assignments: Vec<Assignment>,
-    #[serde(rename = "session", default)]
+    #[serde(rename = "quiz", default, skip_serializing_if = "Vec::is_empty")]
+    quizzes: Vec<Quiz>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    office_hours: Vec<OfficeHours>,
+    #[serde(rename = "session", default, skip_serializing_if = "Vec::is_empty")]
     repeat_sessions: Vec<RepeatSession>,
+    /// Rooms referred to by sessions, keyed by their identifiers
+    #[serde(rename = "location", default, skip_serializing_if = "BTreeMap::is_empty")]
+    locations: BTreeMap<String, Location>,
+    /// Whether each kind of event shows as busy, keyed by kind
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    transparency: BTreeMap<String, Transparency>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    extra: BTreeMap<String, String>,
+    /// Levels for validation rules, keyed by rule name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    lints: BTreeMap<String, Level>,
+    /// Generated text in the language of the course, keyed by the English text
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    strings: BTreeMap<String, String>,
+    /// Deliveries of the course in different terms, kept as written to be applied when selected
+    #[serde(skip)]
+    offerings: Vec<toml::Value>,
+    /// The directory or URL against which relative paths are resolved
+    #[serde(skip)]
+    base: String,
+    /// The locations of the other specifications the course is read from
+    #[serde(skip)]
+    sources: Vec<String>,
+    /// Whether private sessions and fields have been removed
+    #[serde(skip)]
+    redacted: bool,
+    /// The sessions read from the shared files
+    #[serde(skip)]
+    shared_sessions: Vec<RepeatSession>,
 }
 
 impl Course {
+    /// Load a course specification and generate its repeated sessions
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Course::from_path_offering(path, None)
+    }
+
+    /// Load the offering of a course for a term and generate its repeated sessions
+    pub fn from_path_offering<P: AsRef<Path>>(path: P, term: Option<&str>) -> Result<Self, Error> {
+        let mut course = Course::load_offering(path, term)?;
+        course.generate_repeats()?;
+        Ok(course)
+    }
+
+    /// Load a course specification without generating repeated sessions
+    ///
+    /// The path may also be a URL from which to download the specification.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Course::load_offering(path, None)
+    }
+
+    /// Load a course specification with the overrides of the offering for a term
+    pub fn load_offering<P: AsRef<Path>>(path: P, term: Option<&str>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let location = path.to_string_lossy();
+        let (source, base) = if is_url(&location) {
+            (fetch(&location, "")?, location.into_owned())
+        } else {
+            (read_to_string(path)?, path.parent().map(|parent| parent.to_string_lossy().into_owned()).unwrap_or_default())
+        };
+
+        let (spec, included) = spec::include(spec::parse(&source)?, &base)?;
+        let (mut spec, parents) = spec::extend(spec, &base)?;
+        if let Some(term) = term {
+            spec::select_offering(&mut spec, term)?;
+        }
+        spec::interpolate(&mut spec)?;
+        spec::apply_defaults(&mut spec)?;
+        let mut course = Course::from_value(spec)?;
+        course.base = base;
+        course.sources = included.into_iter().chain(parents).collect();
+        course.load_shared()?;
+        Ok(course)
+    }
+
+    /// Read the sessions shared with cross-listed courses
+    ///
+    /// Shared files are prepared in the same way as the course. Unless they say
+    /// otherwise, they have the current layout and number weeks as the course
+    /// does, and they may refer to the weeks of the course by label.
+    fn load_shared(&mut self) -> Result<(), Error> {
+        for location in self.shared.clone() {
+            let (shared, sources) = self.read_shared(&location).map_err(|error| format_err!("{}: {}", location, error))?;
+            for mut session in shared.sessions {
+                index_weeks(&mut session.weeks, shared.week_numbering)?;
+                session.source = Some(location.clone());
+                self.shared_sessions.push(session);
+            }
+            self.sources.extend(sources);
+        }
+        Ok(())
+    }
+
+    /// Read a file of shared sessions, along with the locations of the files it includes or extends
+    fn read_shared(&self, location: &str) -> Result<(Shared, Vec<String>), Error> {
+        let location = spec::resolve(location, &self.base)?;
+        let base = spec::directory(&location);
+        let (spec, included) = spec::include(spec::parse(&fetch(&location, "")?)?, &base)?;
+        let (mut spec, parents) = spec::extend(spec, &base)?;
+        spec::interpolate(&mut spec)?;
+        spec::apply_defaults(&mut spec)?;
+        if let toml::Value::Table(table) = &mut spec {
+            table.entry("version").or_insert(toml::Value::Integer(migrate::CURRENT_VERSION));
+            table.entry("week_numbering").or_insert(toml::Value::try_from(self.week_numbering)?);
+        }
+
+        let mut spec = migrate::upgrade(spec)?;
+        let first = match spec.get("week_numbering").and_then(toml::Value::as_str) {
+            Some("zero-based") => 0,
+            _ => 1,
+        };
+        let labels = self.weeks
+            .iter()
+            .enumerate()
+            .filter_map(|(index, week)| Some((week.label.clone()?, index as i64 + first)))
+            .collect();
+        spec::resolve_labels(&mut spec, &labels)?;
+        spec::separate_instances(&mut spec)?;
+        Ok((spec.try_into()?, included.into_iter().chain(parents).collect()))
+    }
+
+    /// Interpret a parsed specification, upgrading it from older versions
+    pub fn from_value(spec: toml::Value) -> Result<Self, Error> {
+        let mut spec = migrate::upgrade(spec)?;
+        spec::resolve_week_labels(&mut spec)?;
+        spec::separate_instances(&mut spec)?;
+        let offerings = spec.get("offering").and_then(toml::Value::as_array).cloned().unwrap_or_default();
+        let mut course: Course = spec.try_into()?;
+        course.offerings = offerings;
+        course.index_weeks()?;
+        course.check_priorities()?;
+        if let Some(field) = course.private.iter().find(|field| !PRIVATE_FIELDS.contains(&field.as_str())) {
+            bail!("Unknown private field {}, expected one of {}", field, PRIVATE_FIELDS.join(", "));
+        }
+        course.resolve_locations();
+        Ok(course)
+    }
+
+    /// Ensure that explicit priorities are within the range allowed by iCalendar
+    fn check_priorities(&self) -> Result<(), Error> {
+        let assignments = self.assignments.iter().map(|assignment| (&assignment.name, assignment.priority));
+        let quizzes = self.quizzes.iter().map(|quiz| (&quiz.name, quiz.priority));
+        for (name, priority) in assignments.chain(quizzes) {
+            if let Some(priority) = priority.filter(|priority| !(1..=9).contains(priority)) {
+                bail!("The priority of {} is {} but must be from 1 to 9", name, priority);
+            }
+        }
+        Ok(())
+    }
+
+    /// Look up the locations of sessions and office hours in the location table
+    fn resolve_locations(&mut self) {
+        let locations = &self.locations;
+        let resolve = |location: &Option<String>| {
+            let id = location.as_ref()?;
+            locations.get(id).map(|location| location.place(id))
+        };
+        for session in self.weeks.iter_mut().flat_map(|week| &mut week.sessions) {
+            session.place = resolve(&session.location);
+        }
+        for hours in &mut self.office_hours {
+            hours.place = resolve(&hours.location);
+        }
+    }
+
+    /// Generate the course under one of its alias codes instead of its own
+    pub fn use_alias(&mut self, code: &str) -> Result<(), Error> {
+        if code == self.code {
+            return Ok(());
+        }
+        let index = self
+            .aliases
+            .iter()
+            .position(|alias| alias == code)
+            .ok_or(format_err!("{} is not an alias of {}", code, self.code))?;
+        self.aliases[index] = std::mem::replace(&mut self.code, code.to_owned());
+        Ok(())
+    }
+
+    /// Remove private sessions and the private fields of the rest, for a public feed
+    ///
+    /// The links of assignments, which are required, point to the course instead.
+    pub fn redact(&mut self) {
+        let private = |field: &str| self.private.iter().any(|private| private == field);
+        let (location, presenters, description, link) = (private("location"), private("presenters"), private("description"), private("link"));
+        let (meeting_url, notes, attachments) = (private("meeting_url"), private("notes"), private("attachments"));
+        let (slides, recording, participants) = (private("slides"), private("recording"), private("participants"));
+
+        for week in &mut self.weeks {
+            week.sessions.retain(|session| !session.private);
+            for session in &mut week.sessions {
+                if location {
+                    session.location = None;
+                    session.place = None;
+                    session.geo = None;
+                }
+                if presenters {
+                    session.presenters.clear();
+                }
+                if description {
+                    session.description = None;
+                }
+                if link {
+                    session.link = None;
+                }
+                if meeting_url {
+                    session.meeting_url = None;
+                }
+                if notes {
+                    session.notes = None;
+                }
+                if attachments {
+                    session.attachments.clear();
+                }
+                if slides {
+                    session.slides = None;
+                }
+                if recording {
+                    session.recording = None;
+                }
+            }
+        }
+        for assignment in &mut self.assignments {
+            if description {
+                assignment.description = None;
+            }
+            if link {
+                assignment.link = self.link.clone();
+            }
+            for submission in &mut assignment.submissions {
+                if description {
+                    submission.description = None;
+                }
+                if attachments {
+                    submission.attachments.clear();
+                }
+            }
+            for milestone in &mut assignment.milestones {
+                if description {
+                    milestone.description = None;
+                }
+            }
+            for presentation in &mut assignment.presentations {
+                if description {
+                    presentation.description = None;
+                }
+                if attachments {
+                    presentation.attachments.clear();
+                }
+                if participants {
+                    presentation.participants.clear();
+                }
+            }
+        }
+        for quiz in &mut self.quizzes {
+            if description {
+                quiz.description = None;
+            }
+            if link {
+                quiz.link = None;
+            }
+        }
+        self.office_hours.retain(|hours| !hours.private);
+        for hours in &mut self.office_hours {
+            if location {
+                hours.location = None;
+                hours.place = None;
+            }
+            if presenters {
+                hours.presenters.clear();
+            }
+            if description {
+                hours.description = None;
+            }
+            if link || meeting_url {
+                hours.link = None;
+            }
+        }
+        self.redacted = true;
+    }
+
+    /// Whether private sessions and fields have been removed for a public feed
+    pub fn is_redacted(&self) -> bool {
+        self.redacted
+    }
+
+    /// Whether an event has a value in any of the fields withheld from public feeds
+    pub fn withholds(&self, event: &Event) -> bool {
+        !self.redacted && self.private.iter().any(|field| event.has_value(field))
+    }
+
+    /// Convert the week numbers used in the specification into indices
+    fn index_weeks(&mut self) -> Result<(), Error> {
+        let numbering = self.week_numbering;
+        let index = |weeks: &mut Vec<usize>| index_weeks(weeks, numbering);
+
+        for session in &mut self.repeat_sessions {
+            index(&mut session.weeks)?;
+        }
+        for assignment in &mut self.assignments {
+            for presentation in &mut assignment.presentations {
+                index(&mut presentation.weeks)?;
+            }
+        }
+        for quiz in &mut self.quizzes {
+            index(&mut quiz.weeks)?;
+        }
+        for hours in &mut self.office_hours {
+            index(&mut hours.weeks)?;
+        }
+        Ok(())
+    }
+
+    /// The number used to refer to a week in the specification
+    pub fn week_number(&self, index: usize) -> usize {
+        index + self.week_numbering.first()
+    }
+
+    /// The index of a week from its number, if it is a valid number
+    pub fn week_index(&self, number: usize) -> Option<usize> {
+        number.checked_sub(self.week_numbering.first())
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -32,19 +403,181 @@ impl Course {
         &self.code
     }
 
+    /// Write the specification back out as normalized TOML
+    pub fn to_toml(&self) -> Result<String, Error> {
+        let mut spec: toml::Value = toml::from_str(&toml::to_string(self)?)?;
+        if let (false, toml::Value::Table(table)) = (self.offerings.is_empty(), &mut spec) {
+            table.insert("offering".to_owned(), toml::Value::Array(self.offerings.clone()));
+        }
+        Ok(toml::to_string(&spec)?)
+    }
+
+    /// Generated text in the language of the course
+    pub fn text(&self) -> Text<'_> {
+        Text(Some(&self.strings))
+    }
+
+    /// Levels for validation rules configured in the specification
+    pub fn lints(&self) -> &BTreeMap<String, Level> {
+        &self.lints
+    }
+
+    /// Whether events of a kind show as busy, which they do unless configured otherwise
+    pub fn transparency(&self, kind: &str) -> Transparency {
+        self.transparency.get(kind).copied().unwrap_or(Transparency::Opaque)
+    }
+
+    /// The number of weeks in the course
+    pub fn week_count(&self) -> usize {
+        self.weeks.len()
+    }
+
+    /// The start of a particular week
+    pub fn week_start(&self, week: usize) -> Option<DateTime<FixedOffset>> {
+        self.weeks.get(week).map(|week| week.start)
+    }
+
+    /// Every group given a presentation slot, in order of first appearance
+    pub fn groups(&self) -> Vec<&str> {
+        let mut groups = Vec::new();
+        for presentation in self.assignments.iter().flat_map(|assignment| &assignment.presentations) {
+            for group in &presentation.groups {
+                if !groups.contains(&group.as_str()) {
+                    groups.push(group.as_str());
+                }
+            }
+        }
+        groups
+    }
+
+    /// The groups of each presentation that don't fit in its sessions
+    pub fn unallocated_groups(&self) -> Vec<(String, &[String])> {
+        self.assignments
+            .iter()
+            .flat_map(|assignment| assignment.presentations.iter().map(move |presentation| (assignment, presentation)))
+            .map(|(assignment, presentation)| (format!("{}: {}", assignment.name, presentation.name), presentation.slots(assignment, self).1))
+            .filter(|(_, groups)| !groups.is_empty())
+            .collect()
+    }
+
+    /// The time needed to move between buildings, 10 minutes if not given
+    pub fn travel_time(&self) -> Duration {
+        self.travel_time.unwrap_or_else(|| Duration::minutes(10))
+    }
+
+    /// The name by which a week may be referred to, such as Flexibility Week, if it has one
+    pub fn week_label(&self, week: usize) -> Option<&str> {
+        self.weeks.get(week).and_then(|week| week.label.as_deref())
+    }
+
+    /// The topic of a week, if it has one
+    pub fn week_topic(&self, week: usize) -> Option<&str> {
+        self.weeks.get(week).and_then(|week| week.topic.as_deref())
+    }
+
+    /// The preparation tasks of every week, with the index of their week
+    pub fn prep(&self) -> Vec<(usize, &str)> {
+        self.weeks
+            .iter()
+            .enumerate()
+            .flat_map(|(index, week)| week.prep.iter().map(move |task| (index, task.as_str())))
+            .collect()
+    }
+
+    /// The start of the first week of the course
+    pub fn term_start(&self) -> Option<DateTime<FixedOffset>> {
+        self.weeks.first().map(|week| week.start)
+    }
+
+    /// Every reference to a week by a repeated session, presentation, or quiz
+    pub fn week_references(&self) -> Vec<(String, usize)> {
+        let sessions = self.repeat_sessions.iter().flat_map(|session| {
+            session.weeks.iter().map(move |week| (format!("repeated {} session", session.kind), *week))
+        });
+        let presentations = self.assignments.iter().flat_map(|assignment| {
+            assignment.presentations.iter().flat_map(move |presentation| {
+                presentation.weeks.iter().map(move |week| {
+                    (format!("{}: {} presentation", assignment.name, presentation.name), *week)
+                })
+            })
+        });
+        let quizzes = self.quizzes.iter().flat_map(|quiz| {
+            quiz.weeks.iter().map(move |week| (format!("{} quiz", quiz.name), *week))
+        });
+        let office_hours = self.office_hours.iter().flat_map(|hours| {
+            hours.weeks.iter().map(move |week| (format!("{} office hours", hours.weekday), *week))
+        });
+        sessions.chain(presentations).chain(quizzes).chain(office_hours).collect()
+    }
+
+    /// The name of each assignment and quiz with its value, if given
+    pub fn assignment_values(&self) -> Vec<(&str, Option<u64>)> {
+        let assignments = self.assignments.iter().map(|assignment| (assignment.name.as_str(), assignment.value));
+        let quizzes = self.quizzes.iter().map(|quiz| (quiz.name.as_str(), quiz.value));
+        assignments.chain(quizzes).collect()
+    }
+
+    /// The total expected of the values of the assignments, 100 by default
+    pub fn assessment_total(&self) -> u64 {
+        self.assessment_total.unwrap_or(100)
+    }
+
+    /// The kind of session in which each presentation is held
+    pub fn presentation_kinds(&self) -> Vec<(String, &str)> {
+        self.assignments.iter()
+            .flat_map(|assignment| {
+                assignment.presentations.iter().map(move |presentation| {
+                    (format!("{}: {} presentation", assignment.name, presentation.name), presentation.session.as_str())
+                })
+            })
+            .collect()
+    }
+
+    /// Additional properties to emit verbatim on the calendar
+    pub fn extra(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.extra.iter().map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    /// Local files the course is generated from, other than its specification
+    pub fn dependencies(&self) -> Vec<PathBuf> {
+        let sources = self.sources.iter().filter(|location| !is_url(location)).map(PathBuf::from);
+        self.merge
+            .iter()
+            .chain(&self.shared)
+            .filter(|location| !is_url(location) && !is_url(&self.base))
+            .map(|location| Path::new(&self.base).join(location))
+            .chain(sources)
+            .collect()
+    }
+
+    /// Read the external calendars to merge into the output
+    pub fn merged_calendars(&self) -> Result<Vec<ical::Component>, Error> {
+        let mut calendars = Vec::new();
+        for location in &self.merge {
+            let source = fetch(location, &self.base)?;
+            calendars.extend(ical::parse(&source)?.into_iter().filter(|component| component.name == "VCALENDAR"));
+        }
+        Ok(calendars)
+    }
+
     /// Generate all repeated sessions in the course
     pub fn generate_repeats(&mut self) -> Result<(), Error> {
         let mut sessions = Vec::new();
 
-        for session in &self.repeat_sessions {
-            let first_week = if let Some(first) = session.weeks.get(0) {
-                self.weeks.get(*first).ok_or(format_err!("Requested repeat of {} session in non-existent week {}", session.kind, first))?.start
+        for session in self.repeat_sessions.iter().chain(&self.shared_sessions) {
+            for additional in &session.additional {
+                let week = self.week_of(additional.time).ok_or(format_err!("Additional {} session at {} is before the first week", session.kind, additional.time))?;
+                sessions.push((week, session.additional(additional)));
+            }
+
+            let first_week = if let Some(first) = session.weeks.first() {
+                self.weeks.get(*first).ok_or(format_err!("Requested repeat of {} session in non-existent week {}", session.kind, self.week_number(*first)))?.start
             } else {
                 continue;
             };
 
             for week_no in &session.weeks {
-                let week = self.weeks.get(*week_no).ok_or(format_err!("Tried to schedule repeat of {} session in non-existent week {}", session.kind, week_no))?;
+                let week = self.weeks.get(*week_no).ok_or(format_err!("Tried to schedule repeat of {} session in non-existent week {}", session.kind, self.week_number(*week_no)))?;
                 let duplicate = session.duplicate(first_week, week.start);
                 sessions.push((*week_no, duplicate));
             }
@@ -53,122 +586,518 @@ impl Course {
         for (week, session) in sessions.drain(..) {
             self.weeks[week].sessions.push(session);
         }
+        for week in self.weeks.iter_mut().filter(|week| week.tentative) {
+            for session in &mut week.sessions {
+                session.tentative = true;
+            }
+        }
+        self.resolve_locations();
 
         Ok(())
     }
 
     /// Generate an iterator over the events in chronological order
-    pub fn events(&self) -> impl Iterator<Item = Event> {
+    pub fn events(&self) -> impl Iterator<Item = Event<'_>> {
         let mut events = Vec::new();
 
-        for week in &self.weeks {
+        for (index, week) in self.weeks.iter().enumerate() {
             for session in &week.sessions {
-                events.push(session.into());
+                let mut event: Event = session.into();
+                event.week = Some(index);
+                events.push(event);
             }
         }
 
         for assignment in &self.assignments {
-            events.extend(assignment.events(&self));
+            events.extend(assignment.events(self).map(|mut event| {
+                event.week = self.week_of(event.start);
+                event
+            }));
+        }
+
+        for quiz in &self.quizzes {
+            events.extend(quiz.events(self));
+        }
+
+        for hours in &self.office_hours {
+            events.extend(hours.events(self));
         }
 
         events.sort();
+        for event in &mut events {
+            event.first_week = self.week_numbering.first();
+            event.text = self.text();
+        }
         events.into_iter()
     }
+
+    /// Find the index of the week containing a particular time
+    pub fn week_of(&self, time: DateTime<FixedOffset>) -> Option<usize> {
+        self.weeks.iter().rposition(|week| week.start <= time)
+    }
 }
 
-/// A week with interactive sessions
+/// Generated text in the language of a course, which is English unless it is translated
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Text<'c>(Option<&'c BTreeMap<String, String>>);
+
+impl<'c> Text<'c> {
+    /// The translation of some English text, or the text itself if it has none
+    pub fn get(self, english: &'c str) -> &'c str {
+        self.0.and_then(|strings| strings.get(english)).map(String::as_str).unwrap_or(english)
+    }
+}
+
+/// Whether an event occupies time in free/busy searches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Transparency {
+    /// The event makes its attendees busy, as for compulsory classes
+    Opaque,
+    /// The event leaves its attendees free, as for optional drop-in help
+    Transparent,
+}
+
+/// Convert week numbers counting from the first number into indices
+fn index_weeks(weeks: &mut [usize], numbering: WeekNumbering) -> Result<(), Error> {
+    let first = numbering.first();
+    for week in weeks.iter_mut() {
+        *week = week.checked_sub(first).ok_or(format_err!("Weeks are numbered from {}", first))?;
+    }
+    Ok(())
+}
+
+/// How the weeks of a course are numbered in its specification
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum WeekNumbering {
+    ZeroBased,
+    #[default]
+    OneBased,
+}
+
+impl WeekNumbering {
+    /// The number of the first week
+    pub fn first(self) -> usize {
+        match self {
+            WeekNumbering::ZeroBased => 0,
+            WeekNumbering::OneBased => 1,
+        }
+    }
+}
+
+
+/// Sessions defined once and shared by cross-listed courses
 #[derive(Debug, Clone, Deserialize)]
+struct Shared {
+    #[serde(default)]
+    week_numbering: WeekNumbering,
+    #[serde(rename = "session", default)]
+    sessions: Vec<RepeatSession>,
+}
+
+/// A week with interactive sessions
+#[derive(Debug, Clone, Deserialize, Serialize)]
 struct Week {
-    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(deserialize_with = "deserialize_datetime", serialize_with = "serialize_datetime")]
     start: DateTime<FixedOffset>,
-    #[serde(rename = "session", default)]
+    /// A name by which the week may be referred to, such as Flexibility Week
+    #[serde(skip_serializing_if = "Option::is_none")]
+    label: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    topic: Option<String>,
+    /// The times and rooms of every session in the week are yet to be confirmed
+    #[serde(default, skip_serializing_if = "is_false")]
+    tentative: bool,
+    /// Reading and preparation to do during the week
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    prep: Vec<String>,
+    #[serde(rename = "session", default, skip_serializing_if = "Vec::is_empty")]
     sessions: Vec<Session>,
 }
 
 /// An interactive session such as a lecture, tutorial, lab, or seminar
-#[derive(Debug, Clone, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
 struct Session {
-    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(deserialize_with = "deserialize_datetime", serialize_with = "serialize_datetime")]
     time: DateTime<FixedOffset>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    /// What the session covers, recorded in a journal entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    topic: Option<String>,
+    /// Notes from the session, recorded in a journal entry
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notes: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_url", serialize_with = "serialize_optional_url", skip_serializing_if = "Option::is_none")]
+    link: Option<Url>,
+    #[serde(default, deserialize_with = "deserialize_urls", serialize_with = "serialize_urls", skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<Url>,
+    #[serde(default, deserialize_with = "deserialize_optional_url", serialize_with = "serialize_optional_url", skip_serializing_if = "Option::is_none")]
+    recording: Option<Url>,
+    #[serde(default, deserialize_with = "deserialize_optional_url", serialize_with = "serialize_optional_url", skip_serializing_if = "Option::is_none")]
+    slides: Option<Url>,
+    /// A link to join the session online, such as a video meeting
+    #[serde(default, deserialize_with = "deserialize_optional_url", serialize_with = "serialize_optional_url", skip_serializing_if = "Option::is_none")]
+    meeting_url: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     location: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     presenters: Vec<String>,
     kind: String,
-    #[serde(deserialize_with = "deserialize_duration")]
+    /// The group of alternative classes this session belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<String>,
+    #[serde(deserialize_with = "deserialize_duration", serialize_with = "serialize_duration")]
     duration: Duration,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// Equipment needed for the session, such as a projector
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    resources: Vec<String>,
+    /// How long before the session to remind attendees
+    #[serde(default, deserialize_with = "deserialize_durations", serialize_with = "serialize_durations", skip_serializing_if = "Vec::is_empty")]
+    reminders: Vec<Duration>,
+    /// The latitude and longitude of the location
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geo: Option<Geo>,
+    /// Only staff see the session, and it is left out of public feeds
+    #[serde(default, skip_serializing_if = "is_false")]
+    private: bool,
+    /// The time and room are yet to be confirmed
+    #[serde(default, skip_serializing_if = "is_false")]
+    tentative: bool,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    extra: BTreeMap<String, String>,
+    /// The entry in the location table named by the location, once resolved
+    #[serde(skip)]
+    place: Option<Place>,
+    /// The shared file the session was defined in, if any
+    #[serde(skip)]
+    shared: Option<String>,
+    /// Whether the session is an additional session of a series
+    #[serde(skip)]
+    additional: bool,
 }
 
 impl Session {
     fn location(&self) -> Option<&str> {
-        self.location.as_ref().map(|s| s.as_str())
+        match &self.place {
+            Some(place) => Some(&place.text),
+            None => self.location.as_deref(),
+        }
     }
 
     fn presenters(&self) -> Vec<&str> {
         self.presenters.iter().map(|s| s.as_str()).collect::<Vec<_>>()
     }
+
+    fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    fn link(&self) -> Option<&Url> {
+        self.link.as_ref().or(self.meeting_url.as_ref())
+    }
+
+    /// The description including links to the slides and recording
+    fn full_description(&self, text: Text) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(description) = self.description() {
+            parts.push(description.to_owned());
+        }
+        if let Some(slides) = &self.slides {
+            parts.push(format!("{}: <{}>", text.get("Slides"), slides));
+        }
+        if let Some(recording) = &self.recording {
+            parts.push(format!("{}: <{}>", text.get("Recording"), recording));
+        }
+        if let Some(map) = self.place.as_ref().and_then(|place| place.map.as_ref()) {
+            parts.push(format!("{}: <{}>", text.get("Map"), map));
+        }
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("\n\n"))
+        }
+    }
+
+    fn attachments(&self) -> Vec<&Url> {
+        self.attachments.iter()
+            .chain(self.slides.iter())
+            .chain(self.recording.iter())
+            .collect()
+    }
 }
 
 /// An interactive session that repeats in multiple weeks
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 struct RepeatSession {
-    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(deserialize_with = "deserialize_datetime", serialize_with = "serialize_datetime")]
     first: DateTime<FixedOffset>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_url", serialize_with = "serialize_optional_url", skip_serializing_if = "Option::is_none")]
+    link: Option<Url>,
+    #[serde(default, deserialize_with = "deserialize_urls", serialize_with = "serialize_urls", skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<Url>,
+    #[serde(default, deserialize_with = "deserialize_optional_url", serialize_with = "serialize_optional_url", skip_serializing_if = "Option::is_none")]
+    recording: Option<Url>,
+    #[serde(default, deserialize_with = "deserialize_optional_url", serialize_with = "serialize_optional_url", skip_serializing_if = "Option::is_none")]
+    slides: Option<Url>,
+    /// A link to join the session online, such as a video meeting
+    #[serde(default, deserialize_with = "deserialize_optional_url", serialize_with = "serialize_optional_url", skip_serializing_if = "Option::is_none")]
+    meeting_url: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     location: Option<String>,
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
     presenters: Vec<String>,
     kind: String,
-    #[serde(deserialize_with = "deserialize_duration")]
+    /// The group of alternative classes this session belongs to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<String>,
+    #[serde(deserialize_with = "deserialize_duration", serialize_with = "serialize_duration")]
     duration: Duration,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    resources: Vec<String>,
+    #[serde(default, deserialize_with = "deserialize_durations", serialize_with = "serialize_durations", skip_serializing_if = "Vec::is_empty")]
+    reminders: Vec<Duration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geo: Option<Geo>,
+    #[serde(default, skip_serializing_if = "is_false")]
+    private: bool,
+    #[serde(default, skip_serializing_if = "is_false")]
+    tentative: bool,
+    #[serde(serialize_with = "serialize_week_numbers")]
     weeks: Vec<usize>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    extra: BTreeMap<String, String>,
+    /// Single sessions of the series outside of its weeks, such as a revision lecture, given as
+    /// [[session.extra]] or, alongside iCalendar properties, [[session.additional]]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    additional: Vec<AdditionalSession>,
+    /// The shared file the session was read from, if any
+    #[serde(skip)]
+    source: Option<String>,
 }
 
 impl RepeatSession {
     fn duplicate(&self, first_week: DateTime<FixedOffset>, week_start: DateTime<FixedOffset>) -> Session {
-        let offset = self.first - first_week;
+        self.at(week_start + (self.first - first_week))
+    }
 
+    /// A single session of the series with the fields that it overrides
+    fn additional(&self, additional: &AdditionalSession) -> Session {
+        let mut session = self.at(additional.time);
+        session.title = additional.title.clone().or(session.title);
+        session.description = additional.description.clone().or(session.description);
+        session.location = additional.location.clone().or(session.location);
+        session.duration = additional.duration.unwrap_or(session.duration);
+        session.additional = true;
+        session
+    }
+
+    fn at(&self, time: DateTime<FixedOffset>) -> Session {
         Session {
             kind: self.kind.clone(),
+            stream: self.stream.clone(),
             title: self.title.clone(),
+            description: self.description.clone(),
+            topic: None,
+            notes: None,
+            link: self.link.clone(),
+            attachments: self.attachments.clone(),
+            recording: self.recording.clone(),
+            slides: self.slides.clone(),
+            meeting_url: self.meeting_url.clone(),
             presenters: self.presenters.clone(),
             location: self.location.clone(),
-            time: week_start + offset,
+            time,
             duration: self.duration,
+            tags: self.tags.clone(),
+            resources: self.resources.clone(),
+            reminders: self.reminders.clone(),
+            geo: self.geo,
+            private: self.private,
+            tentative: self.tentative,
+            extra: self.extra.clone(),
+            place: None,
+            shared: self.source.clone(),
+            additional: false,
         }
     }
 }
 
+/// A session of a series at a time that is not tied to its weeks
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct AdditionalSession {
+    #[serde(deserialize_with = "deserialize_datetime", serialize_with = "serialize_datetime")]
+    time: DateTime<FixedOffset>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_duration", serialize_with = "serialize_optional_duration", skip_serializing_if = "Option::is_none")]
+    duration: Option<Duration>,
+}
+
+/// A room in the location table
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct Location {
+    /// The name to show, such as the room code, defaulting to the identifier
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    building: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    address: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_url", serialize_with = "serialize_optional_url", skip_serializing_if = "Option::is_none")]
+    map: Option<Url>,
+    /// The latitude and longitude of the room
+    #[serde(skip_serializing_if = "Option::is_none")]
+    geo: Option<Geo>,
+}
+
+impl Location {
+    fn place(&self, id: &str) -> Place {
+        let parts = [Some(self.name.as_deref().unwrap_or(id)), self.building.as_deref(), self.address.as_deref()];
+        Place {
+            text: parts.iter().flatten().cloned().collect::<Vec<_>>().join(", "),
+            map: self.map.clone(),
+            geo: self.geo,
+            building: self.building.clone(),
+        }
+    }
+}
+
+/// A location from the table as it is shown on events
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct Place {
+    /// The name, building, and address
+    text: String,
+    map: Option<Url>,
+    geo: Option<Geo>,
+    building: Option<String>,
+}
+
+/// A latitude and longitude in degrees, written as a pair
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Geo(pub f64, pub f64);
+
+impl PartialEq for Geo {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Geo {}
+
+impl PartialOrd for Geo {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Geo {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.total_cmp(&other.1))
+    }
+}
+
 /// An assignment with presentations and submissions
-#[derive(Debug, Clone, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
 struct Assignment {
     name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
-    #[serde(deserialize_with = "deserialize_url")]
+    #[serde(deserialize_with = "deserialize_url", serialize_with = "serialize_url")]
     link: Url,
+    #[serde(skip_serializing_if = "Option::is_none")]
     value: Option<u64>,
-    #[serde(rename = "submission", default)]
+    /// The iCalendar priority from 1 (highest) to 9, derived from the value if absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// When the specification becomes available
+    #[serde(default, deserialize_with = "deserialize_optional_datetime", serialize_with = "serialize_optional_datetime", skip_serializing_if = "Option::is_none")]
+    released: Option<DateTime<FixedOffset>>,
+    #[serde(rename = "submission", default, skip_serializing_if = "Vec::is_empty")]
     submissions: Vec<Submission>,
-    #[serde(rename = "presentation", default)]
+    #[serde(rename = "presentation", default, skip_serializing_if = "Vec::is_empty")]
     presentations: Vec<Presentation>,
+    #[serde(rename = "milestone", default, skip_serializing_if = "Vec::is_empty")]
+    milestones: Vec<Milestone>,
 }
 
 impl Assignment {
     fn events<'c>(&'c self, course: &'c Course) -> impl Iterator<Item = Event<'c>> {
         let presentations = self.presentations.iter()
+            .filter(|p| p.slot.is_none())
             .flat_map(|p| p.weeks.iter().map(move |w| (*w, p)))
             .flat_map(move |(w, p)| course.weeks.get(w).map(|w| (w, p)))
             .flat_map(move |(w, p)| w.sessions.iter().map(move |s| (s, p)));
 
-        AssignmentEvents {
-            assignment: &self,
-            submissions: self.submissions.iter(),
-            presentations,
-        }
+        let countdowns = self.submissions.iter().flat_map(move |submission| {
+            submission.countdown.iter().filter_map(move |countdown| {
+                Some(Event {
+                    start: submission.time - parse_period(countdown)?,
+                    base: EventBase::Countdown(self, submission, countdown),
+                    week: None,
+                    first_week: 1,
+                    text: Text::default(),
+                })
+            })
+        });
+
+        let milestones = self.milestones.iter().map(move |milestone| Event {
+            start: milestone.time,
+            base: EventBase::Milestone(self, milestone),
+            week: None,
+            first_week: 1,
+            text: Text::default(),
+        });
+
+        let release = self.released.map(|released| Event {
+            start: released,
+            base: EventBase::Release(self),
+            week: None,
+            first_week: 1,
+            text: Text::default(),
+        });
+
+        release
+            .into_iter()
+            .chain(AssignmentEvents {
+                assignment: self,
+                submissions: self.submissions.iter(),
+                presentations,
+            })
+            .chain(countdowns)
+            .chain(milestones)
+            .chain(self.presentations.iter().flat_map(move |p| p.slots(self, course).0))
+            .chain(self.submissions.iter().filter_map(move |submission| {
+                Some(Event {
+                    start: submission.late_cutoff()?,
+                    base: EventBase::Late(self, submission),
+                    week: None,
+                    first_week: 1,
+                    text: Text::default(),
+                })
+            }))
     }
 
     fn description(&self) -> Option<&str> {
-        self.description.as_ref().map(|s| s.as_str())
+        self.description.as_deref()
     }
 }
 
@@ -191,6 +1120,9 @@ where
                 let event = Event {
                     start: submission.time,
                     base: EventBase::Submission(self.assignment, submission),
+                    week: None,
+                    first_week: 1,
+                    text: Text::default(),
                 };
                 break Some(event);
             } else if let Some((session, presentation)) = self.presentations.next() {
@@ -201,6 +1133,9 @@ where
                 let event = Event {
                     start: session.time,
                     base: EventBase::Presentation(self.assignment, presentation, session),
+                    week: None,
+                    first_week: 1,
+                    text: Text::default(),
                 };
                 break Some(event);
             } else {
@@ -210,33 +1145,309 @@ where
     }
 }
 
+/// A quiz that opens and closes at the same times in several weeks
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+struct Quiz {
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_optional_url", serialize_with = "serialize_optional_url", skip_serializing_if = "Option::is_none")]
+    link: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<u64>,
+    /// The iCalendar priority from 1 (highest) to 9, derived from the value if absent
+    #[serde(skip_serializing_if = "Option::is_none")]
+    priority: Option<u8>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// When the quiz opens in the first of its weeks
+    #[serde(deserialize_with = "deserialize_datetime", serialize_with = "serialize_datetime")]
+    opens: DateTime<FixedOffset>,
+    /// When the quiz closes in the first of its weeks
+    #[serde(deserialize_with = "deserialize_datetime", serialize_with = "serialize_datetime")]
+    closes: DateTime<FixedOffset>,
+    #[serde(serialize_with = "serialize_week_numbers")]
+    weeks: Vec<usize>,
+}
+
+impl Quiz {
+    /// The opening and closing of the quiz in each of its weeks
+    fn events<'c>(&'c self, course: &'c Course) -> impl Iterator<Item = Event<'c>> {
+        let first_week = self.weeks.first().and_then(|week| course.week_start(*week));
+        self.weeks.iter().flat_map(move |week| {
+            let offset = course.week_start(*week).zip(first_week).map(|(start, first)| start - first);
+            offset.into_iter().flat_map(move |offset| {
+                [(QuizMoment::Opens, self.opens), (QuizMoment::Closes, self.closes)].map(|(moment, time)| Event {
+                    start: time + offset,
+                    base: EventBase::Quiz(self, moment),
+                    week: Some(*week),
+                    first_week: 1,
+                    text: Text::default(),
+                })
+            })
+        })
+    }
+}
+
+/// Consultation held at the same time every week, by weekday rather than date
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+struct OfficeHours {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    /// The name of a day of the week, checked when loaded
+    #[serde(deserialize_with = "deserialize_weekday")]
+    weekday: String,
+    #[serde(deserialize_with = "deserialize_time", serialize_with = "serialize_time")]
+    time: NaiveTime,
+    #[serde(deserialize_with = "deserialize_duration", serialize_with = "serialize_duration")]
+    duration: Duration,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    presenters: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    /// A link to join online, which doubles as the location if there is none
+    #[serde(default, deserialize_with = "deserialize_optional_url", serialize_with = "serialize_optional_url", skip_serializing_if = "Option::is_none")]
+    link: Option<Url>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+    /// The weeks with office hours, or every week if empty
+    #[serde(default, serialize_with = "serialize_week_numbers", skip_serializing_if = "Vec::is_empty")]
+    weeks: Vec<usize>,
+    /// Only staff see the office hours, and they are left out of public feeds
+    #[serde(default, skip_serializing_if = "is_false")]
+    private: bool,
+    /// The entry in the location table named by the location, once resolved
+    #[serde(skip)]
+    place: Option<Place>,
+}
+
+impl OfficeHours {
+    fn events<'c>(&'c self, course: &'c Course) -> impl Iterator<Item = Event<'c>> {
+        let weeks = if self.weeks.is_empty() { (0..course.weeks.len()).collect() } else { self.weeks.clone() };
+        weeks.into_iter().filter_map(move |index| {
+            let start = course.weeks.get(index)?.start;
+            let monday = start.date().naive_local() - Duration::days(start.weekday().num_days_from_monday() as i64);
+            let weekday = self.weekday.parse::<Weekday>().ok()?;
+            let day = monday + Duration::days(weekday.num_days_from_monday() as i64);
+            Some(Event {
+                start: start.offset().from_local_datetime(&day.and_time(self.time)).single()?,
+                base: EventBase::OfficeHours(self),
+                week: Some(index),
+                first_week: 1,
+                text: Text::default(),
+            })
+        })
+    }
+
+    fn location(&self) -> Option<&str> {
+        match &self.place {
+            Some(place) => Some(&place.text),
+            None => self.location.as_deref().or(self.link.as_ref().map(Url::as_str)),
+        }
+    }
+
+    /// The description including a link to the map of the location
+    fn full_description(&self, text: Text) -> Option<String> {
+        match self.place.as_ref().and_then(|place| place.map.as_ref()) {
+            Some(map) => Some(match &self.description {
+                Some(description) => format!("{}\n\n{}: <{}>", description, text.get("Map"), map),
+                None => format!("{}: <{}>", text.get("Map"), map),
+            }),
+            None => self.description.clone(),
+        }
+    }
+}
+
+/// Whether an event is the opening or closing of a quiz
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum QuizMoment {
+    Opens,
+    Closes,
+}
+
+impl QuizMoment {
+    fn as_str(self) -> &'static str {
+        match self {
+            QuizMoment::Opens => "opens",
+            QuizMoment::Closes => "closes",
+        }
+    }
+}
+
+impl fmt::Display for QuizMoment {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
 /// A submission deadline for an assignment
-#[derive(Debug, Clone, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
 struct Submission {
-    #[serde(deserialize_with = "deserialize_datetime")]
+    #[serde(deserialize_with = "deserialize_datetime", serialize_with = "serialize_datetime")]
     time: DateTime<FixedOffset>,
     name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_urls", serialize_with = "serialize_urls", skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<Url>,
+    /// How long before the deadline to announce it, such as `7d` or `12h`
+    #[serde(default, deserialize_with = "deserialize_countdown", skip_serializing_if = "Vec::is_empty")]
+    countdown: Vec<String>,
+    /// The last time at which late submissions are accepted
+    #[serde(default, deserialize_with = "deserialize_optional_datetime", serialize_with = "serialize_optional_datetime", skip_serializing_if = "Option::is_none")]
+    late_until: Option<DateTime<FixedOffset>>,
+    /// How long after the deadline late submissions are accepted, unless `late_until` is given
+    #[serde(default, deserialize_with = "deserialize_period", skip_serializing_if = "Option::is_none")]
+    grace: Option<String>,
+    /// The penalty applied to late submissions
+    #[serde(skip_serializing_if = "Option::is_none")]
+    penalty: Option<String>,
 }
 
 impl Submission {
     fn description(&self) -> Option<&str> {
-        self.description.as_ref().map(|s| s.as_str())
+        self.description.as_deref()
+    }
+
+    /// When late submissions are no longer accepted, if they are at all
+    fn late_cutoff(&self) -> Option<DateTime<FixedOffset>> {
+        self.late_until.or_else(|| Some(self.time + parse_period(self.grace.as_ref()?)?))
+    }
+
+    /// A note of the late submission policy, appended to descriptions
+    fn late_policy(&self, text: Text) -> Option<String> {
+        let cutoff = self.late_cutoff()?;
+        let mut policy = format!("{} {}.", text.get("Late submissions are accepted until"), cutoff.format("%A %-d %B at %H:%M"));
+        if let Some(penalty) = &self.penalty {
+            policy.push_str(&format!("\n\n{}", penalty));
+        }
+        Some(policy)
     }
 }
 
+/// Parse a period of time such as `7d`, `12h`, or `2w`
+fn parse_period(value: &str) -> Option<Duration> {
+    let amount = value.get(..value.len().checked_sub(1)?)?.parse::<i64>().ok()?;
+    match value.chars().last()? {
+        'w' => Some(Duration::weeks(amount)),
+        'd' => Some(Duration::days(amount)),
+        'h' => Some(Duration::hours(amount)),
+        'm' => Some(Duration::minutes(amount)),
+        _ => None,
+    }
+}
+
+/// Describe a period of time in words, such as `7 days`
+fn describe_period(value: &str, text: Text) -> String {
+    let (amount, unit) = value.split_at(value.len() - 1);
+    let (one, many) = match unit {
+        "w" => ("week", "weeks"),
+        "d" => ("day", "days"),
+        "h" => ("hour", "hours"),
+        _ => ("minute", "minutes"),
+    };
+    format!("{} {}", amount, text.get(if amount == "1" { one } else { many }))
+}
+
+/// An intermediate checkpoint of an assignment, such as a design review
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
+struct Milestone {
+    #[serde(deserialize_with = "deserialize_datetime", serialize_with = "serialize_datetime")]
+    time: DateTime<FixedOffset>,
+    name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+}
+
 /// A presentation within a particular session
-#[derive(Debug, Clone, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Deserialize, Serialize, PartialEq, Eq, PartialOrd, Ord)]
 struct Presentation {
     name: String,
     session: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
     description: Option<String>,
+    #[serde(default, deserialize_with = "deserialize_urls", serialize_with = "serialize_urls", skip_serializing_if = "Vec::is_empty")]
+    attachments: Vec<Url>,
+    #[serde(serialize_with = "serialize_week_numbers")]
     weeks: Vec<usize>,
+    /// The length of the slot given to each group, dividing sessions into slots
+    #[serde(default, deserialize_with = "deserialize_optional_duration", serialize_with = "serialize_optional_duration", skip_serializing_if = "Option::is_none")]
+    slot: Option<Duration>,
+    /// The groups presenting, in the order in which they are given slots
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    groups: Vec<String>,
+    /// The members of each group
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    participants: BTreeMap<String, Vec<String>>,
 }
 
 impl Presentation {
     fn description(&self) -> Option<&str> {
-        self.description.as_ref().map(|s| s.as_str())
+        self.description.as_deref()
+    }
+
+    /// The description of a presentation by some groups, naming who is presenting
+    fn full_description(&self, assignment: &Assignment, groups: &[String], text: Text) -> Option<String> {
+        let mut parts = Vec::new();
+        if let Some(description) = self.description().or_else(|| assignment.description()) {
+            parts.push(description.to_owned());
+        }
+        let presenting = groups
+            .iter()
+            .map(|group| match self.participants.get(group) {
+                Some(members) if !members.is_empty() => format!("{} ({})", group, members.join(", ")),
+                _ => group.clone(),
+            })
+            .collect::<Vec<_>>();
+        if !presenting.is_empty() {
+            parts.push(format!("{}: {}", text.get("Presenting"), presenting.join(", ")));
+        }
+
+        if parts.is_empty() {
+            None
+        } else {
+            Some(parts.join("\n\n"))
+        }
+    }
+
+    /// The sessions in which the presentation is held, in chronological order
+    fn sessions<'c>(&self, course: &'c Course) -> Vec<&'c Session> {
+        let mut sessions = self
+            .weeks
+            .iter()
+            .filter_map(|week| course.weeks.get(*week))
+            .flat_map(|week| &week.sessions)
+            .filter(|session| session.kind == self.session)
+            .collect::<Vec<_>>();
+        sessions.sort_by_key(|session| session.time);
+        sessions
+    }
+
+    /// Divide the sessions into slots, filling each session in turn with the groups in order
+    ///
+    /// Also returns the groups for which there was no room.
+    fn slots<'c>(&'c self, assignment: &'c Assignment, course: &'c Course) -> (Vec<Event<'c>>, &'c [String]) {
+        let length = match self.slot {
+            Some(length) if length > Duration::zero() => length,
+            _ => return (Vec::new(), &[]),
+        };
+        let mut events = Vec::new();
+        for session in self.sessions(course) {
+            let capacity = (session.duration.num_seconds() / length.num_seconds()) as usize;
+            for position in 0..capacity.min(self.groups.len() - events.len()) {
+                events.push(Event {
+                    start: session.time + length * position as i32,
+                    base: EventBase::Slot(assignment, self, session, events.len()),
+                    week: None,
+                    first_week: 1,
+                    text: Text::default(),
+                });
+            }
+        }
+        let allocated = events.len();
+        (events, &self.groups[allocated..])
     }
 }
 
@@ -244,6 +1455,10 @@ impl Presentation {
 pub struct Event<'c> {
     start: DateTime<FixedOffset>,
     base: EventBase<'c>,
+    week: Option<usize>,
+    /// The number of the first week of the course
+    first_week: usize,
+    text: Text<'c>,
 }
 
 impl<'c> Event<'c> {
@@ -251,12 +1466,24 @@ impl<'c> Event<'c> {
         self.start
     }
 
+    /// The index of the week in which the event occurs
+    pub fn week(&self) -> Option<usize> {
+        self.week
+    }
+
+    /// The number of the week in which the event occurs, as the course numbers it
+    pub fn week_number(&self) -> Option<usize> {
+        self.week.map(|week| week + self.first_week)
+    }
+
     pub fn duration(&self) -> Duration {
         use EventBase::*;
         match self.base {
             Session(s) => s.duration,
-            Submission(_, _) => Duration::minutes(5),
+            Submission(_, _) | Late(_, _) | Release(_) | Countdown(_, _, _) | Milestone(_, _) | Quiz(_, _) => Duration::minutes(5),
+            OfficeHours(h) => h.duration,
             Presentation(_, _, s) => s.duration,
+            Slot(_, p, s, _) => p.slot.unwrap_or(s.duration),
         }
     }
 
@@ -266,44 +1493,341 @@ impl<'c> Event<'c> {
 
     pub fn title(&self) -> String {
         match self.base {
-            EventBase::Session(Session { title: Some(title), kind, .. }) => format!("{} ({})", title, kind),
-            EventBase::Session(Session { kind, .. }) => format!("({})", kind),
-            EventBase::Submission(a, s) => format!("{}: {} (submission)", a.name, s.name),
-            EventBase::Presentation(a, p, _) => format!("{}: {} (presentation)", a.name, p.name),
+            EventBase::Session(Session { title: None, kind, .. }) => format!("({})", self.text.get(kind)),
+            _ => format!("{} ({})", self.name(), self.text.get(self.kind())),
+        }
+    }
+
+    /// The title of the event without its kind
+    pub fn name(&self) -> String {
+        let text = self.text;
+        match self.base {
+            EventBase::Session(Session { title: Some(title), .. }) => title.clone(),
+            EventBase::Session(Session { kind, .. }) => text.get(kind).to_owned(),
+            EventBase::Submission(a, s) => format!("{}: {}", a.name, s.name),
+            EventBase::Late(a, s) => format!("{}: {} {}", a.name, s.name, text.get("late cutoff")),
+            EventBase::Release(a) => format!("{} {}", a.name, text.get("spec released")),
+            EventBase::Countdown(a, s, c) => format!("{}: {} {} {}", a.name, s.name, text.get("due in"), describe_period(c, text)),
+            EventBase::Milestone(a, m) => format!("{}: {}", a.name, m.name),
+            EventBase::Presentation(a, p, _) => format!("{}: {}", a.name, p.name),
+            EventBase::Slot(a, p, _, g) => format!("{}: {} {}", a.name, p.groups[g], p.name),
+            EventBase::Quiz(q, moment) => format!("{} {}", q.name, text.get(moment.as_str())),
+            EventBase::OfficeHours(h) => h.title.clone().unwrap_or_else(|| text.get("Office hours").to_owned()),
         }
     }
 
     pub fn location(&self) -> Option<&'c str> {
         match self.base {
             EventBase::Session(s) => s.location(),
-            EventBase::Submission(_, _) => None,
-            EventBase::Presentation(_, _, s) => s.location(),
+            EventBase::Submission(_, _) | EventBase::Late(_, _) | EventBase::Release(_) | EventBase::Countdown(_, _, _) | EventBase::Milestone(_, _) | EventBase::Quiz(_, _) => None,
+            EventBase::Presentation(_, _, s) | EventBase::Slot(_, _, s, _) => s.location(),
+            EventBase::OfficeHours(h) => h.location(),
+        }
+    }
+
+    /// Equipment needed for the event
+    pub fn resources(&self) -> impl Iterator<Item = &'c str> {
+        let resources = match self.base {
+            EventBase::Session(s) | EventBase::Presentation(_, _, s) | EventBase::Slot(_, _, s, _) => &s.resources[..],
+            _ => &[],
+        };
+        resources.iter().map(String::as_str)
+    }
+
+    /// What a session covers, for its journal entry
+    pub fn topic(&self) -> Option<&'c str> {
+        match self.base {
+            EventBase::Session(s) => s.topic.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// What distinguishes the event from others of its kind in the same week
+    ///
+    /// This deliberately leaves out the titles of sessions, so that renaming
+    /// them does not change the identity. Sessions in a stream are told apart
+    /// by their stream, and the rest by the day and time at which they start.
+    pub fn identity(&self) -> String {
+        match self.base {
+            EventBase::Session(s) if s.additional => format!("{} additional {}", s.stream.as_deref().unwrap_or_default(), s.time.format("%Y%m%d%H%M")),
+            EventBase::Session(Session { stream: Some(stream), .. }) => stream.clone(),
+            EventBase::Session(s) => s.time.format("%a %H%M").to_string(),
+            EventBase::Submission(a, s) | EventBase::Late(a, s) => format!("{} {}", a.name, s.name),
+            EventBase::Release(a) => a.name.clone(),
+            EventBase::Countdown(a, s, c) => format!("{} {} {}", a.name, s.name, c),
+            EventBase::Milestone(a, m) => format!("{} {}", a.name, m.name),
+            EventBase::Presentation(a, p, s) => format!("{} {} {}", a.name, p.name, s.stream.as_deref().unwrap_or_default()),
+            EventBase::Slot(a, p, _, g) => format!("{} {} {}", a.name, p.name, p.groups[g]),
+            EventBase::Quiz(q, moment) => format!("{} {}", q.name, moment),
+            EventBase::OfficeHours(h) => format!("{} {}", h.weekday, h.time.format("%H%M")),
+        }
+    }
+
+    /// The shared file defining a session that also belongs to cross-listed courses
+    pub fn shared(&self) -> Option<&'c str> {
+        match self.base {
+            EventBase::Session(s) => s.shared.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// How long before the event to remind attendees
+    pub fn reminders(&self) -> impl Iterator<Item = Duration> + 'c {
+        let reminders: &[Duration] = match self.base {
+            EventBase::Session(s) | EventBase::Presentation(_, _, s) | EventBase::Slot(_, _, s, _) => &s.reminders,
+            _ => &[],
+        };
+        reminders.iter().cloned()
+    }
+
+    /// Whether the time and room of the event are yet to be confirmed
+    pub fn is_tentative(&self) -> bool {
+        match self.base {
+            EventBase::Session(s) | EventBase::Presentation(_, _, s) | EventBase::Slot(_, _, s, _) => s.tentative,
+            _ => false,
+        }
+    }
+
+    /// Whether only staff see the event
+    pub fn is_private(&self) -> bool {
+        match self.base {
+            EventBase::Session(s) | EventBase::Presentation(_, _, s) | EventBase::Slot(_, _, s, _) => s.private,
+            EventBase::OfficeHours(h) => h.private,
+            _ => false,
+        }
+    }
+
+    /// Whether the event has a value in a field that may be withheld from public feeds
+    pub fn has_value(&self, field: &str) -> bool {
+        match field {
+            "location" => self.room().is_some(),
+            "presenters" => self.presenters().next().is_some(),
+            "description" => match self.base {
+                EventBase::Session(s) => s.description.is_some(),
+                EventBase::Submission(a, s) => s.description.is_some() || a.description.is_some(),
+                EventBase::Release(a) => a.description.is_some(),
+                EventBase::Milestone(a, m) => m.description.is_some() || a.description.is_some(),
+                EventBase::Presentation(a, p, _) | EventBase::Slot(a, p, _, _) => p.description.is_some() || a.description.is_some(),
+                EventBase::Quiz(q, _) => q.description.is_some(),
+                EventBase::OfficeHours(h) => h.description.is_some(),
+                EventBase::Late(_, _) | EventBase::Countdown(_, _, _) => false,
+            },
+            "link" => match self.base {
+                EventBase::Session(s) => s.link.is_some(),
+                EventBase::OfficeHours(h) => h.link.is_some(),
+                _ => self.link().is_some(),
+            },
+            "meeting_url" => self.meeting_url().is_some(),
+            "notes" => self.notes().is_some(),
+            "attachments" => match self.base {
+                EventBase::Session(s) => !s.attachments.is_empty(),
+                _ => self.attachments().next().is_some(),
+            },
+            "slides" => matches!(self.base, EventBase::Session(Session { slides: Some(_), .. })),
+            "recording" => matches!(self.base, EventBase::Session(Session { recording: Some(_), .. })),
+            "participants" => match self.base {
+                EventBase::Presentation(_, p, _) | EventBase::Slot(_, p, _, _) => self.groups().any(|group| p.participants.contains_key(group)),
+                _ => false,
+            },
+            _ => false,
+        }
+    }
+
+    /// Notes from a session, for its journal entry
+    pub fn notes(&self) -> Option<&'c str> {
+        match self.base {
+            EventBase::Session(s) => s.notes.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// A link to join the event online
+    pub fn meeting_url(&self) -> Option<&'c Url> {
+        match self.base {
+            EventBase::Session(s) | EventBase::Presentation(_, _, s) | EventBase::Slot(_, _, s, _) => s.meeting_url.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// The coordinates of the location, given directly or in the location table
+    pub fn geo(&self) -> Option<Geo> {
+        let session = match self.base {
+            EventBase::Session(s) | EventBase::Presentation(_, _, s) | EventBase::Slot(_, _, s, _) => s,
+            EventBase::OfficeHours(h) => return h.place.as_ref().and_then(|place| place.geo),
+            _ => return None,
+        };
+        session.geo.or_else(|| session.place.as_ref().and_then(|place| place.geo))
+    }
+
+    /// The building of the location, if it is in the location table
+    pub fn building(&self) -> Option<&'c str> {
+        let place = match self.base {
+            EventBase::Session(s) | EventBase::Presentation(_, _, s) | EventBase::Slot(_, _, s, _) => s.place.as_ref(),
+            EventBase::OfficeHours(h) => h.place.as_ref(),
+            _ => None,
+        };
+        place.and_then(|place| place.building.as_deref())
+    }
+
+    /// The location as written in the specification, such as an identifier in the location table
+    pub fn room(&self) -> Option<&'c str> {
+        match self.base {
+            EventBase::Session(s) => s.location.as_deref(),
+            EventBase::Submission(_, _) | EventBase::Late(_, _) | EventBase::Release(_) | EventBase::Countdown(_, _, _) | EventBase::Milestone(_, _) | EventBase::Quiz(_, _) => None,
+            EventBase::Presentation(_, _, s) | EventBase::Slot(_, _, s, _) => s.location.as_deref(),
+            EventBase::OfficeHours(h) => h.location.as_deref(),
         }
     }
 
     pub fn presenters(&self) -> impl Iterator<Item = &'c str> {
         match self.base {
             EventBase::Session(s) => s.presenters().into_iter(),
-            EventBase::Submission(_, _) => Vec::new().into_iter(),
-            EventBase::Presentation(_, _, s) => s.presenters().into_iter(),
+            EventBase::Submission(_, _) | EventBase::Late(_, _) | EventBase::Release(_) | EventBase::Countdown(_, _, _) | EventBase::Milestone(_, _) | EventBase::Quiz(_, _) => Vec::new().into_iter(),
+            EventBase::Presentation(_, _, s) | EventBase::Slot(_, _, s, _) => s.presenters().into_iter(),
+            EventBase::OfficeHours(h) => h.presenters.iter().map(String::as_str).collect::<Vec<_>>().into_iter(),
         }
     }
 
-    pub fn description(&self) -> Option<&'c str> {
+    pub fn description(&self) -> Option<String> {
         match self.base {
-            EventBase::Session(_) => None,
-            EventBase::Submission(_, s @Submission { description: Some(_), .. }) => s.description(),
-            EventBase::Submission(a, _) => a.description(),
-            EventBase::Presentation(_, p @Presentation { description: Some(_), .. }, _) => p.description(),
-            EventBase::Presentation(a, _, _) => a.description(),
+            EventBase::Session(s) => s.full_description(self.text),
+            EventBase::Submission(a, s) => {
+                let description = s.description().or_else(|| a.description());
+                match (description, s.late_policy(self.text)) {
+                    (Some(description), Some(policy)) => Some(format!("{}\n\n{}", description, policy)),
+                    (description, policy) => description.map(str::to_owned).or(policy),
+                }
+            }
+            EventBase::Late(_, s) => Some(format!("{} {}. {}", self.text.get("Due"), s.time.format("%A %-d %B at %H:%M"), s.late_policy(self.text).unwrap_or_default())),
+            EventBase::Release(a) => a.description().map(str::to_owned),
+            EventBase::Countdown(_, s, _) => Some(format!("{} {}", self.text.get("Due"), s.time.format("%A %-d %B at %H:%M"))),
+            EventBase::Milestone(_, m @Milestone { description: Some(_), .. }) => m.description.clone(),
+            EventBase::Milestone(a, _) => a.description().map(str::to_owned),
+            EventBase::Presentation(a, p, _) => p.full_description(a, &p.groups, self.text),
+            EventBase::Slot(a, p, _, g) => p.full_description(a, &p.groups[g..=g], self.text),
+            EventBase::Quiz(q, _) => q.description.clone(),
+            EventBase::OfficeHours(h) => h.full_description(self.text),
         }
     }
 
     pub fn link(&self) -> Option<&'c Url> {
         match self.base {
-            EventBase::Session(_) => None,
-            EventBase::Submission(a, _) => Some(&a.link),
-            EventBase::Presentation(a, _, _) => Some(&a.link),
+            EventBase::Session(s) => s.link(),
+            EventBase::Submission(a, _) | EventBase::Late(a, _) | EventBase::Release(a) | EventBase::Countdown(a, _, _) | EventBase::Milestone(a, _) => Some(&a.link),
+            EventBase::Presentation(a, _, _) | EventBase::Slot(a, _, _, _) => Some(&a.link),
+            EventBase::Quiz(q, _) => q.link.as_ref(),
+            EventBase::OfficeHours(h) => h.link.as_ref(),
+        }
+    }
+
+    /// The kind of session, or whether this is a submission or presentation
+    pub fn kind(&self) -> &'c str {
+        match self.base {
+            EventBase::Session(s) => &s.kind,
+            EventBase::Submission(_, _) => "submission",
+            EventBase::Late(_, _) => "late",
+            EventBase::Release(_) => "release",
+            EventBase::Countdown(_, _, _) => "countdown",
+            EventBase::Milestone(_, _) => "milestone",
+            EventBase::Presentation(_, _, _) | EventBase::Slot(_, _, _, _) => "presentation",
+            EventBase::Quiz(_, _) => "quiz",
+            EventBase::OfficeHours(_) => "office-hours",
+        }
+    }
+
+    /// The stream of the session in which the event occurs
+    pub fn stream(&self) -> Option<&'c str> {
+        match self.base {
+            EventBase::Session(s) => s.stream.as_deref(),
+            EventBase::Submission(_, _) | EventBase::Late(_, _) | EventBase::Release(_) | EventBase::Countdown(_, _, _) | EventBase::Milestone(_, _) | EventBase::Quiz(_, _) | EventBase::OfficeHours(_) => None,
+            EventBase::Presentation(_, _, s) | EventBase::Slot(_, _, s, _) => s.stream.as_deref(),
+        }
+    }
+
+    /// The groups presenting, which for a slot is only the group given the slot
+    pub fn groups(&self) -> impl Iterator<Item = &'c str> {
+        let groups = match self.base {
+            EventBase::Presentation(_, p, _) => &p.groups[..],
+            EventBase::Slot(_, p, _, g) => &p.groups[g..=g],
+            _ => &[],
+        };
+        groups.iter().map(String::as_str)
+    }
+
+    /// Whether the event is the opening of a quiz, which is followed by its closing
+    pub fn is_opening(&self) -> bool {
+        matches!(self.base, EventBase::Quiz(_, QuizMoment::Opens))
+    }
+
+    /// When the quiz that closes at the event opened
+    pub fn opens(&self) -> Option<DateTime<FixedOffset>> {
+        match self.base {
+            EventBase::Quiz(q, QuizMoment::Closes) => Some(self.start - (q.closes - q.opens)),
+            _ => None,
+        }
+    }
+
+    /// Whether the event marks a moment, such as a deadline, rather than taking up time
+    pub fn is_point_in_time(&self) -> bool {
+        match self.base {
+            EventBase::Submission(_, _) | EventBase::Late(_, _) | EventBase::Release(_) | EventBase::Countdown(_, _, _) | EventBase::Milestone(_, _) | EventBase::Quiz(_, _) => true,
+            EventBase::Session(_) | EventBase::OfficeHours(_) | EventBase::Presentation(_, _, _) | EventBase::Slot(_, _, _, _) => false,
+        }
+    }
+
+    /// Whether the event belongs to an assignment rather than being a plain session
+    pub fn is_assignment(&self) -> bool {
+        match self.base {
+            EventBase::Session(_) | EventBase::OfficeHours(_) => false,
+            EventBase::Submission(_, _) | EventBase::Late(_, _) | EventBase::Release(_) | EventBase::Countdown(_, _, _) | EventBase::Milestone(_, _) | EventBase::Presentation(_, _, _) | EventBase::Slot(_, _, _, _) | EventBase::Quiz(_, _) => true,
+        }
+    }
+
+    /// The iCalendar priority of an assessment, from 1 (highest) to 9
+    ///
+    /// Without an explicit priority, items worth 30 or more are high priority,
+    /// those worth 10 or more are medium, and the rest are low.
+    pub fn priority(&self) -> Option<u8> {
+        let (priority, value) = match self.base {
+            EventBase::Submission(a, _) | EventBase::Late(a, _) | EventBase::Presentation(a, _, _) | EventBase::Slot(a, _, _, _) => (a.priority, a.value),
+            EventBase::Quiz(q, _) => (q.priority, q.value),
+            _ => return None,
+        };
+        priority.or_else(|| match value? {
+            value if value >= 30 => Some(1),
+            value if value >= 10 => Some(5),
+            _ => Some(9),
+        })
+    }
+
+    pub fn tags(&self) -> impl Iterator<Item = &'c str> {
+        let tags = match self.base {
+            EventBase::Session(s) => &s.tags,
+            EventBase::Submission(a, _) | EventBase::Late(a, _) | EventBase::Release(a) | EventBase::Countdown(a, _, _) | EventBase::Milestone(a, _) => &a.tags,
+            EventBase::Presentation(a, _, _) | EventBase::Slot(a, _, _, _) => &a.tags,
+            EventBase::Quiz(q, _) => &q.tags,
+            EventBase::OfficeHours(h) => &h.tags,
+        };
+        tags.iter().map(|s| s.as_str())
+    }
+
+    /// Additional properties to emit verbatim on the event
+    pub fn extra(&self) -> impl Iterator<Item = (&'c str, &'c str)> {
+        let extra = match self.base {
+            EventBase::Session(s) => Some(&s.extra),
+            EventBase::Submission(_, _) | EventBase::Late(_, _) | EventBase::Release(_) | EventBase::Countdown(_, _, _) | EventBase::Milestone(_, _) | EventBase::Presentation(_, _, _) | EventBase::Slot(_, _, _, _) | EventBase::Quiz(_, _) | EventBase::OfficeHours(_) => None,
+        };
+        extra.into_iter()
+            .flat_map(|extra| extra.iter())
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+
+    pub fn attachments(&self) -> impl Iterator<Item = &'c Url> {
+        match self.base {
+            EventBase::Session(s) => s.attachments().into_iter(),
+            EventBase::Submission(_, s) => s.attachments.iter().collect::<Vec<_>>().into_iter(),
+            EventBase::Presentation(_, p, _) | EventBase::Slot(_, p, _, _) => p.attachments.iter().collect::<Vec<_>>().into_iter(),
+            EventBase::Late(_, _) | EventBase::Release(_) | EventBase::Countdown(_, _, _) | EventBase::Milestone(_, _) | EventBase::Quiz(_, _) | EventBase::OfficeHours(_) => Vec::new().into_iter(),
         }
     }
 }
@@ -313,6 +1837,9 @@ impl<'c> From<&'c Session> for Event<'c> {
         Event {
             start: session.time,
             base: EventBase::Session(session),
+            week: None,
+            first_week: 1,
+            text: Text::default(),
         }
     }
 }
@@ -321,7 +1848,15 @@ impl<'c> From<&'c Session> for Event<'c> {
 enum EventBase<'c> {
     Session(&'c Session),
     Submission(&'c Assignment, &'c Submission),
+    Release(&'c Assignment),
+    Late(&'c Assignment, &'c Submission),
+    Countdown(&'c Assignment, &'c Submission, &'c str),
+    Milestone(&'c Assignment, &'c Milestone),
     Presentation(&'c Assignment, &'c Presentation, &'c Session),
+    /// The slot of one group, by index, during a presentation session
+    Slot(&'c Assignment, &'c Presentation, &'c Session, usize),
+    Quiz(&'c Quiz, QuizMoment),
+    OfficeHours(&'c OfficeHours),
 }
 
 struct DateTimeVisitor;
@@ -349,6 +1884,24 @@ fn deserialize_datetime<'de, D: Deserializer<'de>>(
     deserializer.deserialize_map(DateTimeVisitor)
 }
 
+fn serialize_datetime<S: Serializer>(time: &DateTime<FixedOffset>, serializer: S) -> Result<S::Ok, S::Error> {
+    let time: toml::value::Datetime = time.to_rfc3339().parse().map_err(ser::Error::custom)?;
+    time.serialize(serializer)
+}
+
+fn deserialize_optional_datetime<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<DateTime<FixedOffset>>, D::Error> {
+    deserialize_datetime(deserializer).map(Some)
+}
+
+fn serialize_optional_datetime<S: Serializer>(time: &Option<DateTime<FixedOffset>>, serializer: S) -> Result<S::Ok, S::Error> {
+    match time {
+        Some(time) => serialize_datetime(time, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
 struct DurationVisitor;
 
 impl<'de> de::Visitor<'de> for DurationVisitor {
@@ -363,10 +1916,74 @@ impl<'de> de::Visitor<'de> for DurationVisitor {
     }
 }
 
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
 fn deserialize_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
     deserializer.deserialize_i64(DurationVisitor)
 }
 
+fn serialize_duration<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_i64(duration.num_seconds())
+}
+
+fn deserialize_durations<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Duration>, D::Error> {
+    Ok(Vec::<i64>::deserialize(deserializer)?.into_iter().map(Duration::seconds).collect())
+}
+
+fn serialize_durations<S: Serializer>(durations: &[Duration], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(durations.iter().map(Duration::num_seconds))
+}
+
+fn deserialize_optional_duration<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+    deserialize_duration(deserializer).map(Some)
+}
+
+fn serialize_optional_duration<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serialize_duration(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn deserialize_weekday<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let name = String::deserialize(deserializer)?;
+    match name.parse::<Weekday>() {
+        Ok(_) => Ok(name),
+        Err(_) => Err(de::Error::custom(format!("Unknown weekday {}", name))),
+    }
+}
+
+fn deserialize_countdown<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    let countdown = Vec::<String>::deserialize(deserializer)?;
+    match countdown.iter().find(|value| parse_period(value).is_none()) {
+        Some(value) => Err(de::Error::custom(format!("Expected a time before the deadline such as 7d or 12h but found {}", value))),
+        None => Ok(countdown),
+    }
+}
+
+fn deserialize_period<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<String>, D::Error> {
+    let period = String::deserialize(deserializer)?;
+    match parse_period(&period) {
+        Some(_) => Ok(Some(period)),
+        None => Err(de::Error::custom(format!("Expected a period such as 5d or 12h but found {}", period))),
+    }
+}
+
+fn deserialize_time<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveTime, D::Error> {
+    let time = String::deserialize(deserializer)?;
+    NaiveTime::parse_from_str(&time, "%H:%M").map_err(|_| de::Error::custom(format!("Expected a time such as 14:00 but found {}", time)))
+}
+
+fn serialize_time<S: Serializer>(time: &NaiveTime, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&time.format("%H:%M").to_string())
+}
+
+fn serialize_week_numbers<S: Serializer>(weeks: &[usize], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(weeks.iter().map(|week| week + 1))
+}
+
 struct UrlVisitor;
 
 impl<'de> de::Visitor<'de> for UrlVisitor {
@@ -381,6 +1998,195 @@ impl<'de> de::Visitor<'de> for UrlVisitor {
     }
 }
 
-fn deserialize_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Url, D::Error> {
+pub fn deserialize_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Url, D::Error> {
     deserializer.deserialize_str(UrlVisitor)
 }
+
+fn deserialize_optional_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Url>, D::Error> {
+    deserialize_url(deserializer).map(Some)
+}
+
+fn deserialize_urls<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Url>, D::Error> {
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|url| Url::parse(url).map_err(de::Error::custom))
+        .collect()
+}
+
+fn serialize_url<S: Serializer>(url: &Url, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(url.as_str())
+}
+
+fn serialize_optional_url<S: Serializer>(url: &Option<Url>, serializer: S) -> Result<S::Ok, S::Error> {
+    match url {
+        Some(url) => serialize_url(url, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn serialize_urls<S: Serializer>(urls: &[Url], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(urls.iter().map(Url::as_str))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::json::Json;
+    use crate::schema;
+
+    /// A deserializer that only records the fields of the structure asked of it
+    struct Fields<'f>(&'f mut &'static [&'static str]);
+
+    impl<'de, 'f> Deserializer<'de> for Fields<'f> {
+        type Error = de::value::Error;
+
+        fn deserialize_any<V: de::Visitor<'de>>(self, _: V) -> Result<V::Value, Self::Error> {
+            Err(de::Error::custom("expected a structure"))
+        }
+
+        fn deserialize_struct<V: de::Visitor<'de>>(
+            self,
+            _: &'static str,
+            fields: &'static [&'static str],
+            _: V,
+        ) -> Result<V::Value, Self::Error> {
+            *self.0 = fields;
+            Err(de::Error::custom("recorded the fields"))
+        }
+
+        serde::forward_to_deserialize_any! {
+            bool i8 i16 i32 i64 u8 u16 u32 u64 f32 f64 char str string bytes byte_buf option unit
+            unit_struct newtype_struct seq tuple tuple_struct map enum identifier ignored_any
+        }
+    }
+
+    /// The fields that a structure reads from a specification
+    fn fields<T: for<'de> Deserialize<'de>>() -> &'static [&'static str] {
+        let mut fields: &'static [&'static str] = &[];
+        let _ = T::deserialize(Fields(&mut fields));
+        fields
+    }
+
+    /// The schema of a nested table, following the items of arrays
+    fn nested<'s>(mut schema: &'s Json, path: &[&str]) -> &'s Json {
+        for name in path {
+            schema = schema.get("properties").and_then(|properties| properties.get(name)).unwrap();
+            if let Some(items) = schema.get("items") {
+                schema = items;
+            }
+        }
+        schema
+    }
+
+    #[test]
+    fn schema_covers_every_field() {
+        let schema = schema::course();
+        let structures = [
+            (fields::<Course>(), &[][..]),
+            (fields::<Week>(), &["week"][..]),
+            (fields::<Session>(), &["week", "session"][..]),
+            (fields::<RepeatSession>(), &["session"][..]),
+            (fields::<AdditionalSession>(), &["session", "additional"][..]),
+            (fields::<Assignment>(), &["assignment"][..]),
+            (fields::<Submission>(), &["assignment", "submission"][..]),
+            (fields::<Milestone>(), &["assignment", "milestone"][..]),
+            (fields::<Presentation>(), &["assignment", "presentation"][..]),
+            (fields::<Quiz>(), &["quiz"][..]),
+            (fields::<OfficeHours>(), &["office_hours"][..]),
+        ];
+        for (fields, path) in &structures {
+            assert!(!fields.is_empty(), "no fields recorded for {:?}", path);
+            let properties = nested(&schema, path).get("properties").unwrap();
+            for field in fields.iter() {
+                assert!(properties.get(field).is_some(), "{} is missing from the schema of {:?}", field, path);
+            }
+        }
+    }
+
+    #[test]
+    fn extra_sessions_are_added_to_the_series() {
+        let spec = r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/"
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-12T10:00:00+11:00
+            kind = "lecture"
+            duration = 3600
+            weeks = [1]
+
+            [[session.extra]]
+            time = 2024-02-16T10:00:00+11:00
+            title = "Revision"
+
+            [[session]]
+            first = 2024-02-13T10:00:00+11:00
+            kind = "tutorial"
+            duration = 3600
+            weeks = [1]
+
+            [session.extra]
+            X-ROOM-CODE = "K17"
+        "#;
+        let mut course = Course::from_value(toml::from_str(spec).unwrap()).unwrap();
+        course.generate_repeats().unwrap();
+        let mut events = course.events().collect::<Vec<_>>();
+        events.sort_by_key(|event| event.start());
+        let titles = events.iter().map(|event| event.title()).collect::<Vec<_>>();
+        assert_eq!(titles, vec!["(lecture)", "(tutorial)", "Revision (lecture)"]);
+        assert_eq!(events[1].extra().collect::<Vec<_>>(), vec![("X-ROOM-CODE", "K17")]);
+        assert_eq!(events[2].start().to_rfc3339(), "2024-02-16T10:00:00+11:00");
+    }
+
+    #[test]
+    fn shared_files_are_prepared_like_the_course() {
+        let directory = std::env::temp_dir().join(format!("course-calendar-shared-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+        let course = r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/"
+            week_numbering = "zero-based"
+            shared = ["shared.toml"]
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[week]]
+            start = 2024-02-19T00:00:00+11:00
+            label = "Flexibility Week"
+        "#;
+        let shared = r#"
+            [vars]
+            room = "Quad 1001"
+
+            [defaults]
+            duration = 3600
+
+            [[session]]
+            first = 2024-02-12T10:00:00+11:00
+            kind = "lecture"
+            location = "{{room}}"
+            weeks = ["Flexibility Week"]
+
+            [[session.extra]]
+            time = 2024-02-28T10:00:00+11:00
+        "#;
+        std::fs::write(directory.join("course.toml"), course).unwrap();
+        std::fs::write(directory.join("shared.toml"), shared).unwrap();
+        let course = Course::from_path(directory.join("course.toml"));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let course = course.unwrap();
+        let events = course.events().collect::<Vec<_>>();
+        let weeks = events.iter().map(|event| event.week_number()).collect::<Vec<_>>();
+        assert_eq!(weeks, vec![Some(1), Some(1)]);
+        assert!(events.iter().all(|event| event.location() == Some("Quad 1001") && event.duration() == Duration::hours(1)));
+    }
+}
@@ -0,0 +1,219 @@
+//! Differences between two versions of a course schedule
+
+use chrono::{offset::FixedOffset, DateTime, Datelike, Duration, NaiveDate};
+use failure::{format_err, Error};
+
+use crate::course::Course;
+use crate::filter::Filter;
+use crate::ical;
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// An event as it appears in either a specification or a calendar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    summary: String,
+    start: DateTime<FixedOffset>,
+    end: DateTime<FixedOffset>,
+    location: Option<String>,
+    presenters: Vec<String>,
+}
+
+impl Entry {
+    /// Events are matched between versions by their summary and local week
+    fn key(&self) -> (String, NaiveDate) {
+        let date = self.start.naive_local().date();
+        let monday = date - Duration::days(date.weekday().num_days_from_monday().into());
+        (self.summary.clone(), monday)
+    }
+
+    fn time(&self) -> String {
+        format!(
+            "{} {}-{}",
+            self.start.format("%a %Y-%m-%d"),
+            self.start.format("%H:%M"),
+            self.end.format("%H:%M"),
+        )
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} on {}", self.summary, self.time())?;
+        if let Some(location) = &self.location {
+            write!(f, " in {}", location)?;
+        }
+        if !self.presenters.is_empty() {
+            write!(f, " with {}", self.presenters.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Load the events of a course specification or, for `.ics` files, a calendar
+pub fn load<P: AsRef<Path>>(path: P, filter: &Filter) -> Result<Vec<Entry>, Error> {
+    let path = path.as_ref();
+    if path.extension().map(|extension| extension == "ics").unwrap_or(false) {
+        let calendar = ical::parse(&read_to_string(path)?)?
+            .into_iter()
+            .find(|component| component.name == "VCALENDAR")
+            .ok_or(format_err!("No VCALENDAR found in {}", path.display()))?;
+        let entries = ical::occurrences(&calendar)?
+            .into_iter()
+            .map(|occurrence| Entry {
+                summary: occurrence.summary,
+                start: occurrence.start,
+                end: occurrence.end,
+                location: occurrence.location,
+                presenters: occurrence.contacts,
+            })
+            .collect();
+        return Ok(entries);
+    }
+
+    let course = Course::from_path(path)?;
+    let entries = course
+        .events()
+        .filter(|event| filter.matches(event))
+        .map(|event| Entry {
+            summary: format!("{} {}", course.code(), event.title()),
+            start: event.start(),
+            end: event.end(),
+            location: event.location().map(str::to_owned),
+            presenters: event.presenters().map(str::to_owned).collect(),
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// A difference between two versions of a schedule
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Change {
+    Added(Entry),
+    Removed(Entry),
+    Changed(Entry, Entry),
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added(entry) => write!(f, "+ {}", entry),
+            Change::Removed(entry) => write!(f, "- {}", entry),
+            Change::Changed(old, new) => {
+                write!(f, "~ {} on {}", old.summary, old.time())?;
+                if old.start != new.start || old.end != new.end {
+                    write!(f, "\n    time: {} -> {}", old.time(), new.time())?;
+                }
+                if old.location != new.location {
+                    let location = |entry: &Entry| entry.location.clone().unwrap_or_else(|| "none".to_owned());
+                    write!(f, "\n    location: {} -> {}", location(old), location(new))?;
+                }
+                if old.presenters != new.presenters {
+                    let presenters = |entry: &Entry| {
+                        if entry.presenters.is_empty() {
+                            "none".to_owned()
+                        } else {
+                            entry.presenters.join(", ")
+                        }
+                    };
+                    write!(f, "\n    presenters: {} -> {}", presenters(old), presenters(new))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Compare two schedules
+///
+/// Events with the same summary in the same week are paired in chronological
+/// order, so a session that moves within its week is reported as changed.
+pub fn compare(old: Vec<Entry>, new: Vec<Entry>) -> Vec<Change> {
+    let mut groups: BTreeMap<_, (Vec<Entry>, Vec<Entry>)> = BTreeMap::new();
+    for entry in old {
+        groups.entry(entry.key()).or_default().0.push(entry);
+    }
+    for entry in new {
+        groups.entry(entry.key()).or_default().1.push(entry);
+    }
+
+    let mut changes = Vec::new();
+    for (_, (mut old, mut new)) in groups {
+        old.sort_by_key(|entry| entry.start);
+        new.sort_by_key(|entry| entry.start);
+        let mut old = old.into_iter();
+        let mut new = new.into_iter();
+        loop {
+            match (old.next(), new.next()) {
+                (Some(old), Some(new)) if old == new => {}
+                (Some(old), Some(new)) => changes.push(Change::Changed(old, new)),
+                (Some(old), None) => changes.push(Change::Removed(old)),
+                (None, Some(new)) => changes.push(Change::Added(new)),
+                (None, None) => break,
+            }
+        }
+    }
+
+    changes.sort_by_key(|change| match change {
+        Change::Added(entry) | Change::Removed(entry) | Change::Changed(entry, _) => entry.start,
+    });
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(summary: &str, start: &str, location: &str) -> Entry {
+        let start = DateTime::parse_from_rfc3339(start).unwrap();
+        Entry {
+            summary: summary.to_owned(),
+            start,
+            end: start + Duration::hours(1),
+            location: Some(location.to_owned()),
+            presenters: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn unchanged_events_are_not_reported() {
+        let schedule = vec![entry("COMP1000 Lecture", "2024-02-12T10:00:00+11:00", "Quad")];
+        assert!(compare(schedule.clone(), schedule).is_empty());
+    }
+
+    #[test]
+    fn events_moved_within_their_week_are_changed() {
+        let old = vec![entry("COMP1000 Lecture", "2024-02-12T10:00:00+11:00", "Quad")];
+        let new = vec![entry("COMP1000 Lecture", "2024-02-14T10:00:00+11:00", "Quad")];
+        let changes = compare(old.clone(), new.clone());
+        assert_eq!(changes, vec![Change::Changed(old[0].clone(), new[0].clone())]);
+        assert_eq!(
+            changes[0].to_string(),
+            "~ COMP1000 Lecture on Mon 2024-02-12 10:00-11:00\n    time: Mon 2024-02-12 10:00-11:00 -> Wed 2024-02-14 10:00-11:00"
+        );
+    }
+
+    #[test]
+    fn events_moved_to_another_week_are_removed_and_added() {
+        let old = vec![entry("COMP1000 Lecture", "2024-02-12T10:00:00+11:00", "Quad")];
+        let new = vec![entry("COMP1000 Lecture", "2024-02-19T10:00:00+11:00", "Quad")];
+        let changes = compare(old.clone(), new.clone());
+        assert_eq!(changes, vec![Change::Removed(old[0].clone()), Change::Added(new[0].clone())]);
+    }
+
+    #[test]
+    fn repeated_events_pair_in_order() {
+        let old = vec![
+            entry("COMP1000 Tutorial", "2024-02-13T10:00:00+11:00", "Quad 1001"),
+            entry("COMP1000 Tutorial", "2024-02-13T14:00:00+11:00", "Quad 1002"),
+        ];
+        let mut new = old.clone();
+        new[1].location = Some("Quad 1003".to_owned());
+        let changes = compare(old.clone(), new.clone());
+        assert_eq!(changes, vec![Change::Changed(old[1].clone(), new[1].clone())]);
+        assert!(changes[0].to_string().ends_with("location: Quad 1002 -> Quad 1003"));
+    }
+}
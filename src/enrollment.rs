@@ -0,0 +1,53 @@
+//! Enrollment of students in the streams of each course
+
+use failure::{format_err, Error};
+use serde::Deserialize;
+
+use std::collections::BTreeMap;
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// The streams chosen by each student
+#[derive(Debug, Clone, Deserialize)]
+pub struct Enrollment {
+    #[serde(rename = "student", default)]
+    students: Vec<Student>,
+}
+
+impl Enrollment {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(toml::from_str(&read_to_string(path)?)?)
+    }
+
+    pub fn student(&self, id: &str) -> Result<&Student, Error> {
+        self.students
+            .iter()
+            .find(|student| student.id == id)
+            .ok_or(format_err!("No student {} in enrollment", id))
+    }
+}
+
+/// A student and the streams they attend in each course
+#[derive(Debug, Clone, Deserialize)]
+pub struct Student {
+    id: String,
+    name: Option<String>,
+    /// Chosen streams keyed by course code
+    #[serde(default)]
+    streams: BTreeMap<String, Vec<String>>,
+}
+
+impl Student {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// The streams chosen for a particular course
+    pub fn streams(&self, code: &str) -> &[String] {
+        self.streams.get(code).map(|streams| streams.as_slice()).unwrap_or(&[])
+    }
+}
@@ -0,0 +1,130 @@
+//! An Atom feed of assignment deadlines, soonest first
+
+use chrono::{DateTime, SecondsFormat, TimeZone, Utc};
+use failure::Error;
+
+use crate::calendar;
+use crate::course::Course;
+use crate::filter::Filter;
+use crate::state::State;
+use crate::markdown::{escape_html, to_html_fragment};
+
+use super::uuid;
+
+use std::io::Write;
+
+pub fn write(course: &Course, filter: &Filter, state: &State, output: &mut dyn Write) -> Result<(), Error> {
+    let mut deadlines = calendar::identified(course)
+        .into_iter()
+        .filter(|(_, event)| event.kind() == "submission" && filter.matches(event))
+        .collect::<Vec<_>>();
+    deadlines.sort_by_key(|(_, event)| event.start());
+
+    // Entries are dated by their last change, or by the deadline itself without a revision
+    let changed = |uid: &str, start: DateTime<Utc>| {
+        state
+            .revision(uid)
+            .and_then(|revision| Utc.datetime_from_str(&revision.modified, "%Y%m%dT%H%M%SZ").ok())
+            .unwrap_or(start)
+    };
+    let changes = deadlines.iter().map(|(uid, event)| changed(uid, event.start().into())).collect::<Vec<_>>();
+    let updated = changes.iter().max().cloned().unwrap_or_else(|| Utc.timestamp(0, 0));
+    let title = escape_html(&format!("{} {} deadlines", course.code(), course.name()));
+
+    writeln!(output, "<?xml version=\"1.0\" encoding=\"utf-8\"?>")?;
+    writeln!(output, "<feed xmlns=\"http://www.w3.org/2005/Atom\">")?;
+    writeln!(output, "  <id>urn:uuid:{}</id>", uuid(course.code()))?;
+    writeln!(output, "  <title>{}</title>", title)?;
+    writeln!(output, "  <updated>{}</updated>", updated.to_rfc3339_opts(SecondsFormat::Secs, true))?;
+    writeln!(output, "  <author><name>{}</name></author>", escape_html(course.code()))?;
+
+    for ((uid, event), changed) in deadlines.iter().zip(changes) {
+        let due = event.start().format("%a %-d %b %H:%M");
+        writeln!(output, "  <entry>")?;
+        writeln!(output, "    <id>urn:uuid:{}</id>", uuid(uid))?;
+        writeln!(output, "    <title>{}</title>", escape_html(&format!("{} ({})", event.name(), due)))?;
+        writeln!(output, "    <updated>{}</updated>", changed.to_rfc3339_opts(SecondsFormat::Secs, true))?;
+        if let Some(link) = event.link() {
+            writeln!(output, "    <link href=\"{}\"/>", escape_html(link.as_str()))?;
+        }
+        let mut summary = format!("<p>Due {}</p>", due);
+        if let Some(description) = event.description() {
+            summary.push_str(&to_html_fragment(&description));
+        }
+        writeln!(output, "    <content type=\"html\">{}</content>", escape_html(&summary))?;
+        writeln!(output, "  </entry>")?;
+    }
+
+    writeln!(output, "</feed>")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::calendar::update_state;
+    use crate::export::tests::{course, export};
+    use crate::export::Format;
+
+    const DEADLINES: &str = r#"
+        version = 2
+        code = "COMP1000"
+        name = "Testing & Debugging"
+        link = "https://example.edu/comp1000"
+
+        [[week]]
+        start = 2024-02-12T00:00:00+11:00
+
+        [[assignment]]
+        name = "Assignment"
+        link = "https://example.edu/assignment?part=1&lang=en"
+
+        [[assignment.submission]]
+        time = 2024-02-23T17:00:00+11:00
+        name = "Final"
+        description = "Submit *everything*"
+
+        [[assignment.submission]]
+        time = 2024-02-16T17:00:00+11:00
+        name = "Draft"
+        "#;
+
+    fn entries(feed: &str) -> Vec<&str> {
+        feed.split("<entry>").skip(1).collect()
+    }
+
+    #[test]
+    fn deadlines_are_listed_soonest_first() {
+        let feed = export(Format::Atom, &course(DEADLINES));
+        assert!(feed.contains("<title>COMP1000 Testing &amp; Debugging deadlines</title>"));
+        assert!(feed.contains(&format!("<id>urn:uuid:{}</id>", uuid("COMP1000"))));
+
+        let entries = entries(&feed);
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].contains("<title>Assignment: Draft (Fri 16 Feb 17:00)</title>"));
+        assert!(entries[1].contains("<title>Assignment: Final (Fri 23 Feb 17:00)</title>"));
+        assert!(entries[1].contains("<link href=\"https://example.edu/assignment?part=1&amp;lang=en\"/>"));
+        assert!(entries[1].contains("&lt;p&gt;Due Fri 23 Feb 17:00&lt;/p&gt;"));
+        assert!(entries[1].contains("&lt;em&gt;everything&lt;/em&gt;"));
+    }
+
+    #[test]
+    fn entries_are_dated_by_their_last_change() {
+        let course = course(DEADLINES);
+        let feed = export(Format::Atom, &course);
+        assert!(entries(&feed)[0].contains("<updated>2024-02-16T06:00:00Z</updated>"));
+        assert!(feed.contains("<updated>2024-02-23T06:00:00Z</updated>\n  <author>"));
+
+        let mut state = State::default();
+        update_state(&course, &Filter::default(), &mut state);
+        let (uid, _) = calendar::identified(&course).into_iter().find(|(_, event)| event.name().ends_with("Draft")).unwrap();
+        let modified = Utc.datetime_from_str(&state.revision(&uid).unwrap().modified, "%Y%m%dT%H%M%SZ").unwrap();
+
+        let mut feed = Vec::new();
+        write(&course, &Filter::default(), &state, &mut feed).unwrap();
+        let feed = String::from_utf8(feed).unwrap();
+        let updated = format!("<updated>{}</updated>", modified.to_rfc3339_opts(SecondsFormat::Secs, true));
+        assert!(entries(&feed)[0].contains(&updated));
+    }
+}
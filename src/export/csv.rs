@@ -0,0 +1,88 @@
+//! Comma-separated values with one row per event
+
+use failure::Error;
+
+use crate::course::Course;
+use crate::filter::Filter;
+
+use std::io::Write;
+
+const HEADER: &[&str] = &["start", "end", "title", "kind", "location", "presenters", "link"];
+
+pub fn write(course: &Course, filter: &Filter, output: &mut dyn Write) -> Result<(), Error> {
+    write_row(output, HEADER.iter().map(|field| field.to_string()))?;
+    for event in course.events().filter(|event| filter.matches(event)) {
+        write_row(
+            output,
+            vec![
+                event.start().to_rfc3339(),
+                event.end().to_rfc3339(),
+                format!("{} {}", course.code(), event.name()),
+                event.kind().to_owned(),
+                event.location().unwrap_or_default().to_owned(),
+                event.presenters().collect::<Vec<_>>().join("; "),
+                event.link().map(|link| link.to_string()).unwrap_or_default(),
+            ],
+        )?;
+    }
+    Ok(())
+}
+
+fn write_row<I: IntoIterator<Item = String>>(output: &mut dyn Write, fields: I) -> Result<(), Error> {
+    let fields = fields.into_iter().map(|field| quote(&field)).collect::<Vec<_>>();
+    write!(output, "{}\r\n", fields.join(","))?;
+    Ok(())
+}
+
+/// Quote a field if it contains a delimiter, quote, or line break
+fn quote(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::export::tests::{course, export};
+    use crate::export::Format;
+
+    #[test]
+    fn fields_are_quoted_only_when_needed() {
+        assert_eq!(quote("Quad 1001"), "Quad 1001");
+        assert_eq!(quote("Quad, room 1001"), "\"Quad, room 1001\"");
+        assert_eq!(quote("the \"big\" hall"), "\"the \"\"big\"\" hall\"");
+        assert_eq!(quote("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn each_event_is_a_row() {
+        let course = course(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/comp1000"
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-14T15:00:00+11:00
+            kind = "lab"
+            location = "Building K17, Lab 2"
+            presenters = ["Ada", "Grace"]
+            duration = 7200
+            weeks = [1]
+            "#,
+        );
+        assert_eq!(
+            export(Format::Csv, &course),
+            "start,end,title,kind,location,presenters,link\r\n\
+             2024-02-14T15:00:00+11:00,2024-02-14T17:00:00+11:00,COMP1000 lab,lab,\"Building K17, Lab 2\",Ada; Grace,\r\n"
+        );
+    }
+}
@@ -0,0 +1,69 @@
+//! An iCalendar free/busy schedule, showing when the course is busy but not why
+
+use failure::Error;
+
+use crate::calendar;
+use crate::course::Course;
+use crate::filter::Filter;
+
+use std::io::Write;
+
+pub fn write(course: &Course, filter: &Filter, output: &mut dyn Write) -> Result<(), Error> {
+    calendar::freebusy(course, filter).write(output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::tests::{course, export};
+    use crate::export::Format;
+
+    #[test]
+    fn overlapping_sessions_are_one_busy_period_without_details() {
+        let course = course(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/comp1000"
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-12T10:00:00+11:00
+            kind = "lecture"
+            location = "Quad 1001"
+            duration = 7200
+            weeks = [1]
+
+            [[session]]
+            first = 2024-02-12T11:00:00+11:00
+            kind = "tutorial"
+            location = "Quad 1002"
+            duration = 7200
+            weeks = [1]
+
+            [[session]]
+            first = 2024-02-14T10:00:00+11:00
+            kind = "lab"
+            duration = 3600
+            weeks = [1]
+            "#,
+        );
+        let schedule = export(Format::FreeBusy, &course);
+        let lines = schedule.lines().collect::<Vec<_>>();
+        assert!(lines.contains(&"BEGIN:VFREEBUSY"));
+        assert!(lines.contains(&"DTSTART:20240211T230000Z"));
+        assert!(lines.contains(&"DTEND:20240214T000000Z"));
+        let busy = lines.iter().filter(|line| line.starts_with("FREEBUSY")).collect::<Vec<_>>();
+        assert_eq!(
+            busy,
+            [
+                &"FREEBUSY;FBTYPE=BUSY:20240211T230000Z/20240212T020000Z",
+                &"FREEBUSY;FBTYPE=BUSY:20240213T230000Z/20240214T000000Z",
+            ]
+        );
+        assert!(!schedule.contains("Quad") && !schedule.contains("SUMMARY") && !schedule.contains("tutorial"));
+    }
+}
@@ -0,0 +1,167 @@
+//! A static HTML page with a timetable grid for each week
+
+use chrono::{Datelike, Duration, Timelike};
+use failure::Error;
+
+use crate::course::{Course, Event};
+use crate::filter::Filter;
+use crate::markdown::escape_html;
+
+use super::colour;
+
+use std::collections::BTreeSet;
+use std::io::Write;
+
+const STYLE: &str = "\
+body { font-family: sans-serif; }
+table.timetable { border-collapse: collapse; width: 100%; table-layout: fixed; margin-bottom: 2em; }
+table.timetable th, table.timetable td { border: 1px solid #ccc; padding: 0.25em; vertical-align: top; }
+table.timetable tbody th { width: 4em; text-align: right; }
+.event { border-radius: 0.25em; padding: 0.25em; margin-bottom: 0.25em; font-size: 0.85em; }
+.event .time { font-weight: bold; }
+";
+
+pub fn write(course: &Course, filter: &Filter, output: &mut dyn Write) -> Result<(), Error> {
+    let events = course.events().filter(|event| filter.matches(event)).collect::<Vec<_>>();
+    let title = escape_html(&format!("{} {}", course.code(), course.name()));
+
+    writeln!(output, "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    write!(output, "<title>{}</title>\n<style>\n{}", title, STYLE)?;
+    for kind in events.iter().map(Event::kind).collect::<BTreeSet<_>>() {
+        writeln!(output, ".kind-{} {{ background: {}; }}", class(kind), colour(kind))?;
+    }
+    writeln!(output, "</style>\n</head>\n<body>\n<h1>{}</h1>", title)?;
+
+    // Every week shares the same hours and days so that they line up
+    let first_hour = events.iter().map(|event| event.start().hour()).min().unwrap_or(9);
+    let last_hour = events.iter().map(|event| last_hour(event)).max().unwrap_or(17);
+    let weekend = events.iter().any(|event| event.start().weekday().num_days_from_monday() >= 5);
+    let days = if weekend { 7 } else { 5 };
+
+    for week in 0..course.week_count() {
+        let start = match course.week_start(week) {
+            Some(start) => start,
+            None => continue,
+        };
+        let events = events.iter().filter(|event| event.week() == Some(week)).collect::<Vec<_>>();
+
+        writeln!(output, "<section>\n<h2>Week {} ({})</h2>", course.week_number(week), start.format("%a %-d %b"))?;
+        writeln!(output, "<table class=\"timetable\">\n<thead>\n<tr><th></th>")?;
+        for day in 0..days {
+            writeln!(output, "<th>{}</th>", (start + Duration::days(day)).format("%a %-d %b"))?;
+        }
+        writeln!(output, "</tr>\n</thead>\n<tbody>")?;
+
+        for hour in first_hour..=last_hour {
+            writeln!(output, "<tr><th>{:02}:00</th>", hour)?;
+            for day in 0..days {
+                write!(output, "<td>")?;
+                let starting = events.iter().filter(|event| {
+                    event.start().weekday().num_days_from_monday() as i64 == day && event.start().hour() == hour
+                });
+                for event in starting {
+                    write!(output, "{}", cell(event))?;
+                }
+                writeln!(output, "</td>")?;
+            }
+            writeln!(output, "</tr>")?;
+        }
+        writeln!(output, "</tbody>\n</table>\n</section>")?;
+    }
+
+    writeln!(output, "</body>\n</html>")?;
+    Ok(())
+}
+
+/// The last hour in which an event is running
+fn last_hour(event: &Event) -> u32 {
+    let end = event.end() - Duration::seconds(1);
+    if end.date() == event.start().date() { end.hour().max(event.start().hour()) } else { 23 }
+}
+
+fn cell(event: &Event) -> String {
+    let mut cell = format!(
+        "<div class=\"event kind-{}\"><span class=\"time\">{}–{}</span> ",
+        class(event.kind()),
+        event.start().format("%H:%M"),
+        event.end().format("%H:%M"),
+    );
+    match event.link() {
+        Some(link) => cell.push_str(&format!("<a href=\"{}\">{}</a>", escape_html(link.as_str()), escape_html(&event.name()))),
+        None => cell.push_str(&escape_html(&event.name())),
+    }
+    if event.kind() != event.name() {
+        cell.push_str(&format!(" ({})", escape_html(event.kind())));
+    }
+    if let Some(location) = event.location() {
+        cell.push_str(&format!("<br>{}", escape_html(location)));
+    }
+    let presenters = event.presenters().collect::<Vec<_>>();
+    if !presenters.is_empty() {
+        cell.push_str(&format!("<br>{}", escape_html(&presenters.join(", "))));
+    }
+    cell.push_str("</div>");
+    cell
+}
+
+/// A CSS class name for a kind of event
+fn class(kind: &str) -> String {
+    kind.chars().map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '-' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::export::tests::{course, export};
+    use crate::export::Format;
+
+    const WEEKEND: &str = r#"
+        version = 2
+        code = "COMP1000"
+        name = "Testing & Debugging"
+        link = "https://example.edu/comp1000"
+
+        [[week]]
+        start = 2024-02-12T00:00:00+11:00
+
+        [[session]]
+        first = 2024-02-12T10:00:00+11:00
+        kind = "lecture"
+        location = "Quad <G040>"
+        duration = 7200
+        weeks = [1]
+
+        [[session]]
+        first = 2024-02-17T13:30:00+11:00
+        kind = "help session"
+        duration = 3600
+        weeks = [1]
+        "#;
+
+    #[test]
+    fn kinds_become_class_names() {
+        assert_eq!(class("help session"), "help-session");
+        assert_eq!(class("Lab/Tute"), "lab-tute");
+    }
+
+    #[test]
+    fn sessions_ending_on_the_hour_leave_it_free() {
+        let course = course(WEEKEND);
+        let hours = course.events().map(|event| (event.start().hour(), last_hour(&event))).collect::<Vec<_>>();
+        assert_eq!(hours, [(10, 11), (13, 14)]);
+    }
+
+    #[test]
+    fn weeks_are_grids_covering_every_session() {
+        let page = export(Format::Html, &course(WEEKEND));
+        assert!(page.contains("<title>COMP1000 Testing &amp; Debugging</title>"));
+        assert!(page.contains(".kind-help-session { background: hsl("));
+        assert!(page.contains("<h2>Week 1 (Mon 12 Feb)</h2>"));
+        // Saturday sessions add the weekend to the grid
+        assert!(page.contains("<th>Sun 18 Feb</th>"));
+        let hours = page.lines().filter(|line| line.starts_with("<tr><th>") && line.ends_with(":00</th>")).collect::<Vec<_>>();
+        assert_eq!(hours, ["<tr><th>10:00</th>", "<tr><th>11:00</th>", "<tr><th>12:00</th>", "<tr><th>13:00</th>", "<tr><th>14:00</th>"]);
+        assert!(page.contains("<td><div class=\"event kind-lecture\"><span class=\"time\">10:00–12:00</span> lecture<br>Quad &lt;G040&gt;</div></td>"));
+    }
+}
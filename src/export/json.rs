@@ -0,0 +1,89 @@
+//! A JSON document with the expanded list of events
+
+use failure::Error;
+
+use crate::calendar;
+use crate::course::Course;
+use crate::filter::Filter;
+use crate::json::Json;
+
+use std::io::Write;
+
+pub fn write(course: &Course, filter: &Filter, output: &mut dyn Write) -> Result<(), Error> {
+    let events = calendar::identified(course)
+        .into_iter()
+        .filter(|(_, event)| filter.matches(event))
+        .map(|(uid, event)| {
+            Json::object(vec![
+                ("uid", uid.into()),
+                ("summary", format!("{} {}", course.code(), event.title()).into()),
+                ("name", event.name().into()),
+                ("kind", event.kind().into()),
+                ("start", event.start().to_rfc3339().into()),
+                ("end", event.end().to_rfc3339().into()),
+                ("week", event.week().map(|week| course.week_number(week) as i64).into()),
+                ("stream", event.stream().into()),
+                ("location", event.location().into()),
+                ("presenters", event.presenters().collect::<Vec<_>>().into()),
+                ("link", event.link().map(|link| link.to_string()).into()),
+                ("attachments", event.attachments().map(|url| url.to_string()).collect::<Vec<_>>().into()),
+                ("description", event.description().into()),
+                ("tags", event.tags().collect::<Vec<_>>().into()),
+            ])
+        })
+        .collect::<Vec<_>>();
+
+    let document = Json::object(vec![
+        ("code", course.code().into()),
+        ("name", course.name().into()),
+        ("events", Json::Array(events)),
+    ]);
+    writeln!(output, "{}", document.pretty())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::tests::{course, export};
+    use crate::export::Format;
+    use crate::json::Json;
+
+    #[test]
+    fn events_carry_their_uid_week_and_stream() {
+        let course = course(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/comp1000"
+            week_numbering = "zero-based"
+
+            [[week]]
+            start = 2024-02-05T00:00:00+11:00
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-13T09:00:00+11:00
+            kind = "tutorial"
+            stream = "T09A"
+            duration = 3600
+            weeks = [1]
+            tags = ["online"]
+            "#,
+        );
+        let document = Json::parse(&export(Format::Json, &course)).unwrap();
+        assert_eq!(document.get("code").and_then(Json::as_str), Some("COMP1000"));
+
+        let events = document.get("events").and_then(Json::as_array).unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.get("uid").and_then(Json::as_str), Some("comp1000-tutorial-2-t09a@course-calendar"));
+        assert_eq!(event.get("week").and_then(Json::as_f64), Some(1.0));
+        assert_eq!(event.get("stream").and_then(Json::as_str), Some("T09A"));
+        assert_eq!(event.get("start").and_then(Json::as_str), Some("2024-02-13T09:00:00+11:00"));
+        assert_eq!(event.get("location"), Some(&Json::Null));
+        assert_eq!(event.get("tags"), Some(&Json::Array(vec!["online".into()])));
+    }
+}
@@ -0,0 +1,134 @@
+//! A LaTeX longtable of every session and assessment, for inclusion in a course outline
+
+use failure::Error;
+
+use crate::course::Course;
+use crate::filter::Filter;
+
+use std::io::Write;
+
+pub fn write(course: &Course, filter: &Filter, output: &mut dyn Write) -> Result<(), Error> {
+    writeln!(output, "% Schedule for {} {}, requires \\usepackage{{longtable}}", course.code(), course.name())?;
+    writeln!(output, "\\begin{{longtable}}{{r l l p{{0.35\\linewidth}} l}}")?;
+    writeln!(output, "\\caption{{{} schedule}} \\\\", escape(course.code()))?;
+    writeln!(output, "\\hline")?;
+    writeln!(output, "Week & Date & Time & Activity & Location \\\\")?;
+    writeln!(output, "\\hline")?;
+    writeln!(output, "\\endfirsthead")?;
+    writeln!(output, "\\hline")?;
+    writeln!(output, "Week & Date & Time & Activity & Location \\\\")?;
+    writeln!(output, "\\hline")?;
+    writeln!(output, "\\endhead")?;
+    writeln!(output, "\\hline")?;
+    writeln!(output, "\\endfoot")?;
+
+    let mut last_week = None;
+    for event in course.events().filter(|event| filter.matches(event)) {
+        if last_week.is_some() && last_week != Some(event.week()) {
+            writeln!(output, "\\hline")?;
+        }
+        last_week = Some(event.week());
+
+        let week = event.week().map(|week| course.week_number(week).to_string()).unwrap_or_default();
+        let time = if event.kind() == "submission" {
+            format!("Due {}", event.start().format("%H:%M"))
+        } else {
+            format!("{}--{}", event.start().format("%H:%M"), event.end().format("%H:%M"))
+        };
+        let mut activity = escape(&event.name());
+        if !event.kind().eq_ignore_ascii_case(&event.name()) {
+            activity.push_str(&format!(" ({})", escape(event.kind())));
+        }
+        writeln!(
+            output,
+            "{} & {} & {} & {} & {} \\\\",
+            week,
+            event.start().format("%a %-d %b"),
+            time,
+            activity,
+            escape(event.location().unwrap_or_default()),
+        )?;
+    }
+
+    writeln!(output, "\\end{{longtable}}")?;
+    Ok(())
+}
+
+/// Escape the characters that have special meaning in LaTeX
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' | '%' | '$' | '#' | '_' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '~' => escaped.push_str("\\textasciitilde{}"),
+            '^' => escaped.push_str("\\textasciicircum{}"),
+            '\\' => escaped.push_str("\\textbackslash{}"),
+            '\n' => escaped.push(' '),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::export::tests::{course, export};
+    use crate::export::Format;
+
+    #[test]
+    fn special_characters_are_escaped() {
+        assert_eq!(escape("50% of C#_1 & {more}"), "50\\% of C\\#\\_1 \\& \\{more\\}");
+        assert_eq!(escape("~user/^\\"), "\\textasciitilde{}user/\\textasciicircum{}\\textbackslash{}");
+    }
+
+    #[test]
+    fn weeks_are_separated_by_rules() {
+        let course = course(
+            r#"
+            version = 2
+            code = "COMP_1000"
+            name = "Testing"
+            link = "https://example.edu/comp1000"
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[week]]
+            start = 2024-02-19T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-12T10:00:00+11:00
+            kind = "lecture"
+            location = "Room 1 & 2"
+            duration = 3600
+            weeks = [1, 2]
+
+            [[assignment]]
+            name = "Essay"
+            link = "https://example.edu/essay"
+
+            [[assignment.submission]]
+            time = 2024-02-16T17:00:00+11:00
+            name = "Draft"
+            "#,
+        );
+        let table = export(Format::Latex, &course);
+        assert!(table.contains("\\caption{COMP\\_1000 schedule} \\\\\n"));
+        let rows = table.lines().skip_while(|line| *line != "\\endfoot").skip(1).collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            [
+                "1 & Mon 12 Feb & 10:00--11:00 & lecture & Room 1 \\& 2 \\\\",
+                "1 & Fri 16 Feb & Due 17:00 & Essay: Draft (submission) &  \\\\",
+                "\\hline",
+                "2 & Mon 19 Feb & 10:00--11:00 & lecture & Room 1 \\& 2 \\\\",
+                "\\end{longtable}",
+            ]
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! A Markdown document with a section for each week
+
+use failure::Error;
+
+use crate::course::{Course, Event};
+use crate::filter::Filter;
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+pub fn write(course: &Course, filter: &Filter, output: &mut dyn Write) -> Result<(), Error> {
+    writeln!(output, "# {} {}", escape(course.code()), escape(course.name()))?;
+
+    let mut weeks: BTreeMap<Option<usize>, Vec<Event>> = BTreeMap::new();
+    for event in course.events().filter(|event| filter.matches(event)) {
+        weeks.entry(event.week()).or_default().push(event);
+    }
+
+    for (week, events) in &weeks {
+        match week {
+            Some(week) => {
+                let start = course.week_start(*week).map(|start| format!(" ({})", start.format("%a %-d %b")));
+                writeln!(output, "\n## Week {}{}", course.week_number(*week), start.unwrap_or_default())?;
+            }
+            None => writeln!(output, "\n## Before the first week")?,
+        }
+
+        let (deadlines, sessions): (Vec<_>, Vec<_>) = events.iter().partition(|event| event.is_point_in_time());
+        if !sessions.is_empty() {
+            writeln!(output, "\n### Sessions\n")?;
+            for event in sessions {
+                writeln!(output, "- {}", describe(event, true))?;
+            }
+        }
+        if !deadlines.is_empty() {
+            writeln!(output, "\n### Deadlines\n")?;
+            for event in deadlines {
+                writeln!(output, "- {}", describe(event, false))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A list item for an event, with its end time if it's a session
+fn describe(event: &Event, session: bool) -> String {
+    let time = if session {
+        format!("{}–{}", event.start().format("%a %-d %b %H:%M"), event.end().format("%H:%M"))
+    } else {
+        event.start().format("%a %-d %b %H:%M").to_string()
+    };
+    let name = match event.link() {
+        Some(link) => format!("[{}]({})", escape(&event.name()), link),
+        None => escape(&event.name()),
+    };
+
+    let mut item = format!("**{}** {}", time, name);
+    if session && event.kind() != event.name() {
+        item.push_str(&format!(" ({})", escape(event.kind())));
+    }
+    if let Some(stream) = event.stream() {
+        item.push_str(&format!(", stream {}", escape(stream)));
+    }
+    if let Some(location) = event.location() {
+        item.push_str(&format!(" in {}", escape(location)));
+    }
+    let presenters = event.presenters().collect::<Vec<_>>();
+    if !presenters.is_empty() {
+        item.push_str(&format!(" with {}", escape(&presenters.join(", "))));
+    }
+    item
+}
+
+/// Escape characters that Markdown would interpret as formatting
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\`*_[]<>#".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::export::tests::{course, export};
+    use crate::export::Format;
+
+    #[test]
+    fn formatting_characters_are_escaped() {
+        assert_eq!(escape("C# & *pointers* [intro]"), "C\\# & \\*pointers\\* \\[intro\\]");
+        assert_eq!(escape("snake_case <T>"), "snake\\_case \\<T\\>");
+    }
+
+    #[test]
+    fn weeks_list_sessions_before_deadlines() {
+        let course = course(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/comp1000"
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-12T10:00:00+11:00
+            kind = "lecture"
+            location = "Quad_1001"
+            presenters = ["Ada"]
+            duration = 7200
+            weeks = [1]
+
+            [[assignment]]
+            name = "Assignment 1"
+            link = "https://example.edu/a1"
+
+            [[assignment.submission]]
+            time = 2024-02-16T17:00:00+11:00
+            name = "Report"
+            "#,
+        );
+        assert_eq!(
+            export(Format::Markdown, &course),
+            "# COMP1000 Testing\n\
+             \n## Week 1 (Mon 12 Feb)\n\
+             \n### Sessions\n\n\
+             - **Mon 12 Feb 10:00–12:00** lecture in Quad\\_1001 with Ada\n\
+             \n### Deadlines\n\n\
+             - **Fri 16 Feb 17:00** [Assignment 1: Report](https://example.edu/a1)\n"
+        );
+    }
+}
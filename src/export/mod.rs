@@ -0,0 +1,132 @@
+//! Output formats other than iCalendar
+
+use failure::{format_err, Error};
+
+use crate::course::Course;
+use crate::filter::Filter;
+use crate::state::{self, State};
+
+use std::io::Write;
+
+mod atom;
+mod csv;
+mod freebusy;
+mod html;
+mod json;
+mod latex;
+mod markdown;
+mod org;
+mod pdf;
+mod remind;
+mod svg;
+mod taskwarrior;
+
+/// The format in which to write the events of a course
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    Ics,
+    Csv,
+    Json,
+    Markdown,
+    Html,
+    Svg,
+    Pdf,
+    Latex,
+    Org,
+    Remind,
+    Taskwarrior,
+    Atom,
+    FreeBusy,
+}
+
+impl Format {
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "ics" => Ok(Format::Ics),
+            "csv" => Ok(Format::Csv),
+            "json" => Ok(Format::Json),
+            "markdown" | "md" => Ok(Format::Markdown),
+            "html" => Ok(Format::Html),
+            "svg" => Ok(Format::Svg),
+            "pdf" => Ok(Format::Pdf),
+            "latex" | "tex" => Ok(Format::Latex),
+            "org" => Ok(Format::Org),
+            "remind" | "rem" => Ok(Format::Remind),
+            "taskwarrior" | "task" => Ok(Format::Taskwarrior),
+            "atom" => Ok(Format::Atom),
+            "freebusy" => Ok(Format::FreeBusy),
+            _ => Err(format_err!("Unknown format {}", name)),
+        }
+    }
+}
+
+/// Write the events of a course that match a filter in a format other than iCalendar
+pub fn write(format: Format, course: &Course, filter: &Filter, state: &State, output: &mut dyn Write) -> Result<(), Error> {
+    match format {
+        Format::Ics => unreachable!("iCalendar output is generated by the calendar module"),
+        Format::Csv => csv::write(course, filter, output),
+        Format::Json => json::write(course, filter, output),
+        Format::Markdown => markdown::write(course, filter, output),
+        Format::Html => html::write(course, filter, output),
+        Format::Svg => svg::write(course, filter, output),
+        Format::Pdf => pdf::write(course, filter, output),
+        Format::Latex => latex::write(course, filter, output),
+        Format::Org => org::write(course, filter, output),
+        Format::Remind => remind::write(course, filter, output),
+        Format::Taskwarrior => taskwarrior::write(course, filter, output),
+        Format::Atom => atom::write(course, filter, state, output),
+        Format::FreeBusy => freebusy::write(course, filter, output),
+    }
+}
+
+/// A hue chosen consistently for a kind of event
+fn hue(kind: &str) -> u32 {
+    (state::hash(kind) % 360) as u32
+}
+
+/// A pale CSS colour for a kind of event
+fn colour(kind: &str) -> String {
+    format!("hsl({}, 70%, 85%)", hue(kind))
+}
+
+/// A UUID derived from the UID of an event, for formats that require one
+fn uuid(uid: &str) -> String {
+    let high = state::hash(uid);
+    let low = state::hash(&format!("{}\0", uid));
+    format!(
+        "{:08x}-{:04x}-4{:03x}-{:04x}-{:012x}",
+        high >> 32,
+        (high >> 16) & 0xffff,
+        high & 0xfff,
+        0x8000 | ((low >> 48) & 0x3fff),
+        low & 0xffff_ffff_ffff,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A course generated from a specification, as it would be for export
+    pub(super) fn course(spec: &str) -> Course {
+        let mut course = Course::from_value(toml::from_str(spec).unwrap()).unwrap();
+        course.generate_repeats().unwrap();
+        course
+    }
+
+    /// The output of a format for every event in a course
+    pub(super) fn export(format: Format, course: &Course) -> String {
+        let mut output = Vec::new();
+        write(format, course, &Filter::default(), &State::default(), &mut output).unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn formats_are_named() {
+        assert_eq!(Format::from_name("md").unwrap(), Format::Markdown);
+        assert_eq!(Format::from_name("tex").unwrap(), Format::Latex);
+        assert_eq!(Format::from_name("csv").unwrap(), Format::Csv);
+        assert_eq!(Format::from_name("xlsx").unwrap_err().to_string(), "Unknown format xlsx");
+    }
+}
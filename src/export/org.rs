@@ -0,0 +1,139 @@
+//! An Emacs Org document with scheduled headings for sessions and deadlines for submissions
+
+use failure::Error;
+
+use crate::course::{Course, Event};
+use crate::filter::Filter;
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+pub fn write(course: &Course, filter: &Filter, output: &mut dyn Write) -> Result<(), Error> {
+    writeln!(output, "#+TITLE: {} {}", course.code(), course.name())?;
+
+    let mut weeks: BTreeMap<Option<usize>, Vec<Event>> = BTreeMap::new();
+    for event in course.events().filter(|event| filter.matches(event)) {
+        weeks.entry(event.week()).or_default().push(event);
+    }
+
+    for (week, events) in &weeks {
+        match week {
+            Some(week) => writeln!(output, "* Week {}", course.week_number(*week))?,
+            None => writeln!(output, "* Before the first week")?,
+        }
+
+        for event in events {
+            let heading = format!("{} {}", course.code(), event.name()).replace('\n', " ");
+            writeln!(output, "** {} :{}:", heading, tag(event.kind()))?;
+            if event.kind() == "submission" {
+                writeln!(output, "DEADLINE: {}", event.start().format("<%Y-%m-%d %a %H:%M>"))?;
+            } else {
+                writeln!(
+                    output,
+                    "SCHEDULED: {}-{}>",
+                    event.start().format("<%Y-%m-%d %a %H:%M"),
+                    event.end().format("%H:%M"),
+                )?;
+            }
+
+            let presenters = event.presenters().collect::<Vec<_>>();
+            if event.location().is_some() || !presenters.is_empty() || event.link().is_some() {
+                writeln!(output, ":PROPERTIES:")?;
+                if let Some(location) = event.location() {
+                    writeln!(output, ":LOCATION: {}", location)?;
+                }
+                if !presenters.is_empty() {
+                    writeln!(output, ":PRESENTERS: {}", presenters.join(", "))?;
+                }
+                if let Some(link) = event.link() {
+                    writeln!(output, ":URL: {}", link)?;
+                }
+                writeln!(output, ":END:")?;
+            }
+
+            if let Some(description) = event.description() {
+                for line in description.lines() {
+                    // Lines starting with an asterisk would otherwise become headings
+                    if line.starts_with('*') {
+                        writeln!(output, " {}", line)?;
+                    } else {
+                        writeln!(output, "{}", line)?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// A kind of event as an Org tag, which may only contain word characters
+fn tag(kind: &str) -> String {
+    kind.chars()
+        .map(|c| if c.is_alphanumeric() || c == '_' || c == '@' { c } else { '_' })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::export::tests::{course, export};
+    use crate::export::Format;
+
+    #[test]
+    fn kinds_become_tags() {
+        assert_eq!(tag("help session"), "help_session");
+        assert_eq!(tag("lab-exam@home"), "lab_exam@home");
+    }
+
+    #[test]
+    fn sessions_are_scheduled_and_submissions_have_deadlines() {
+        let course = course(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/comp1000"
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-12T10:00:00+11:00
+            kind = "help session"
+            location = "Quad 1001"
+            presenters = ["Ada", "Grace"]
+            duration = 3600
+            weeks = [1]
+
+            [[assignment]]
+            name = "Assignment"
+            link = "https://example.edu/assignment"
+
+            [[assignment.submission]]
+            time = 2024-02-16T17:00:00+11:00
+            name = "Final"
+            description = "Submit:\n* the report\n* the code"
+            "#,
+        );
+        assert_eq!(
+            export(Format::Org, &course),
+            "#+TITLE: COMP1000 Testing\n\
+             * Week 1\n\
+             ** COMP1000 help session :help_session:\n\
+             SCHEDULED: <2024-02-12 Mon 10:00-11:00>\n\
+             :PROPERTIES:\n\
+             :LOCATION: Quad 1001\n\
+             :PRESENTERS: Ada, Grace\n\
+             :END:\n\
+             ** COMP1000 Assignment: Final :submission:\n\
+             DEADLINE: <2024-02-16 Fri 17:00>\n\
+             :PROPERTIES:\n\
+             :URL: https://example.edu/assignment\n\
+             :END:\n\
+             Submit:\n \
+             * the report\n \
+             * the code\n"
+        );
+    }
+}
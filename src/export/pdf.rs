@@ -0,0 +1,330 @@
+//! A printable PDF with a timetable for each week and a table of deadlines
+
+use chrono::{Datelike, Timelike};
+use failure::Error;
+
+use crate::course::{Course, Event};
+use crate::filter::Filter;
+
+use super::hue;
+
+use std::io::Write;
+
+/// Landscape A4 in points
+const PAGE_WIDTH: f64 = 842.0;
+const PAGE_HEIGHT: f64 = 595.0;
+const MARGIN: f64 = 36.0;
+const GUTTER: f64 = 36.0;
+const HEADER: f64 = 40.0;
+
+pub fn write(course: &Course, filter: &Filter, output: &mut dyn Write) -> Result<(), Error> {
+    let events = course.events().filter(|event| filter.matches(event)).collect::<Vec<_>>();
+    let title = format!("{} {}", course.code(), course.name());
+    let mut pages = Vec::new();
+
+    let first_hour = events.iter().map(|event| event.start().hour()).min().unwrap_or(9);
+    let last_hour = events
+        .iter()
+        .map(|event| (event.start().hour() * 60 + event.start().minute() + event.duration().num_minutes() as u32).div_ceil(60))
+        .max()
+        .unwrap_or(17)
+        .clamp(first_hour + 1, 24);
+    let weekend = events.iter().any(|event| event.start().weekday().num_days_from_monday() >= 5);
+    let days = if weekend { 7 } else { 5 };
+
+    for week in 0..course.week_count() {
+        let week_events = events.iter().filter(|event| event.week() == Some(week)).collect::<Vec<_>>();
+        let mut page = Page::default();
+        page.text(MARGIN, MARGIN, 16.0, true, &format!("{}, week {}", title, course.week_number(week)));
+
+        let day_width = (PAGE_WIDTH - 2.0 * MARGIN - GUTTER) / days as f64;
+        let hour_height = (PAGE_HEIGHT - 2.0 * MARGIN - HEADER) / (last_hour - first_hour) as f64;
+        let top = MARGIN + HEADER;
+        let start = course.week_start(week);
+
+        for day in 0..days {
+            let x = MARGIN + GUTTER + day as f64 * day_width;
+            let label = match start {
+                Some(start) => (start + chrono::Duration::days(day as i64)).format("%a %-d %b").to_string(),
+                None => String::new(),
+            };
+            page.text(x + 4.0, top - 8.0, 10.0, true, &label);
+            page.line(x, top, x, PAGE_HEIGHT - MARGIN);
+        }
+        page.line(PAGE_WIDTH - MARGIN, top, PAGE_WIDTH - MARGIN, PAGE_HEIGHT - MARGIN);
+        for hour in first_hour..=last_hour {
+            let y = top + (hour - first_hour) as f64 * hour_height;
+            page.line(MARGIN + GUTTER, y, PAGE_WIDTH - MARGIN, y);
+            page.text(MARGIN, y + 8.0, 8.0, false, &format!("{:02}:00", hour));
+        }
+
+        for day in 0..days {
+            let mut day_events = week_events
+                .iter()
+                .filter(|event| event.start().weekday().num_days_from_monday() == day)
+                .collect::<Vec<_>>();
+            day_events.sort_by_key(|event| event.start());
+
+            // Overlapping events share the width of the day side by side
+            let mut lanes: Vec<chrono::DateTime<chrono::FixedOffset>> = Vec::new();
+            let mut placed = Vec::new();
+            for event in day_events {
+                let lane = lanes.iter().position(|end| *end <= event.start()).unwrap_or_else(|| {
+                    lanes.push(event.start());
+                    lanes.len() - 1
+                });
+                lanes[lane] = event.end();
+                placed.push((lane, event));
+            }
+
+            let lane_width = day_width / lanes.len().max(1) as f64;
+            for (lane, event) in placed {
+                let minutes = (event.start().hour() * 60 + event.start().minute()) as f64 - (first_hour * 60) as f64;
+                let x = MARGIN + GUTTER + day as f64 * day_width + lane as f64 * lane_width;
+                let y = top + minutes / 60.0 * hour_height;
+                let height = (event.duration().num_minutes() as f64 / 60.0 * hour_height).max(12.0);
+                page.event(event, x + 1.0, y + 1.0, lane_width - 2.0, height - 2.0);
+            }
+        }
+        pages.push(page);
+    }
+
+    let deadlines = events.iter().filter(|event| event.kind() == "submission").collect::<Vec<_>>();
+    let rows_per_page = ((PAGE_HEIGHT - 2.0 * MARGIN - HEADER) / 18.0) as usize;
+    for chunk in deadlines.chunks(rows_per_page.max(1)) {
+        let mut page = Page::default();
+        page.text(MARGIN, MARGIN, 16.0, true, &format!("{}, deadlines", title));
+        let columns = [MARGIN, MARGIN + 160.0, PAGE_WIDTH - MARGIN - 60.0];
+        let mut y = MARGIN + HEADER;
+        for (x, heading) in columns.iter().zip(&["Due", "Assessment", "Week"]) {
+            page.text(*x, y, 10.0, true, heading);
+        }
+        for event in chunk {
+            y += 18.0;
+            page.line(MARGIN, y - 12.0, PAGE_WIDTH - MARGIN, y - 12.0);
+            page.text(columns[0], y, 10.0, false, &event.start().format("%a %-d %b %H:%M").to_string());
+            page.text(columns[1], y, 10.0, false, &event.name());
+            let week = event.week().map(|week| course.week_number(week).to_string()).unwrap_or_default();
+            page.text(columns[2], y, 10.0, false, &week);
+        }
+        pages.push(page);
+    }
+
+    if pages.is_empty() {
+        let mut page = Page::default();
+        page.text(MARGIN, MARGIN, 16.0, true, &title);
+        pages.push(page);
+    }
+    write_document(output, &pages)
+}
+
+/// The drawing operations on a single page, measured from the top left
+#[derive(Debug, Default)]
+struct Page {
+    content: String,
+}
+
+impl Page {
+    fn text(&mut self, x: f64, y: f64, size: f64, bold: bool, text: &str) {
+        let font = if bold { "F2" } else { "F1" };
+        self.content.push_str(&format!(
+            "BT /{} {} Tf {:.1} {:.1} Td ({}) Tj ET\n",
+            font,
+            size,
+            x,
+            PAGE_HEIGHT - y,
+            encode(text),
+        ));
+    }
+
+    fn line(&mut self, x1: f64, y1: f64, x2: f64, y2: f64) {
+        self.content.push_str(&format!(
+            "0.8 G 0.5 w {:.1} {:.1} m {:.1} {:.1} l S\n",
+            x1,
+            PAGE_HEIGHT - y1,
+            x2,
+            PAGE_HEIGHT - y2,
+        ));
+    }
+
+    fn event(&mut self, event: &Event, x: f64, y: f64, width: f64, height: f64) {
+        let (r, g, b) = pale(hue(event.kind()));
+        self.content.push_str(&format!(
+            "{:.3} {:.3} {:.3} rg 0.5 G {:.1} {:.1} {:.1} {:.1} re B\n0 g\n",
+            r,
+            g,
+            b,
+            x,
+            PAGE_HEIGHT - y - height,
+            width,
+            height,
+        ));
+
+        // Helvetica averages about half its size in width
+        let fit = |text: &str, size: f64| {
+            let limit = ((width - 4.0) / (size * 0.5)).max(1.0) as usize;
+            text.chars().take(limit).collect::<String>()
+        };
+        self.text(x + 2.0, y + 9.0, 8.0, true, &fit(&event.name(), 8.0));
+        if height >= 20.0 {
+            let time = format!("{}-{}", event.start().format("%H:%M"), event.end().format("%H:%M"));
+            self.text(x + 2.0, y + 18.0, 7.0, false, &fit(&time, 7.0));
+        }
+        if let (Some(location), true) = (event.location(), height >= 28.0) {
+            self.text(x + 2.0, y + 26.0, 7.0, false, &fit(location, 7.0));
+        }
+    }
+}
+
+/// The RGB components of a pale colour with a particular hue
+fn pale(hue: u32) -> (f64, f64, f64) {
+    let (saturation, lightness) = (0.7, 0.85);
+    let chroma = (1.0 - (2.0 * lightness - 1.0f64).abs()) * saturation;
+    let section = hue as f64 / 60.0;
+    let second = chroma * (1.0 - (section % 2.0 - 1.0).abs());
+    let (r, g, b) = match section as u32 {
+        0 => (chroma, second, 0.0),
+        1 => (second, chroma, 0.0),
+        2 => (0.0, chroma, second),
+        3 => (0.0, second, chroma),
+        4 => (second, 0.0, chroma),
+        _ => (chroma, 0.0, second),
+    };
+    let offset = lightness - chroma / 2.0;
+    (r + offset, g + offset, b + offset)
+}
+
+/// Encode text as a PDF string in the standard fonts' WinAnsi encoding
+fn encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '(' | ')' | '\\' => {
+                encoded.push('\\');
+                encoded.push(c);
+            }
+            ' '..='~' => encoded.push(c),
+            '–' => encoded.push_str("\\226"),
+            '—' => encoded.push_str("\\227"),
+            c if ('\u{a0}'..='\u{ff}').contains(&c) => encoded.push_str(&format!("\\{:03o}", c as u32)),
+            _ => encoded.push('?'),
+        }
+    }
+    encoded
+}
+
+fn write_document(output: &mut dyn Write, pages: &[Page]) -> Result<(), Error> {
+    let mut objects = vec![
+        "<< /Type /Catalog /Pages 2 0 R >>".to_owned(),
+        String::new(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica /Encoding /WinAnsiEncoding >>".to_owned(),
+        "<< /Type /Font /Subtype /Type1 /BaseFont /Helvetica-Bold /Encoding /WinAnsiEncoding >>".to_owned(),
+    ];
+    let mut kids = Vec::new();
+    for page in pages {
+        let page_id = objects.len() + 1;
+        kids.push(format!("{} 0 R", page_id));
+        objects.push(format!(
+            "<< /Type /Page /Parent 2 0 R /MediaBox [0 0 {} {}] /Resources << /Font << /F1 3 0 R /F2 4 0 R >> >> /Contents {} 0 R >>",
+            PAGE_WIDTH,
+            PAGE_HEIGHT,
+            page_id + 1,
+        ));
+        objects.push(format!("<< /Length {} >>\nstream\n{}endstream", page.content.len(), page.content));
+    }
+    objects[1] = format!("<< /Type /Pages /Kids [{}] /Count {} >>", kids.join(" "), pages.len());
+
+    let mut document = b"%PDF-1.4\n".to_vec();
+    let mut offsets = Vec::new();
+    for (index, object) in objects.iter().enumerate() {
+        offsets.push(document.len());
+        document.extend(format!("{} 0 obj\n{}\nendobj\n", index + 1, object).as_bytes());
+    }
+    let xref = document.len();
+    document.extend(format!("xref\n0 {}\n0000000000 65535 f \n", objects.len() + 1).as_bytes());
+    for offset in offsets {
+        document.extend(format!("{:010} 00000 n \n", offset).as_bytes());
+    }
+    document.extend(format!("trailer\n<< /Size {} /Root 1 0 R >>\nstartxref\n{}\n%%EOF\n", objects.len() + 1, xref).as_bytes());
+
+    output.write_all(&document)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::export::tests::{course, export};
+    use crate::export::Format;
+
+    #[test]
+    fn text_is_encoded_for_the_standard_fonts() {
+        assert_eq!(encode("Lab (K17) \\ 10:00–11:00"), "Lab \\(K17\\) \\\\ 10:00\\22611:00");
+        assert_eq!(encode("Café"), "Caf\\351");
+        assert_eq!(encode("日本"), "??");
+    }
+
+    #[test]
+    fn colours_are_pale() {
+        let (r, g, b) = pale(0);
+        assert!((r - 0.955).abs() < 1e-9 && (g - 0.745).abs() < 1e-9 && (b - 0.745).abs() < 1e-9);
+        let (r, g, b) = pale(240);
+        assert!(b > r && (r - g).abs() < 1e-9);
+    }
+
+    #[test]
+    fn every_object_is_found_through_the_cross_reference_table() {
+        let course = course(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/comp1000"
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[week]]
+            start = 2024-02-19T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-12T10:00:00+11:00
+            kind = "lecture"
+            location = "Quad (G040)"
+            duration = 3600
+            weeks = [1, 2]
+
+            [[assignment]]
+            name = "Assignment"
+            link = "https://example.edu/assignment"
+
+            [[assignment.submission]]
+            time = 2024-02-23T17:00:00+11:00
+            name = "Final"
+            "#,
+        );
+        let document = export(Format::Pdf, &course);
+        assert!(document.starts_with("%PDF-1.4\n"));
+        // A page for each week, and one for the deadlines
+        assert!(document.contains("/Count 3 >>"));
+        assert!(document.contains("(COMP1000 Testing, deadlines)"));
+        assert!(document.contains("(Quad \\(G040\\))"));
+
+        let xref = document.lines().rev().nth(1).unwrap().parse::<usize>().unwrap();
+        let table = document[xref..].lines().collect::<Vec<_>>();
+        assert_eq!(table[..2], ["xref", "0 11"]);
+        for (index, entry) in table[3..13].iter().enumerate() {
+            let offset = entry[..10].parse::<usize>().unwrap();
+            assert!(document[offset..].starts_with(&format!("{} 0 obj\n", index + 1)));
+        }
+    }
+
+    #[test]
+    fn courses_without_events_have_a_title_page() {
+        let course = course("version = 2\ncode = \"COMP1000\"\nname = \"Testing\"\nlink = \"https://example.edu/comp1000\"");
+        let document = export(Format::Pdf, &course);
+        assert!(document.contains("/Count 1 >>"));
+        assert!(document.contains("Td (COMP1000 Testing) Tj"));
+    }
+}
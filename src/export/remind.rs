@@ -0,0 +1,123 @@
+//! A remind(1) file, with weekly sessions as repeating reminders
+
+use chrono::{DateTime, Duration, FixedOffset};
+use failure::Error;
+
+use crate::course::{Course, Event};
+use crate::filter::Filter;
+
+use std::io::Write;
+
+pub fn write(course: &Course, filter: &Filter, output: &mut dyn Write) -> Result<(), Error> {
+    writeln!(output, "# {} {}", course.code(), course.name())?;
+
+    // Chain together occurrences of the same session a week apart
+    let mut runs: Vec<Vec<Event>> = Vec::new();
+    for event in course.events().filter(|event| filter.matches(event)) {
+        let run = runs.iter_mut().find(|run| {
+            let last = run.last().expect("runs are never empty");
+            last.start() + Duration::weeks(1) == event.start()
+                && last.duration() == event.duration()
+                && last.kind() == event.kind()
+                && last.name() == event.name()
+                && last.location() == event.location()
+        });
+        match run {
+            Some(run) => run.push(event),
+            None => runs.push(vec![event]),
+        }
+    }
+    runs.sort_by_key(|run| run[0].start());
+
+    for run in &runs {
+        let (first, last) = (&run[0], &run[run.len() - 1]);
+        let mut line = format!("REM {}", date(first.start()));
+        if run.len() > 1 {
+            line.push_str(&format!(" *7 UNTIL {}", date(last.start())));
+        }
+        line.push_str(&format!(" AT {}", first.start().format("%H:%M")));
+        if first.kind() != "submission" {
+            let minutes = first.duration().num_minutes();
+            line.push_str(&format!(" DURATION {}:{:02}", minutes / 60, minutes % 60));
+        }
+
+        let mut message = format!("{} {}", course.code(), first.name());
+        if let Some(location) = first.location() {
+            message.push_str(&format!(" in {}", location));
+        }
+        writeln!(output, "{} MSG {} %b", line, escape(&message))?;
+    }
+    Ok(())
+}
+
+fn date(time: DateTime<FixedOffset>) -> String {
+    time.format("%-d %b %Y").to_string()
+}
+
+/// Escape the characters that remind would otherwise substitute or evaluate
+fn escape(text: &str) -> String {
+    text.replace('\n', " ").replace('%', "%%").replace('[', "[\"[\"]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::export::tests::{course, export};
+    use crate::export::Format;
+
+    #[test]
+    fn messages_cannot_substitute_or_evaluate() {
+        assert_eq!(escape("100% [marks]\nnow"), "100%% [\"[\"]marks] now");
+    }
+
+    #[test]
+    fn weekly_sessions_repeat_until_they_change() {
+        let course = course(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/comp1000"
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[week]]
+            start = 2024-02-19T00:00:00+11:00
+
+            [[week]]
+            start = 2024-02-26T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-12T10:00:00+11:00
+            kind = "lecture"
+            location = "Quad 1001"
+            duration = 5400
+            weeks = [1, 2]
+
+            [[session]]
+            first = 2024-02-26T10:00:00+11:00
+            kind = "lecture"
+            location = "Online"
+            duration = 5400
+            weeks = [3]
+
+            [[assignment]]
+            name = "Assignment"
+            link = "https://example.edu/assignment"
+
+            [[assignment.submission]]
+            time = 2024-02-23T17:00:00+11:00
+            name = "Final"
+            "#,
+        );
+        assert_eq!(
+            export(Format::Remind, &course),
+            "# COMP1000 Testing\n\
+             REM 12 Feb 2024 *7 UNTIL 19 Feb 2024 AT 10:00 DURATION 1:30 MSG COMP1000 lecture in Quad 1001 %b\n\
+             REM 23 Feb 2024 AT 17:00 MSG COMP1000 Assignment: Final %b\n\
+             REM 26 Feb 2024 AT 10:00 DURATION 1:30 MSG COMP1000 lecture in Online %b\n"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! An SVG timetable of a single representative week
+
+use chrono::{Datelike, Timelike};
+use failure::Error;
+
+use crate::course::{Course, Event};
+use crate::filter::Filter;
+use crate::markdown::escape_html;
+
+use super::colour;
+
+use std::io::Write;
+
+const DAY_WIDTH: u32 = 160;
+const HOUR_HEIGHT: u32 = 48;
+const GUTTER: u32 = 48;
+const HEADER: u32 = 28;
+
+/// Draw the busiest week of the course, so `--weeks` selects a particular week
+pub fn write(course: &Course, filter: &Filter, output: &mut dyn Write) -> Result<(), Error> {
+    let events = course.events().filter(|event| filter.matches(event)).collect::<Vec<_>>();
+    let week = (0..course.week_count()).max_by_key(|week| {
+        // Prefer the earliest of equally busy weeks
+        let count = events.iter().filter(|event| event.week() == Some(*week)).count();
+        (count, std::cmp::Reverse(*week))
+    });
+    let events = events.iter().filter(|event| week.is_some() && event.week() == week).collect::<Vec<_>>();
+
+    let first_hour = events.iter().map(|event| event.start().hour()).min().unwrap_or(9);
+    let last_hour = events.iter().map(|event| end_minutes(event).div_ceil(60)).max().unwrap_or(17).max(first_hour + 1);
+    let weekend = events.iter().any(|event| event.start().weekday().num_days_from_monday() >= 5);
+    let days: u32 = if weekend { 7 } else { 5 };
+    let width = GUTTER + days * DAY_WIDTH;
+    let height = HEADER + (last_hour - first_hour) * HOUR_HEIGHT;
+
+    writeln!(
+        output,
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\" font-family=\"sans-serif\" font-size=\"11\">",
+        width, height,
+    )?;
+    let title = match week {
+        Some(week) => format!("{} {}, week {}", course.code(), course.name(), course.week_number(week)),
+        None => format!("{} {}", course.code(), course.name()),
+    };
+    writeln!(output, "<title>{}</title>", escape_html(&title))?;
+    writeln!(output, "<rect width=\"{}\" height=\"{}\" fill=\"white\"/>", width, height)?;
+
+    for (day, name) in ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"].iter().enumerate().take(days as usize) {
+        let x = GUTTER + day as u32 * DAY_WIDTH;
+        writeln!(output, "<text x=\"{}\" y=\"18\" text-anchor=\"middle\" font-weight=\"bold\">{}</text>", x + DAY_WIDTH / 2, name)?;
+        writeln!(output, "<line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"#ccc\"/>", x, HEADER, height)?;
+    }
+    for hour in first_hour..=last_hour {
+        let y = HEADER + (hour - first_hour) * HOUR_HEIGHT;
+        writeln!(output, "<line x1=\"{0}\" y1=\"{1}\" x2=\"{2}\" y2=\"{1}\" stroke=\"#ccc\"/>", GUTTER, y, width)?;
+        writeln!(output, "<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{:02}:00</text>", GUTTER - 4, y + 4, hour)?;
+    }
+
+    for day in 0..days {
+        let mut day_events = events
+            .iter()
+            .filter(|event| event.start().weekday().num_days_from_monday() == day)
+            .collect::<Vec<_>>();
+        day_events.sort_by_key(|event| event.start());
+
+        // Overlapping events share the width of the day side by side
+        let mut lanes: Vec<u32> = Vec::new();
+        let mut placed = Vec::new();
+        for event in day_events {
+            let start = start_minutes(event);
+            let lane = match lanes.iter().position(|end| *end <= start) {
+                Some(lane) => lane,
+                None => {
+                    lanes.push(0);
+                    lanes.len() - 1
+                }
+            };
+            lanes[lane] = end_minutes(event);
+            placed.push((lane, event));
+        }
+
+        let lane_width = DAY_WIDTH / lanes.len().max(1) as u32;
+        for (lane, event) in placed {
+            let x = GUTTER + day * DAY_WIDTH + lane as u32 * lane_width;
+            let y = HEADER + (start_minutes(event) - first_hour * 60) * HOUR_HEIGHT / 60;
+            let height = ((end_minutes(event) - start_minutes(event)) * HOUR_HEIGHT / 60).max(12);
+            draw(output, event, x, y, lane_width, height)?;
+        }
+    }
+
+    writeln!(output, "</svg>")?;
+    Ok(())
+}
+
+fn draw(output: &mut dyn Write, event: &Event, x: u32, y: u32, width: u32, height: u32) -> Result<(), Error> {
+    let name = escape_html(&event.name());
+    let time = format!("{}–{}", event.start().format("%H:%M"), event.end().format("%H:%M"));
+    writeln!(output, "<g>\n<title>{} {}</title>", name, time)?;
+    writeln!(
+        output,
+        "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" rx=\"3\" fill=\"{}\" stroke=\"#888\"/>",
+        x + 1,
+        y + 1,
+        width.saturating_sub(2),
+        height.saturating_sub(2),
+        colour(event.kind()),
+    )?;
+    writeln!(output, "<text x=\"{}\" y=\"{}\" font-weight=\"bold\">{}</text>", x + 4, y + 13, name)?;
+    if height >= 28 {
+        let detail = match event.location() {
+            Some(location) => format!("{} {}", time, location),
+            None => time,
+        };
+        writeln!(output, "<text x=\"{}\" y=\"{}\">{}</text>", x + 4, y + 26, escape_html(&detail))?;
+    }
+    writeln!(output, "</g>")?;
+    Ok(())
+}
+
+fn start_minutes(event: &Event) -> u32 {
+    event.start().hour() * 60 + event.start().minute()
+}
+
+/// The end of an event in minutes from the start of its first day
+fn end_minutes(event: &Event) -> u32 {
+    let minutes = start_minutes(event) as i64 + event.duration().num_minutes();
+    minutes.clamp(0, 24 * 60) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::export::tests::{course, export};
+    use crate::export::Format;
+
+    /// The lecture runs every week, but only the second week has tutorials
+    const TIMETABLE: &str = r#"
+        version = 2
+        code = "COMP1000"
+        name = "Testing"
+        link = "https://example.edu/comp1000"
+
+        [[week]]
+        start = 2024-02-12T00:00:00+11:00
+
+        [[week]]
+        start = 2024-02-19T00:00:00+11:00
+
+        [[session]]
+        first = 2024-02-12T09:00:00+11:00
+        kind = "lecture"
+        duration = 3600
+        weeks = [1, 2]
+
+        [[session]]
+        first = 2024-02-20T10:00:00+11:00
+        kind = "tutorial"
+        stream = "T10A"
+        location = "Quad 1001"
+        duration = 5400
+        weeks = [2]
+
+        [[session]]
+        first = 2024-02-20T10:30:00+11:00
+        kind = "tutorial"
+        stream = "T10B"
+        duration = 3600
+        weeks = [2]
+        "#;
+
+    #[test]
+    fn the_busiest_week_is_drawn() {
+        let image = export(Format::Svg, &course(TIMETABLE));
+        assert!(image.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"848\" height=\"172\""));
+        assert!(image.contains("<title>COMP1000 Testing, week 2</title>"));
+        assert_eq!(image.matches("<g>").count(), 3);
+        assert!(image.contains("<text x=\"44\" y=\"32\" text-anchor=\"end\">09:00</text>"));
+        assert!(image.contains("<text x=\"44\" y=\"176\" text-anchor=\"end\">12:00</text>"));
+    }
+
+    #[test]
+    fn overlapping_sessions_share_the_day() {
+        let image = export(Format::Svg, &course(TIMETABLE));
+        // Tuesday starts at 208, and each tutorial takes half of it
+        assert!(image.contains("<rect x=\"209\" y=\"77\" width=\"78\" height=\"70\""));
+        assert!(image.contains("<rect x=\"289\" y=\"101\" width=\"78\" height=\"46\""));
+        assert!(image.contains("<text x=\"212\" y=\"102\">10:00–11:30 Quad 1001</text>"));
+        // Monday's lecture has the day to itself
+        assert!(image.contains("<rect x=\"49\" y=\"29\" width=\"158\" height=\"46\""));
+    }
+}
@@ -0,0 +1,121 @@
+//! Taskwarrior tasks for assignment submissions, presentations, and quizzes, one JSON object per line
+
+use chrono::{DateTime, FixedOffset, Utc};
+use failure::Error;
+
+use crate::calendar;
+use crate::course::Course;
+use crate::filter::Filter;
+use crate::json::Json;
+
+use super::uuid;
+
+use std::io::Write;
+
+pub fn write(course: &Course, filter: &Filter, output: &mut dyn Write) -> Result<(), Error> {
+    let assignments = calendar::identified(course)
+        .into_iter()
+        .filter(|(_, event)| event.is_assignment() && !matches!(event.kind(), "countdown" | "late") && filter.matches(event))
+        // A quiz is a single task, due when it closes and hidden until it opens
+        .filter(|(_, event)| !event.is_opening());
+
+    for (uid, event) in assignments {
+        let mut tags = vec![tag(event.kind())];
+        tags.extend(event.tags().map(tag));
+
+        let mut task = vec![
+            ("uuid", uuid(&uid).into()),
+            ("description", event.name().into()),
+            ("project", course.code().into()),
+            ("status", "pending".into()),
+            ("entry", time(Utc::now().into())),
+            ("due", time(event.start())),
+            ("tags", tags.into()),
+        ];
+        if let Some(opens) = event.opens() {
+            task.push(("wait", time(opens)));
+        } else if event.kind() != "submission" {
+            task.push(("scheduled", time(event.start())));
+        }
+        if let Some(link) = event.link() {
+            task.push(("annotations", Json::Array(vec![Json::object(vec![
+                ("entry", time(Utc::now().into())),
+                ("description", link.to_string().into()),
+            ])])));
+        }
+        writeln!(output, "{}", Json::object(task))?;
+    }
+    Ok(())
+}
+
+fn time(time: DateTime<FixedOffset>) -> Json {
+    time.with_timezone(&Utc).format("%Y%m%dT%H%M%SZ").to_string().into()
+}
+
+/// A tag without the spaces that Taskwarrior doesn't allow
+fn tag(tag: &str) -> String {
+    tag.replace(char::is_whitespace, "_")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::export::tests::{course, export};
+    use crate::export::Format;
+
+    #[test]
+    fn tasks_are_assessments_due_when_they_close() {
+        let course = course(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/comp1000"
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-12T10:00:00+11:00
+            kind = "lecture"
+            duration = 3600
+            weeks = [1]
+
+            [[assignment]]
+            name = "Assignment"
+            link = "https://example.edu/assignment"
+            tags = ["group work"]
+
+            [[assignment.submission]]
+            time = 2024-02-16T17:00:00+11:00
+            name = "Final"
+            countdown = ["2d"]
+            grace = "1d"
+
+            [[quiz]]
+            name = "Quiz"
+            opens = 2024-02-12T09:00:00+11:00
+            closes = 2024-02-14T17:00:00+11:00
+            weeks = [1]
+            "#,
+        );
+        let tasks = export(Format::Taskwarrior, &course).lines().map(|line| Json::parse(line).unwrap()).collect::<Vec<_>>();
+        let field = |task: &Json, name: &str| task.get(name).and_then(Json::as_str).map(str::to_owned);
+        let descriptions = tasks.iter().map(|task| field(task, "description").unwrap()).collect::<Vec<_>>();
+        assert_eq!(descriptions, ["Quiz closes", "Assignment: Final"]);
+
+        let (quiz, submission) = (&tasks[0], &tasks[1]);
+        assert_eq!(field(quiz, "due").as_deref(), Some("20240214T060000Z"));
+        assert_eq!(field(quiz, "wait").as_deref(), Some("20240211T220000Z"));
+        assert_eq!(field(submission, "due").as_deref(), Some("20240216T060000Z"));
+        assert_eq!(submission.get("scheduled"), None);
+        assert_eq!(submission.get("tags"), Some(&Json::from(vec!["submission", "group_work"])));
+        assert_eq!(field(submission, "project").as_deref(), Some("COMP1000"));
+
+        let uuid = field(submission, "uuid").unwrap();
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert_ne!(Some(uuid), field(quiz, "uuid"));
+    }
+}
@@ -0,0 +1,117 @@
+//! Retrieval of files referenced by a specification
+
+use failure::{bail, format_err, Error};
+use url::Url;
+
+use crate::http;
+use crate::state;
+
+use std::env;
+use std::fs::{create_dir_all, read_to_string, write};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// The maximum number of redirects followed for a single request
+const MAX_REDIRECTS: usize = 5;
+
+/// The longest a download over HTTPS may take, in seconds
+const HTTPS_TIMEOUT: &str = "30";
+
+/// Read a file from a URL, or from a path relative to `base`
+///
+/// The base is either a directory or the URL of the file that refers to the
+/// location. Remote files are cached, and the cached copy is used if they
+/// can't be downloaded.
+pub fn fetch(location: &str, base: &str) -> Result<String, Error> {
+    if let Some(url) = parse_url(location) {
+        return fetch_url(&url);
+    }
+    match parse_url(base) {
+        Some(base) => fetch_url(&base.join(location)?),
+        None => Ok(read_to_string(Path::new(base).join(location))
+            .map_err(|error| format_err!("Could not read {}: {}", location, error))?),
+    }
+}
+
+/// Whether a location is a URL rather than a path
+pub fn is_url(location: &str) -> bool {
+    parse_url(location).is_some()
+}
+
+fn parse_url(location: &str) -> Option<Url> {
+    Url::parse(location)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "file" | "http" | "https"))
+}
+
+fn fetch_url(url: &Url) -> Result<String, Error> {
+    if url.scheme() == "file" {
+        let path = url.to_file_path().map_err(|_| format_err!("Invalid file URL {}", url))?;
+        return Ok(read_to_string(path)?);
+    }
+
+    let cache = cache_path(url);
+    match download(url) {
+        Ok(body) => {
+            if let Some(cache) = &cache {
+                let stored = cache.parent().map(create_dir_all).transpose().and_then(|_| write(cache, &body));
+                if let Err(error) = stored {
+                    eprintln!("warning: could not cache {}: {}", url, error);
+                }
+            }
+            Ok(body)
+        }
+        Err(error) => match cache.and_then(|cache| read_to_string(cache).ok()) {
+            Some(body) => {
+                eprintln!("warning: using cached copy of {}: {}", url, error);
+                Ok(body)
+            }
+            None => Err(error),
+        },
+    }
+}
+
+/// Where a downloaded copy of a URL is kept
+fn cache_path(url: &Url) -> Option<PathBuf> {
+    let directory = env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cache")))?;
+    Some(directory.join("course-calendar").join(format!("{:016x}", state::hash(url.as_str()))))
+}
+
+fn download(url: &Url) -> Result<String, Error> {
+    match url.scheme() {
+        "http" => get(url.clone()),
+        // There is no TLS implementation available, so defer to curl
+        _ => {
+            let output = Command::new("curl")
+                .args(["--fail", "--silent", "--show-error", "--location", "--max-time", HTTPS_TIMEOUT])
+                .arg(url.as_str())
+                .output()
+                .map_err(|error| format_err!("Downloading {} requires curl: {}", url, error))?;
+            if !output.status.success() {
+                bail!("Could not download {}: {}", url, String::from_utf8_lossy(&output.stderr).trim());
+            }
+            Ok(String::from_utf8(output.stdout)?)
+        }
+    }
+}
+
+/// Perform an HTTP GET request, following redirects
+fn get(mut url: Url) -> Result<String, Error> {
+    for _ in 0..=MAX_REDIRECTS {
+        let response = http::request("GET", &url, &[], None)?;
+        match response.status {
+            200 => return Ok(response.body),
+            301 | 302 | 303 | 307 | 308 => {
+                let location = response.header("Location").ok_or(format_err!("Redirect from {} without a location", url))?;
+                url = url.join(location)?;
+                if url.scheme() != "http" {
+                    return download(&url);
+                }
+            }
+            status => bail!("Request for {} failed with status {}", url, status),
+        }
+    }
+    bail!("Too many redirects fetching {}", url)
+}
@@ -0,0 +1,248 @@
+//! Selection of the events to include in a calendar
+
+use chrono::{offset::FixedOffset, DateTime, Duration, NaiveDate};
+use failure::{format_err, Error};
+
+use crate::course::Event;
+
+/// Criteria that events must satisfy to be included
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Only include events of these kinds
+    pub kinds: Vec<String>,
+    /// Only include events with at least one of these tags
+    pub tags: Vec<String>,
+    /// Exclude events with any of these tags
+    pub exclude_tags: Vec<String>,
+    /// Only include events starting at or after this point
+    pub from: Option<Bound>,
+    /// Only include events starting at or before this point
+    pub to: Option<Bound>,
+    /// Only include events in these inclusive ranges of week numbers, as the course numbers them
+    pub weeks: Vec<(usize, usize)>,
+    /// Only include events run by at least one of these presenters
+    pub presenters: Vec<String>,
+    /// Whether assignment events are included
+    pub assignments: Assignments,
+    /// Only include streamed sessions from these streams
+    pub streams: Vec<String>,
+    /// Only include the presentations of these groups
+    pub groups: Vec<String>,
+    /// Only include events held in one of these locations
+    pub locations: Vec<String>,
+}
+
+/// Inclusion of assignment submissions and presentations
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Assignments {
+    #[default]
+    Include,
+    Exclude,
+    Only,
+}
+
+
+impl Filter {
+    /// Determine whether an event should be included
+    pub fn matches(&self, event: &Event) -> bool {
+        match (self.assignments, event.is_assignment()) {
+            (Assignments::Exclude, true) | (Assignments::Only, false) => return false,
+            _ => (),
+        }
+
+        if !self.kinds.is_empty() && !self.kinds.iter().any(|kind| kind == event.kind()) {
+            return false;
+        }
+
+        let tags = event.tags().collect::<Vec<_>>();
+
+        if !self.tags.is_empty() && !self.tags.iter().any(|tag| tags.contains(&tag.as_str())) {
+            return false;
+        }
+
+        if self.exclude_tags.iter().any(|tag| tags.contains(&tag.as_str())) {
+            return false;
+        }
+
+        if let Some(from) = &self.from {
+            if from.is_after(event.start()) {
+                return false;
+            }
+        }
+
+        if let Some(to) = &self.to {
+            if to.is_before(event.start()) {
+                return false;
+            }
+        }
+
+        if let (false, Some(stream)) = (self.streams.is_empty(), event.stream()) {
+            if !self.streams.iter().any(|selected| selected == stream) {
+                return false;
+            }
+        }
+
+        let rooms = [event.room(), event.location()];
+        if !self.locations.is_empty() && !self.locations.iter().any(|selected| rooms.contains(&Some(selected.as_str()))) {
+            return false;
+        }
+
+        let groups = event.groups().collect::<Vec<_>>();
+        if !self.groups.is_empty() && !groups.is_empty() && !self.groups.iter().any(|group| groups.contains(&group.as_str())) {
+            return false;
+        }
+
+        if !self.presenters.is_empty() {
+            let presented = event.presenters().any(|presenter| {
+                self.presenters.iter().any(|name| name.eq_ignore_ascii_case(presenter))
+            });
+            if !presented {
+                return false;
+            }
+        }
+
+        if !self.weeks.is_empty() && !event.week_number().map(|week| self.includes_week(week)).unwrap_or(false) {
+            return false;
+        }
+
+        true
+    }
+
+    /// Determine whether something generated for a whole week, of a particular kind, should be included
+    ///
+    /// The week is given by its number, as the course numbers it.
+    pub fn matches_week(&self, week: usize, start: DateTime<FixedOffset>, kind: &str) -> bool {
+        if self.assignments == Assignments::Only || !self.tags.is_empty() || !self.presenters.is_empty() || !self.locations.is_empty() {
+            return false;
+        }
+        if !self.kinds.is_empty() && !self.kinds.iter().any(|selected| selected == kind) {
+            return false;
+        }
+        if self.from.map(|from| from.is_after(start + Duration::days(7))).unwrap_or(false) {
+            return false;
+        }
+        if self.to.map(|to| to.is_before(start)).unwrap_or(false) {
+            return false;
+        }
+        self.includes_week(week)
+    }
+
+    /// Whether a week number is in one of the selected ranges, or no weeks were selected
+    pub fn includes_week(&self, week: usize) -> bool {
+        self.weeks.is_empty() || self.weeks.iter().any(|(first, last)| *first <= week && week <= *last)
+    }
+}
+
+/// One end of a range of time, either a whole day or a precise time
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    Date(NaiveDate),
+    Time(DateTime<FixedOffset>),
+}
+
+impl Bound {
+    /// Parse either an RFC 3339 datetime or a YYYY-MM-DD date
+    pub fn parse(value: &str) -> Result<Self, Error> {
+        if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+            return Ok(Bound::Time(time));
+        }
+
+        NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map(Bound::Date)
+            .map_err(|_| format_err!("Expected a date (YYYY-MM-DD) or datetime but found {}", value))
+    }
+
+    /// Whether a time falls before the bound, with dates in the timezone of the time
+    pub fn is_after(&self, time: DateTime<FixedOffset>) -> bool {
+        match self {
+            Bound::Date(date) => time < local_midnight(*date, time),
+            Bound::Time(bound) => time < *bound,
+        }
+    }
+
+    /// Whether a time falls after the bound, with dates in the timezone of the time
+    pub fn is_before(&self, time: DateTime<FixedOffset>) -> bool {
+        match self {
+            Bound::Date(date) => time >= local_midnight(*date, time) + Duration::days(1),
+            Bound::Time(bound) => time > *bound,
+        }
+    }
+}
+
+fn local_midnight(date: NaiveDate, reference: DateTime<FixedOffset>) -> DateTime<FixedOffset> {
+    let midnight = date.and_hms(0, 0, 0) - *reference.offset();
+    DateTime::from_utc(midnight, *reference.offset())
+}
+
+/// Parse a list of week numbers such as `3-7` or `1,4,6-8` into ranges
+///
+/// The numbers are kept as written, to be matched against the numbering of each course.
+pub fn parse_weeks(value: &str) -> Result<Vec<(usize, usize)>, Error> {
+    value
+        .split(',')
+        .map(|range| {
+            let mut bounds = range.splitn(2, '-').map(|week| {
+                week.trim().parse::<usize>().map_err(|_| format_err!("Invalid week range {}", range))
+            });
+            let first = bounds.next().ok_or(format_err!("Invalid week range {}", range))??;
+            let last = bounds.next().transpose()?.unwrap_or(first);
+            if last < first {
+                return Err(format_err!("Week range {} ends before it starts", range));
+            }
+            Ok((first, last))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::course::Course;
+
+    /// A course with a single lecture in its first week
+    fn course(numbering: &str, first: usize) -> Course {
+        let spec = format!(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/"
+            week_numbering = "{}"
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[week]]
+            start = 2024-02-19T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-12T10:00:00+11:00
+            kind = "lecture"
+            duration = 3600
+            weeks = [{}]
+            "#,
+            numbering, first
+        );
+        let mut course = Course::from_value(toml::from_str(&spec).unwrap()).unwrap();
+        course.generate_repeats().unwrap();
+        course
+    }
+
+    #[test]
+    fn weeks_are_parsed_as_written() {
+        assert_eq!(parse_weeks("0,3-5").unwrap(), vec![(0, 0), (3, 5)]);
+        assert!(parse_weeks("5-3").is_err());
+        assert!(parse_weeks("three").is_err());
+    }
+
+    #[test]
+    fn weeks_follow_the_numbering_of_the_course() {
+        let first = Filter { weeks: parse_weeks("1").unwrap(), ..Filter::default() };
+        let one_based = course("one-based", 1);
+        let zero_based = course("zero-based", 0);
+        assert_eq!(one_based.events().filter(|event| first.matches(event)).count(), 1);
+        assert_eq!(zero_based.events().filter(|event| first.matches(event)).count(), 0);
+        let zero = Filter { weeks: parse_weeks("0").unwrap(), ..Filter::default() };
+        assert_eq!(zero_based.events().filter(|event| zero.matches(event)).count(), 1);
+    }
+}
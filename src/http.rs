@@ -0,0 +1,268 @@
+//! A minimal HTTP/1.0 client over plain TCP, deferring to curl for HTTPS
+
+use failure::{bail, format_err, Error};
+use url::Url;
+
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// How long to wait to connect, or for the server to respond
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A response to a request
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: String,
+}
+
+impl Response {
+    /// The value of the first header with a particular name
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Whether the status indicates success
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Send a request and read the whole response
+///
+/// Plain `http:` URLs are requested directly. There is no TLS implementation
+/// available, so `https:` requests defer to curl, as downloads do.
+pub fn request(method: &str, url: &Url, headers: &[(&str, String)], body: Option<&str>) -> Result<Response, Error> {
+    match url.scheme() {
+        "http" => request_plain(method, url, headers, body),
+        "https" => request_curl(method, url, headers, body),
+        _ => bail!("Unsupported URL {}, expected HTTP or HTTPS", url),
+    }
+}
+
+fn request_plain(method: &str, url: &Url, headers: &[(&str, String)], body: Option<&str>) -> Result<Response, Error> {
+    let host = url.host_str().ok_or(format_err!("No host in {}", url))?;
+    let port = url.port_or_known_default().unwrap_or(80);
+    let mut target = url.path().to_owned();
+    if let Some(query) = url.query() {
+        target.push('?');
+        target.push_str(query);
+    }
+
+    let mut request = format!("{} {} HTTP/1.0\r\nHost: {}\r\nConnection: close\r\n", method, target, host);
+    for (name, value) in headers {
+        request.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    if let Some(body) = body {
+        request.push_str(&format!("Content-Length: {}\r\n", body.len()));
+    }
+    request.push_str("\r\n");
+    request.push_str(body.unwrap_or_default());
+
+    let address = (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or(format_err!("Could not resolve {}", host))?;
+    let mut stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    stream.write_all(request.as_bytes())?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    parse_response(&String::from_utf8(response)?, url)
+}
+
+/// Make a request with curl, passing everything through its standard input
+///
+/// The headers and body often hold tokens, so they are written as a curl
+/// configuration rather than given as arguments visible to other users.
+fn request_curl(method: &str, url: &Url, headers: &[(&str, String)], body: Option<&str>) -> Result<Response, Error> {
+    let mut config = format!("url = {}\nrequest = {}\n", quote(url.as_str()), quote(method));
+    for (name, value) in headers {
+        config.push_str(&format!("header = {}\n", quote(&format!("{}: {}", name, value))));
+    }
+    if let Some(body) = body {
+        config.push_str(&format!("data-raw = {}\n", quote(body)));
+    }
+
+    let mut child = Command::new("curl")
+        .args(["--silent", "--show-error", "--include", "--max-time", &TIMEOUT.as_secs().to_string(), "--config", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format_err!("Requesting {} requires curl: {}", url, error))?;
+    child.stdin.take().ok_or(format_err!("Could not write to curl"))?.write_all(config.as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("Request for {} failed: {}", url, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    parse_response(&String::from_utf8(output.stdout)?, url)
+}
+
+/// Quote a value for a curl configuration file
+fn quote(value: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Split a response into its status, headers, and body
+///
+/// Interim responses, such as `100 Continue`, are skipped.
+fn parse_response(mut response: &str, url: &Url) -> Result<Response, Error> {
+    loop {
+        let split = response.find("\r\n\r\n").ok_or(format_err!("Malformed response from {}", url))?;
+        let mut lines = response[..split].lines();
+        let status = lines
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|status| status.parse::<u16>().ok())
+            .ok_or(format_err!("Malformed response from {}", url))?;
+        // A proxy tunnelling the connection answers before the server does
+        let tunnelled = response[..split].lines().next().is_some_and(|line| line.ends_with("Connection established"));
+        if (100..200).contains(&status) || tunnelled {
+            response = &response[split + 4..];
+            continue;
+        }
+        let headers = lines
+            .filter_map(|line| line.split_once(':'))
+            .map(|(name, value)| (name.trim().to_owned(), value.trim().to_owned()))
+            .collect();
+        return Ok(Response { status, headers, body: response[split + 4..].to_owned() });
+    }
+}
+
+/// The value of an `Authorization` header for basic authentication
+pub fn basic_auth(username: &str, password: &str) -> String {
+    format!("Basic {}", base64(format!("{}:{}", username, password).as_bytes()))
+}
+
+/// Encode bytes in standard base64 with padding
+pub fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let value = chunk.iter().enumerate().fold(0u32, |value, (index, byte)| value | u32::from(*byte) << (16 - 8 * index));
+        for index in 0..4 {
+            if index <= chunk.len() {
+                encoded.push(ALPHABET[(value >> (18 - 6 * index) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread;
+
+    fn url() -> Url {
+        Url::parse("https://example.edu/calendar").unwrap()
+    }
+
+    #[test]
+    fn configuration_values_are_quoted() {
+        assert_eq!(quote("Bearer abc"), "\"Bearer abc\"");
+        assert_eq!(quote("say \"hi\"\\\n\tnow\r"), "\"say \\\"hi\\\"\\\\\\n\\tnow\\r\"");
+    }
+
+    #[test]
+    fn responses_are_split_into_status_headers_and_body() {
+        let response = parse_response("HTTP/1.1 201 Created\r\nETag: \"1\"\r\nContent-Type: text/plain\r\n\r\nline one\r\n\r\nline two", &url()).unwrap();
+        assert_eq!(response.status, 201);
+        assert!(response.is_success());
+        assert_eq!(response.header("etag"), Some("\"1\""));
+        assert_eq!(response.header("Location"), None);
+        assert_eq!(response.body, "line one\r\n\r\nline two");
+    }
+
+    #[test]
+    fn interim_responses_are_skipped() {
+        let response = parse_response(
+            "HTTP/1.1 200 Connection established\r\n\r\nHTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 404 Not Found\r\n\r\n",
+            &url(),
+        )
+        .unwrap();
+        assert_eq!(response.status, 404);
+        assert!(!response.is_success());
+        assert_eq!(response.body, "");
+    }
+
+    #[test]
+    fn malformed_responses_are_rejected() {
+        for response in &["", "HTTP/1.1 200 OK\r\n", "garbage\r\n\r\n", "HTTP/1.1 100 Continue\r\n\r\n"] {
+            let error = parse_response(response, &url()).unwrap_err();
+            assert_eq!(error.to_string(), "Malformed response from https://example.edu/calendar");
+        }
+    }
+
+    #[test]
+    fn credentials_are_encoded_in_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(basic_auth("Aladdin", "open sesame"), "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ==");
+    }
+
+    #[test]
+    fn plain_requests_are_sent_directly() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/events/1.ics?sync=1", listener.local_addr().unwrap())).unwrap();
+        let server = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut lines = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                lines.push(line.trim_end().to_owned());
+            }
+            let mut body = vec![0; 5];
+            reader.read_exact(&mut body).unwrap();
+            stream.write_all(b"HTTP/1.0 204 No Content\r\nServer: test\r\n\r\n").unwrap();
+            (lines, String::from_utf8(body).unwrap())
+        });
+
+        let response = request("PUT", &url, &[("Content-Type", "text/calendar".to_owned())], Some("BEGIN")).unwrap();
+        let (lines, body) = server.join().unwrap();
+        assert_eq!(response.status, 204);
+        assert_eq!(response.header("server"), Some("test"));
+        assert_eq!(lines[0], "PUT /events/1.ics?sync=1 HTTP/1.0");
+        assert!(lines.contains(&"Content-Type: text/calendar".to_owned()));
+        assert!(lines.contains(&"Content-Length: 5".to_owned()));
+        assert_eq!(body, "BEGIN");
+    }
+
+    #[test]
+    fn other_schemes_are_unsupported() {
+        let error = request("GET", &Url::parse("ftp://example.edu/").unwrap(), &[], None).unwrap_err();
+        assert_eq!(error.to_string(), "Unsupported URL ftp://example.edu/, expected HTTP or HTTPS");
+    }
+}
@@ -0,0 +1,472 @@
+//! Parsing of existing iCalendar files
+
+use chrono::{offset::FixedOffset, DateTime, Duration, NaiveDate, NaiveDateTime, TimeZone};
+use failure::{bail, format_err, Error};
+
+use std::collections::BTreeMap;
+
+/// A component such as a VCALENDAR or VEVENT
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Component {
+    pub name: String,
+    pub properties: Vec<ContentLine>,
+    pub components: Vec<Component>,
+}
+
+/// A single property with its parameters
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ContentLine {
+    pub name: String,
+    pub parameters: Vec<(String, String)>,
+    pub value: String,
+}
+
+impl Component {
+    /// The first property with a particular name
+    pub fn property(&self, name: &str) -> Option<&ContentLine> {
+        self.properties.iter().find(|property| property.name.eq_ignore_ascii_case(name))
+    }
+
+    /// The unescaped text of the first property with a particular name
+    pub fn text(&self, name: &str) -> Option<String> {
+        self.property(name).map(|property| unescape_text(&property.value))
+    }
+
+    /// All nested components with a particular name
+    pub fn components<'c>(&'c self, name: &'c str) -> impl Iterator<Item = &'c Component> {
+        self.components.iter().filter(move |component| component.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl ContentLine {
+    pub fn parameter(&self, name: &str) -> Option<&str> {
+        self.parameters
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Parse the top-level components of an iCalendar file
+pub fn parse(source: &str) -> Result<Vec<Component>, Error> {
+    let mut stack: Vec<Component> = Vec::new();
+    let mut components = Vec::new();
+
+    for line in unfold(source) {
+        let line = parse_line(&line)?;
+        if line.name.eq_ignore_ascii_case("BEGIN") {
+            stack.push(Component {
+                name: line.value.to_uppercase(),
+                properties: Vec::new(),
+                components: Vec::new(),
+            });
+        } else if line.name.eq_ignore_ascii_case("END") {
+            let component = stack.pop().ok_or(format_err!("Unexpected END:{}", line.value))?;
+            if !component.name.eq_ignore_ascii_case(&line.value) {
+                bail!("Expected END:{} but found END:{}", component.name, line.value);
+            }
+            match stack.last_mut() {
+                Some(parent) => parent.components.push(component),
+                None => components.push(component),
+            }
+        } else {
+            match stack.last_mut() {
+                Some(component) => component.properties.push(line),
+                None => bail!("Property {} outside of any component", line.name),
+            }
+        }
+    }
+
+    if let Some(component) = stack.last() {
+        bail!("Unterminated {} component", component.name);
+    }
+    Ok(components)
+}
+
+/// Join folded lines back together
+fn unfold(source: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in source.split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        if let (Some(rest), Some(last)) = (line.strip_prefix(|c| c == ' ' || c == '\t'), lines.last_mut()) {
+            last.push_str(rest);
+        } else if !line.is_empty() {
+            lines.push(line.to_owned());
+        }
+    }
+    lines
+}
+
+fn parse_line(line: &str) -> Result<ContentLine, Error> {
+    // The value starts at the first colon that isn't within a quoted parameter
+    let mut quoted = false;
+    let split = line
+        .char_indices()
+        .find(|(_, c)| {
+            if *c == '"' {
+                quoted = !quoted;
+            }
+            *c == ':' && !quoted
+        })
+        .map(|(index, _)| index)
+        .ok_or(format_err!("Invalid content line: {}", line))?;
+
+    let mut parts = line[..split].split(';');
+    let name = parts.next().unwrap_or_default().to_uppercase();
+    let parameters = parts
+        .filter_map(|parameter| {
+            let mut pair = parameter.splitn(2, '=');
+            let key = pair.next()?.to_uppercase();
+            let value = pair.next().unwrap_or_default().trim_matches('"').to_owned();
+            Some((key, value))
+        })
+        .collect();
+
+    Ok(ContentLine {
+        name,
+        parameters,
+        value: line[split + 1..].to_owned(),
+    })
+}
+
+/// Reverse the escaping of an iCalendar TEXT value
+pub fn unescape_text(value: &str) -> String {
+    let mut text = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') | Some('N') => text.push('\n'),
+                Some(c) => text.push(c),
+                None => text.push('\\'),
+            }
+        } else {
+            text.push(c);
+        }
+    }
+    text
+}
+
+/// Fixed offsets for each time zone defined in a calendar
+///
+/// Only the standard offset of each zone is used, so times during daylight
+/// saving may be an hour off.
+pub fn timezones(calendar: &Component) -> BTreeMap<String, FixedOffset> {
+    calendar
+        .components("VTIMEZONE")
+        .filter_map(|zone| {
+            let id = zone.text("TZID")?;
+            let definition = zone.components("STANDARD").next().or_else(|| zone.components.first())?;
+            let offset = parse_offset(&definition.property("TZOFFSETTO")?.value)?;
+            Some((id, offset))
+        })
+        .collect()
+}
+
+fn parse_offset(value: &str) -> Option<FixedOffset> {
+    let sign = match value.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _ => return None,
+    };
+    let hours = value.get(1..3)?.parse::<i32>().ok()?;
+    let minutes = value.get(3..5)?.parse::<i32>().ok()?;
+    FixedOffset::east_opt(sign * (hours * 3600 + minutes * 60))
+}
+
+/// Parse a DATE or DATE-TIME property value
+///
+/// Floating times and unknown time zones are treated as UTC.
+pub fn parse_datetime(
+    property: &ContentLine,
+    timezones: &BTreeMap<String, FixedOffset>,
+) -> Result<DateTime<FixedOffset>, Error> {
+    let value = property.value.trim();
+    let utc = FixedOffset::east(0);
+
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y%m%d") {
+        return Ok(utc.from_utc_datetime(&date.and_hms(0, 0, 0)));
+    }
+
+    if let Some(value) = value.strip_suffix('Z') {
+        let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")?;
+        return Ok(utc.from_utc_datetime(&time));
+    }
+
+    let time = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")?;
+    let offset = property
+        .parameter("TZID")
+        .and_then(|zone| timezones.get(zone))
+        .cloned()
+        .unwrap_or(utc);
+    offset
+        .from_local_datetime(&time)
+        .single()
+        .ok_or(format_err!("Invalid local time {}", value))
+}
+
+/// Parse a DURATION value such as `PT1H30M`
+pub fn parse_duration(value: &str) -> Result<Duration, Error> {
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(value) => (-1, value),
+        None => (1, value.strip_prefix('+').unwrap_or(value)),
+    };
+    let value = value.strip_prefix('P').ok_or(format_err!("Invalid duration {}", value))?;
+
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in value.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => continue,
+            unit => {
+                let amount = number.parse::<i64>().map_err(|_| format_err!("Invalid duration {}", value))?;
+                number.clear();
+                seconds += amount * match unit {
+                    'W' => 7 * 24 * 3600,
+                    'D' => 24 * 3600,
+                    'H' => 3600,
+                    'M' => 60,
+                    'S' => 1,
+                    _ => bail!("Invalid duration {}", value),
+                };
+            }
+        }
+    }
+    Ok(Duration::seconds(sign * seconds))
+}
+
+/// A single occurrence of an event read from a calendar
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Occurrence {
+    pub uid: Option<String>,
+    pub summary: String,
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+    pub location: Option<String>,
+    pub description: Option<String>,
+    pub url: Option<String>,
+    pub contacts: Vec<String>,
+}
+
+/// The occurrences of all events in a calendar with simple recurrences expanded
+///
+/// Daily and weekly recurrence rules are expanded using their `INTERVAL`,
+/// `COUNT`, and `UNTIL` parts, along with any `EXDATE`s.
+pub fn occurrences(calendar: &Component) -> Result<Vec<Occurrence>, Error> {
+    let timezones = timezones(calendar);
+    let mut occurrences = Vec::new();
+
+    for event in calendar.components("VEVENT") {
+        let start = parse_datetime(event.property("DTSTART").ok_or(format_err!("Event without DTSTART"))?, &timezones)?;
+        let end = match (event.property("DTEND"), event.property("DURATION")) {
+            (Some(end), _) => parse_datetime(end, &timezones)?,
+            (None, Some(duration)) => start + parse_duration(&duration.value)?,
+            (None, None) => start,
+        };
+
+        let excluded = event
+            .properties
+            .iter()
+            .filter(|property| property.name == "EXDATE")
+            .flat_map(|property| {
+                property.value.split(',').map(move |value| ContentLine {
+                    value: value.to_owned(),
+                    ..property.clone()
+                })
+            })
+            .map(|property| parse_datetime(&property, &timezones))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let starts = match event.property("RRULE") {
+            Some(rule) => recurrences(&rule.value, start, &timezones)?,
+            None => vec![start],
+        };
+
+        for occurrence in starts.into_iter().filter(|start| !excluded.contains(start)) {
+            occurrences.push(Occurrence {
+                uid: event.text("UID"),
+                summary: event.text("SUMMARY").unwrap_or_default(),
+                start: occurrence,
+                end: occurrence + (end - start),
+                location: event.text("LOCATION"),
+                description: event.text("DESCRIPTION"),
+                url: event.property("URL").map(|url| url.value.clone()),
+                contacts: event
+                    .properties
+                    .iter()
+                    .filter(|property| property.name == "CONTACT")
+                    .map(|property| unescape_text(&property.value))
+                    .collect(),
+            });
+        }
+    }
+
+    occurrences.sort_by_key(|occurrence| occurrence.start);
+    Ok(occurrences)
+}
+
+/// The maximum number of occurrences generated for an unbounded rule
+const MAX_RECURRENCES: usize = 500;
+
+fn recurrences(
+    rule: &str,
+    start: DateTime<FixedOffset>,
+    timezones: &BTreeMap<String, FixedOffset>,
+) -> Result<Vec<DateTime<FixedOffset>>, Error> {
+    let parts = rule
+        .split(';')
+        .filter_map(|part| {
+            let mut pair = part.splitn(2, '=');
+            Some((pair.next()?.to_uppercase(), pair.next()?.to_owned()))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let step = match parts.get("FREQ").map(String::as_str) {
+        Some("DAILY") => Duration::days(1),
+        Some("WEEKLY") => Duration::weeks(1),
+        Some(frequency) => bail!("Unsupported recurrence frequency {}", frequency),
+        None => bail!("Recurrence rule without a frequency: {}", rule),
+    };
+    let interval = parts.get("INTERVAL").map(|interval| interval.parse::<i32>()).transpose()?.unwrap_or(1);
+    let count = parts.get("COUNT").map(|count| count.parse::<usize>()).transpose()?.unwrap_or(MAX_RECURRENCES);
+    let until = parts
+        .get("UNTIL")
+        .map(|until| {
+            let until = ContentLine { name: "UNTIL".to_owned(), parameters: Vec::new(), value: until.clone() };
+            parse_datetime(&until, timezones)
+        })
+        .transpose()?;
+
+    let mut starts = Vec::new();
+    let mut time = start;
+    while starts.len() < count.min(MAX_RECURRENCES) && until.map(|until| time <= until).unwrap_or(true) {
+        starts.push(time);
+        time = time + step * interval;
+    }
+    Ok(starts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A weekly lecture, skipping a week, in a zone with daylight saving
+    const TIMETABLE: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VTIMEZONE\r
+TZID:Australia/Sydney\r
+BEGIN:DAYLIGHT\r
+TZOFFSETTO:+1100\r
+END:DAYLIGHT\r
+BEGIN:STANDARD\r
+TZOFFSETTO:+1000\r
+END:STANDARD\r
+END:VTIMEZONE\r
+BEGIN:VEVENT\r
+UID:lecture@example.edu\r
+SUMMARY:COMP1000 Introduction\\, part one (lecture)\r
+DTSTART;TZID=Australia/Sydney:20240212T100000\r
+DURATION:PT1H30M\r
+RRULE:FREQ=WEEKLY;COUNT=3\r
+EXDATE;TZID=Australia/Sydney:20240219T100000\r
+LOCATION:Quad 1001\r
+DESCRIPTION:Bring a\\nlaptop\r
+CONTACT:Ada <ada@example.edu>\r
+CONTACT:Grace\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+SUMMARY:Exam\r
+DTSTART:20240301T230000Z\r
+DTEND:20240302T010000Z\r
+URL:https://example.edu/exam\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn calendar() -> Component {
+        parse(TIMETABLE).unwrap().remove(0)
+    }
+
+    #[test]
+    fn folded_lines_are_joined() {
+        assert_eq!(unfold("SUMMARY:Long\r\n  title,\r\n\t continued\r\n\r\nEND:VEVENT"), ["SUMMARY:Long title, continued".to_owned(), "END:VEVENT".to_owned()]);
+    }
+
+    #[test]
+    fn colons_in_quoted_parameters_do_not_end_the_name() {
+        let line = parse_line("ATTENDEE;cn=\"Ada: tutor\";ROLE=CHAIR:mailto:ada@example.edu").unwrap();
+        assert_eq!(line.name, "ATTENDEE");
+        assert_eq!(line.parameter("CN"), Some("Ada: tutor"));
+        assert_eq!(line.parameter("role"), Some("CHAIR"));
+        assert_eq!(line.value, "mailto:ada@example.edu");
+        assert_eq!(parse_line("no value").unwrap_err().to_string(), "Invalid content line: no value");
+    }
+
+    #[test]
+    fn text_is_unescaped() {
+        assert_eq!(unescape_text(r"a\, b\; c\nd\\e\"), "a, b; c\nd\\e\\");
+    }
+
+    #[test]
+    fn components_must_be_balanced() {
+        assert_eq!(parse("BEGIN:VCALENDAR\nEND:VEVENT").unwrap_err().to_string(), "Expected END:VCALENDAR but found END:VEVENT");
+        assert_eq!(parse("BEGIN:VCALENDAR").unwrap_err().to_string(), "Unterminated VCALENDAR component");
+        assert_eq!(parse("SUMMARY:Loose").unwrap_err().to_string(), "Property SUMMARY outside of any component");
+        let calendar = calendar();
+        assert_eq!(calendar.components("vevent").count(), 2);
+        assert_eq!(calendar.components("VTIMEZONE").next().unwrap().components.len(), 2);
+    }
+
+    #[test]
+    fn times_use_the_standard_offset_of_their_zone() {
+        let zones = timezones(&calendar());
+        assert_eq!(zones.get("Australia/Sydney"), Some(&FixedOffset::east(10 * 3600)));
+
+        let line = |value: &str| parse_line(value).unwrap();
+        let time = |value: &str| parse_datetime(&line(value), &zones).unwrap().to_rfc3339();
+        assert_eq!(time("DTSTART;TZID=Australia/Sydney:20240212T100000"), "2024-02-12T10:00:00+10:00");
+        assert_eq!(time("DTSTART;TZID=Europe/Paris:20240212T100000"), "2024-02-12T10:00:00+00:00");
+        assert_eq!(time("DTSTART:20240212T100000Z"), "2024-02-12T10:00:00+00:00");
+        assert_eq!(time("DTSTART;VALUE=DATE:20240212"), "2024-02-12T00:00:00+00:00");
+        assert_eq!(parse_offset("-0930"), FixedOffset::east_opt(-(9 * 3600 + 30 * 60)));
+        assert_eq!(parse_offset("1000"), None);
+    }
+
+    #[test]
+    fn durations_are_parsed() {
+        assert_eq!(parse_duration("PT1H30M").unwrap(), Duration::minutes(90));
+        assert_eq!(parse_duration("-P1W2D").unwrap(), Duration::days(-9));
+        assert_eq!(parse_duration("+PT15S").unwrap(), Duration::seconds(15));
+        assert_eq!(parse_duration("1H").unwrap_err().to_string(), "Invalid duration 1H");
+        assert_eq!(parse_duration("P1Y").unwrap_err().to_string(), "Invalid duration 1Y");
+    }
+
+    #[test]
+    fn recurrences_are_expanded_without_excluded_dates() {
+        let occurrences = occurrences(&calendar()).unwrap();
+        let starts = occurrences.iter().map(|occurrence| occurrence.start.to_rfc3339()).collect::<Vec<_>>();
+        assert_eq!(starts, ["2024-02-12T10:00:00+10:00", "2024-02-26T10:00:00+10:00", "2024-03-01T23:00:00+00:00"]);
+
+        let lecture = &occurrences[1];
+        assert_eq!(lecture.uid.as_deref(), Some("lecture@example.edu"));
+        assert_eq!(lecture.summary, "COMP1000 Introduction, part one (lecture)");
+        assert_eq!(lecture.end - lecture.start, Duration::minutes(90));
+        assert_eq!(lecture.description.as_deref(), Some("Bring a\nlaptop"));
+        assert_eq!(lecture.contacts, ["Ada <ada@example.edu>", "Grace"]);
+
+        let exam = &occurrences[2];
+        assert_eq!((exam.end - exam.start, exam.url.as_deref()), (Duration::hours(2), Some("https://example.edu/exam")));
+    }
+
+    #[test]
+    fn rules_are_bounded() {
+        let start = FixedOffset::east(0).ymd(2024, 2, 12).and_hms(10, 0, 0);
+        let zones = BTreeMap::new();
+        let days = recurrences("FREQ=DAILY;INTERVAL=2;UNTIL=20240216T100000Z", start, &zones).unwrap();
+        assert_eq!(days.iter().map(|day| day.format("%d").to_string()).collect::<Vec<_>>(), ["12", "14", "16"]);
+        assert_eq!(recurrences("FREQ=WEEKLY", start, &zones).unwrap().len(), MAX_RECURRENCES);
+        assert_eq!(recurrences("FREQ=MONTHLY", start, &zones).unwrap_err().to_string(), "Unsupported recurrence frequency MONTHLY");
+        assert_eq!(recurrences("COUNT=2", start, &zones).unwrap_err().to_string(), "Recurrence rule without a frequency: COUNT=2");
+    }
+}
@@ -0,0 +1,238 @@
+//! Conversion of existing calendars into course specifications
+
+use chrono::{offset::FixedOffset, DateTime, Datelike, Duration, Timelike};
+use failure::{format_err, Error};
+use toml::value::{Table, Value};
+
+use crate::course::Course;
+use crate::ical::{self, Occurrence};
+use crate::migrate::CURRENT_VERSION;
+
+use std::collections::BTreeMap;
+
+/// Placeholder for the course link when the calendar doesn't provide one
+const PLACEHOLDER_LINK: &str = "https://example.invalid/";
+
+/// Details of the course that can't be inferred from the calendar
+#[derive(Debug, Clone, Default)]
+pub struct Details {
+    pub code: Option<String>,
+    pub name: Option<String>,
+    pub link: Option<String>,
+}
+
+/// Build a best-effort course specification from an iCalendar file
+///
+/// Events that occur at the same time on the same weekday with the same
+/// summary and location are combined into repeated sessions.
+pub fn from_ics(source: &str, details: &Details) -> Result<Course, Error> {
+    let calendar = ical::parse(source)?
+        .into_iter()
+        .find(|component| component.name == "VCALENDAR")
+        .ok_or(format_err!("No VCALENDAR found"))?;
+
+    let offset = ical::timezones(&calendar).values().next().cloned().unwrap_or(FixedOffset::east(0));
+    let occurrences = ical::occurrences(&calendar)?
+        .into_iter()
+        .map(|occurrence| Occurrence {
+            start: occurrence.start.with_timezone(&offset),
+            end: occurrence.end.with_timezone(&offset),
+            ..occurrence
+        })
+        .collect::<Vec<_>>();
+
+    let calendar_name = calendar.text("X-WR-CALNAME").or_else(|| calendar.text("NAME"));
+    let code = details.code.clone()
+        .or_else(|| calendar_name.as_ref().and_then(|name| name.split_whitespace().next().map(str::to_owned)))
+        .unwrap_or_else(|| "COURSE".to_owned());
+    let name = details.name.clone().or(calendar_name).unwrap_or_else(|| "Imported course".to_owned());
+    let link = details.link.clone().unwrap_or_else(|| PLACEHOLDER_LINK.to_owned());
+
+    let mut spec = Table::new();
+    spec.insert("version".to_owned(), Value::Integer(CURRENT_VERSION));
+    spec.insert("code".to_owned(), Value::String(code.clone()));
+    spec.insert("name".to_owned(), Value::String(name));
+    spec.insert("link".to_owned(), Value::String(link));
+
+    let first = match occurrences.first() {
+        Some(first) => first.start,
+        None => return Course::from_value(Value::Table(spec)),
+    };
+    let term_start = first.date().and_hms(0, 0, 0) - Duration::days(first.weekday().num_days_from_monday().into());
+    let week_of = |time: DateTime<FixedOffset>| ((time - term_start).num_days() / 7) as usize;
+    let week_count = occurrences.iter().map(|occurrence| week_of(occurrence.start)).max().unwrap_or(0) + 1;
+
+    // Group occurrences that form a weekly series
+    let mut series: BTreeMap<_, Vec<&Occurrence>> = BTreeMap::new();
+    for occurrence in &occurrences {
+        let key = (
+            occurrence.summary.clone(),
+            occurrence.location.clone(),
+            occurrence.start.weekday().num_days_from_monday(),
+            occurrence.start.num_seconds_from_midnight(),
+            (occurrence.end - occurrence.start).num_seconds(),
+        );
+        series.entry(key).or_default().push(occurrence);
+    }
+
+    let mut weeks = (0..week_count)
+        .map(|week| {
+            let mut table = Table::new();
+            table.insert("start".to_owned(), datetime(term_start + Duration::weeks(week as i64))?);
+            Ok(table)
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    let mut repeats = Vec::new();
+
+    for occurrences in series.values() {
+        let mut weeks_of_series = occurrences.iter().map(|occurrence| week_of(occurrence.start)).collect::<Vec<_>>();
+        weeks_of_series.dedup();
+
+        if weeks_of_series.len() > 1 && weeks_of_series.len() == occurrences.len() {
+            let mut session = session(&code, occurrences[0], "first")?;
+            let numbers = weeks_of_series.iter().map(|week| Value::Integer(*week as i64 + 1)).collect();
+            session.insert("weeks".to_owned(), Value::Array(numbers));
+            repeats.push(Value::Table(session));
+        } else {
+            for occurrence in occurrences {
+                let week = &mut weeks[week_of(occurrence.start)];
+                let sessions = week
+                    .entry("session".to_owned())
+                    .or_insert_with(|| Value::Array(Vec::new()));
+                if let Value::Array(sessions) = sessions {
+                    sessions.push(Value::Table(session(&code, occurrence, "time")?));
+                }
+            }
+        }
+    }
+
+    spec.insert("week".to_owned(), Value::Array(weeks.into_iter().map(Value::Table).collect()));
+    if !repeats.is_empty() {
+        spec.insert("session".to_owned(), Value::Array(repeats));
+    }
+
+    Course::from_value(Value::Table(spec))
+}
+
+/// Describe an occurrence as a session, with its start time under `time_key`
+fn session(code: &str, occurrence: &Occurrence, time_key: &str) -> Result<Table, Error> {
+    let (title, kind) = title_and_kind(code, &occurrence.summary);
+
+    let mut session = Table::new();
+    session.insert(time_key.to_owned(), datetime(occurrence.start)?);
+    if let Some(title) = title {
+        session.insert("title".to_owned(), Value::String(title));
+    }
+    if let Some(description) = &occurrence.description {
+        session.insert("description".to_owned(), Value::String(description.clone()));
+    }
+    if let Some(url) = &occurrence.url {
+        session.insert("link".to_owned(), Value::String(url.clone()));
+    }
+    if let Some(location) = &occurrence.location {
+        session.insert("location".to_owned(), Value::String(location.clone()));
+    }
+    session.insert("kind".to_owned(), Value::String(kind));
+    session.insert("duration".to_owned(), Value::Integer((occurrence.end - occurrence.start).num_seconds()));
+    Ok(session)
+}
+
+/// Kinds of session recognised within event summaries
+const KINDS: &[&str] = &["lecture", "tutorial", "lab", "seminar", "workshop", "exam", "consultation"];
+
+/// Infer the title and kind of a session from a summary
+///
+/// Summaries generated by this tool take the form `CODE Title (kind)`.
+fn title_and_kind(code: &str, summary: &str) -> (Option<String>, String) {
+    let summary = summary.trim();
+    let summary = summary.strip_prefix(code).map(str::trim_start).unwrap_or(summary);
+
+    if let (Some(open), true) = (summary.rfind('('), summary.ends_with(')')) {
+        let kind = summary[open + 1..summary.len() - 1].trim().to_lowercase();
+        let title = summary[..open].trim();
+        let title = if title.is_empty() { None } else { Some(title.to_owned()) };
+        return (title, kind);
+    }
+
+    let lower = summary.to_lowercase();
+    let kind = KINDS.iter().find(|kind| lower.contains(*kind)).map(|kind| kind.to_string());
+    let title = if summary.is_empty() { None } else { Some(summary.to_owned()) };
+    (title, kind.unwrap_or_else(|| "class".to_owned()))
+}
+
+fn datetime(time: DateTime<FixedOffset>) -> Result<Value, Error> {
+    Ok(Value::Datetime(time.to_rfc3339().parse().map_err(|error| format_err!("{}", error))?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three weekly lectures and a workshop, as exported by another calendar
+    const EXPORTED: &str = "BEGIN:VCALENDAR
+X-WR-CALNAME:COMP1000 Testing
+BEGIN:VTIMEZONE
+TZID:Australia/Sydney
+BEGIN:STANDARD
+TZOFFSETTO:+1100
+END:STANDARD
+END:VTIMEZONE
+BEGIN:VEVENT
+SUMMARY:COMP1000 Lecture
+DTSTART;TZID=Australia/Sydney:20240214T100000
+DTEND;TZID=Australia/Sydney:20240214T120000
+RRULE:FREQ=WEEKLY;COUNT=3
+LOCATION:Quad 1001
+END:VEVENT
+BEGIN:VEVENT
+SUMMARY:COMP1000 Revision (Workshop)
+DTSTART:20240220T030000Z
+DURATION:PT1H
+URL:https://example.edu/revision
+END:VEVENT
+END:VCALENDAR
+";
+
+    #[test]
+    fn titles_and_kinds_are_read_from_summaries() {
+        assert_eq!(title_and_kind("COMP1000", "COMP1000 Revision (Workshop)"), (Some("Revision".to_owned()), "workshop".to_owned()));
+        assert_eq!(title_and_kind("COMP1000", "COMP1000 (lab)"), (None, "lab".to_owned()));
+        assert_eq!(title_and_kind("COMP1000", "Weekly Tutorial"), (Some("Weekly Tutorial".to_owned()), "tutorial".to_owned()));
+        assert_eq!(title_and_kind("COMP1000", "Drop-in"), (Some("Drop-in".to_owned()), "class".to_owned()));
+    }
+
+    #[test]
+    fn weekly_events_become_repeated_sessions() {
+        let mut course = from_ics(EXPORTED, &Details::default()).unwrap();
+        assert_eq!((course.code(), course.name()), ("COMP1000", "COMP1000 Testing"));
+        assert_eq!(course.week_count(), 3);
+        assert_eq!(course.week_start(0).unwrap().to_rfc3339(), "2024-02-12T00:00:00+11:00");
+
+        course.generate_repeats().unwrap();
+        let events = course
+            .events()
+            .map(|event| (course.week_number(event.week().unwrap()), event.kind().to_owned(), event.start().to_rfc3339(), event.location().map(str::to_owned)))
+            .collect::<Vec<_>>();
+        let lecture = |week, start: &str| (week, "lecture".to_owned(), start.to_owned(), Some("Quad 1001".to_owned()));
+        assert_eq!(
+            events,
+            [
+                lecture(1, "2024-02-14T10:00:00+11:00"),
+                (2, "workshop".to_owned(), "2024-02-20T14:00:00+11:00".to_owned(), None),
+                lecture(2, "2024-02-21T10:00:00+11:00"),
+                lecture(3, "2024-02-28T10:00:00+11:00"),
+            ]
+        );
+    }
+
+    #[test]
+    fn details_override_the_calendar() {
+        let details = Details { code: Some("COMP2000".to_owned()), name: Some("Advanced".to_owned()), link: Some("https://example.edu/comp2000".to_owned()) };
+        let course = from_ics("BEGIN:VCALENDAR\nEND:VCALENDAR\n", &details).unwrap();
+        assert_eq!((course.code(), course.name(), course.week_count()), ("COMP2000", "Advanced", 0));
+
+        let course = from_ics("BEGIN:VCALENDAR\nEND:VCALENDAR\n", &Details::default()).unwrap();
+        assert_eq!((course.code(), course.name()), ("COURSE", "Imported course"));
+        assert_eq!(from_ics("BEGIN:VTODO\nEND:VTODO\n", &Details::default()).unwrap_err().to_string(), "No VCALENDAR found");
+    }
+}
@@ -0,0 +1,346 @@
+//! A minimal JSON document model for output formats
+
+use failure::{bail, format_err, Error};
+
+use std::fmt;
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A JSON value
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Construct an object from key and value pairs, keeping their order
+    pub fn object<K: Into<String>>(members: Vec<(K, Json)>) -> Self {
+        Json::Object(members.into_iter().map(|(key, value)| (key.into(), value)).collect())
+    }
+
+    /// Parse a JSON document
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let mut chars = source.chars().peekable();
+        let value = parse_value(&mut chars)?;
+        skip_whitespace(&mut chars);
+        match chars.next() {
+            None => Ok(value),
+            Some(c) => bail!("Unexpected {:?} after JSON value", c),
+        }
+    }
+
+    /// The value of a member of an object
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(name, _)| name == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(value) => Some(*value),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Format the value with indentation
+    pub fn pretty(&self) -> String {
+        let mut output = String::new();
+        self.write_pretty(&mut output, 0);
+        output
+    }
+
+    fn write_pretty(&self, output: &mut String, depth: usize) {
+        let indent = |depth| "  ".repeat(depth);
+        match self {
+            Json::Array(items) if !items.is_empty() => {
+                output.push_str("[\n");
+                for (index, item) in items.iter().enumerate() {
+                    output.push_str(&indent(depth + 1));
+                    item.write_pretty(output, depth + 1);
+                    output.push_str(if index + 1 < items.len() { ",\n" } else { "\n" });
+                }
+                output.push_str(&indent(depth));
+                output.push(']');
+            }
+            Json::Object(members) if !members.is_empty() => {
+                output.push_str("{\n");
+                for (index, (key, value)) in members.iter().enumerate() {
+                    output.push_str(&indent(depth + 1));
+                    output.push_str(&format!("{}: ", Json::String(key.clone())));
+                    value.write_pretty(output, depth + 1);
+                    output.push_str(if index + 1 < members.len() { ",\n" } else { "\n" });
+                }
+                output.push_str(&indent(depth));
+                output.push('}');
+            }
+            value => output.push_str(&value.to_string()),
+        }
+    }
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+    while chars.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+        chars.next();
+    }
+}
+
+fn expect(chars: &mut Peekable<Chars>, word: &str) -> Result<(), Error> {
+    for expected in word.chars() {
+        if chars.next() != Some(expected) {
+            bail!("Expected {} in JSON", word);
+        }
+    }
+    Ok(())
+}
+
+fn parse_value(chars: &mut Peekable<Chars>) -> Result<Json, Error> {
+    skip_whitespace(chars);
+    match chars.peek().cloned() {
+        Some('n') => expect(chars, "null").map(|_| Json::Null),
+        Some('t') => expect(chars, "true").map(|_| Json::Bool(true)),
+        Some('f') => expect(chars, "false").map(|_| Json::Bool(false)),
+        Some('"') => parse_string(chars).map(Json::String),
+        Some('[') => {
+            chars.next();
+            let mut items = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&']') {
+                chars.next();
+                return Ok(Json::Array(items));
+            }
+            loop {
+                items.push(parse_value(chars)?);
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some(']') => return Ok(Json::Array(items)),
+                    _ => bail!("Expected , or ] in JSON array"),
+                }
+            }
+        }
+        Some('{') => {
+            chars.next();
+            let mut members = Vec::new();
+            skip_whitespace(chars);
+            if chars.peek() == Some(&'}') {
+                chars.next();
+                return Ok(Json::Object(members));
+            }
+            loop {
+                skip_whitespace(chars);
+                let key = parse_string(chars)?;
+                skip_whitespace(chars);
+                expect(chars, ":")?;
+                members.push((key, parse_value(chars)?));
+                skip_whitespace(chars);
+                match chars.next() {
+                    Some(',') => continue,
+                    Some('}') => return Ok(Json::Object(members)),
+                    _ => bail!("Expected , or }} in JSON object"),
+                }
+            }
+        }
+        Some(c) if c == '-' || c.is_ascii_digit() => {
+            let mut number = String::new();
+            while let Some(c) = chars.peek().cloned().filter(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+                number.push(c);
+                chars.next();
+            }
+            Ok(Json::Number(number.parse().map_err(|_| format_err!("Invalid JSON number {}", number))?))
+        }
+        Some(c) => bail!("Unexpected {:?} in JSON", c),
+        None => bail!("Unexpected end of JSON"),
+    }
+}
+
+fn parse_string(chars: &mut Peekable<Chars>) -> Result<String, Error> {
+    expect(chars, "\"")?;
+    let mut value = String::new();
+    loop {
+        match chars.next().ok_or(format_err!("Unterminated JSON string"))? {
+            '"' => return Ok(value),
+            '\\' => match chars.next().ok_or(format_err!("Unterminated JSON string"))? {
+                'n' => value.push('\n'),
+                'r' => value.push('\r'),
+                't' => value.push('\t'),
+                'b' => value.push('\u{8}'),
+                'f' => value.push('\u{c}'),
+                'u' => {
+                    let mut code = parse_unit(chars)?;
+                    if (0xd800..0xdc00).contains(&code) {
+                        expect(chars, "\\u")?;
+                        code = 0x10000 + ((code - 0xd800) << 10) + parse_unit(chars)?.wrapping_sub(0xdc00);
+                    }
+                    value.push(std::char::from_u32(code).unwrap_or('\u{fffd}'));
+                }
+                c => value.push(c),
+            },
+            c => value.push(c),
+        }
+    }
+}
+
+/// Parse the four hexadecimal digits of a `\\u` escape
+fn parse_unit(chars: &mut Peekable<Chars>) -> Result<u32, Error> {
+    let hex = chars.by_ref().take(4).collect::<String>();
+    u32::from_str_radix(&hex, 16).map_err(|_| format_err!("Invalid JSON escape \\u{}", hex))
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(value) => write!(f, "{}", value),
+            Json::Number(value) if value.fract() == 0.0 && value.abs() < 1e15 => write!(f, "{}", *value as i64),
+            Json::Number(value) if value.is_finite() => write!(f, "{}", value),
+            Json::Number(_) => f.write_str("null"),
+            Json::String(value) => {
+                f.write_str("\"")?;
+                for c in value.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\r' => f.write_str("\\r")?,
+                        '\t' => f.write_str("\\t")?,
+                        c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                        c => write!(f, "{}", c)?,
+                    }
+                }
+                f.write_str("\"")
+            }
+            Json::Array(items) => {
+                f.write_str("[")?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(members) => {
+                f.write_str("{")?;
+                for (index, (key, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        f.write_str(",")?;
+                    }
+                    write!(f, "{}:{}", Json::String(key.clone()), value)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+impl From<bool> for Json {
+    fn from(value: bool) -> Self {
+        Json::Bool(value)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(value: i64) -> Self {
+        Json::Number(value as f64)
+    }
+}
+
+impl From<f64> for Json {
+    fn from(value: f64) -> Self {
+        Json::Number(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(value: &str) -> Self {
+        Json::String(value.to_owned())
+    }
+}
+
+impl From<String> for Json {
+    fn from(value: String) -> Self {
+        Json::String(value)
+    }
+}
+
+impl<T: Into<Json>> From<Option<T>> for Json {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Json::Null)
+    }
+}
+
+impl<T: Into<Json>> From<Vec<T>> for Json {
+    fn from(values: Vec<T>) -> Self {
+        Json::Array(values.into_iter().map(Into::into).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_written_compactly_or_indented() {
+        let value = Json::object(vec![
+            ("name", "Quiz \"1\"\n".into()),
+            ("weight", 0.5.into()),
+            ("tags", vec!["a", "b"].into()),
+            ("link", Json::Null),
+        ]);
+        assert_eq!(value.to_string(), r#"{"name":"Quiz \"1\"\n","weight":0.5,"tags":["a","b"],"link":null}"#);
+        assert_eq!(
+            value.pretty(),
+            "{\n  \"name\": \"Quiz \\\"1\\\"\\n\",\n  \"weight\": 0.5,\n  \"tags\": [\n    \"a\",\n    \"b\"\n  ],\n  \"link\": null\n}"
+        );
+    }
+
+    #[test]
+    fn written_values_parse_back() {
+        let value = Json::object(vec![("value", 10.into()), ("open", true.into()), ("items", vec![1.5, -3.0].into())]);
+        assert_eq!(Json::parse(&value.to_string()).unwrap(), value);
+        assert_eq!(Json::parse(&value.pretty()).unwrap(), value);
+    }
+
+    #[test]
+    fn escapes_are_decoded() {
+        assert_eq!(Json::parse(r#""tab\there é 😀 \/""#).unwrap().as_str(), Some("tab\there é 😀 /"));
+        assert_eq!(Json::parse(r#""\u00e9 \ud83d\ude00""#).unwrap().as_str(), Some("é 😀"));
+    }
+
+    #[test]
+    fn members_are_found_by_key() {
+        let value = Json::parse(r#" { "items" : [1, 2.5e1, -3] , "next": null } "#).unwrap();
+        let numbers = value.get("items").and_then(Json::as_array).unwrap().iter().filter_map(Json::as_f64).collect::<Vec<_>>();
+        assert_eq!(numbers, vec![1.0, 25.0, -3.0]);
+        assert_eq!(value.get("next"), Some(&Json::Null));
+        assert_eq!(value.get("missing"), None);
+    }
+
+    #[test]
+    fn malformed_documents_are_rejected() {
+        for source in &["", "[1,", "{\"a\" 1}", "\"unterminated", "tru", "1 2"] {
+            assert!(Json::parse(source).is_err(), "accepted {:?}", source);
+        }
+    }
+}
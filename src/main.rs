@@ -1,84 +1,593 @@
 //! Generate an ical file from the specification of course events.
 
 use failure::{format_err, Error};
-use toml;
-use ics::{ICalendar, Event, properties};
-use uuid::Uuid;
-use chrono::{DateTime, Duration, offset::Utc};
+use ics::ICalendar;
 
+// The modules that derive serde traits are exempt from `non_local_definitions`,
+// since serde_derive 1.0.101 places its impls inside a named const.
+mod calendar;
+mod clashes;
+#[allow(non_local_definitions)]
 mod course;
+mod diff;
+#[allow(non_local_definitions)]
+mod enrollment;
+mod export;
+mod fetch;
+mod filter;
+mod http;
+mod ical;
+mod import;
+mod json;
+mod markdown;
+mod migrate;
+#[allow(non_local_definitions)]
+mod notify;
+mod oauth;
+mod options;
+mod pattern;
+#[allow(non_local_definitions)]
+mod program;
+#[allow(non_local_definitions)]
+mod publish;
+mod roster;
+mod schema;
+#[allow(non_local_definitions)]
+mod serve;
+mod smtp;
+mod spec;
+mod split;
+#[allow(non_local_definitions)]
+mod state;
+mod stats;
+mod study;
+mod terminal;
+#[allow(non_local_definitions)]
+mod validate;
+mod zip;
 
+use course::Course;
+use enrollment::Enrollment;
+use filter::Filter;
+use options::{Command, Options};
+use split::Split;
+use state::State;
+use zip::Archive;
+
+use std::collections::BTreeSet;
 use std::env::args;
-use std::fs::File;
-use std::io::{BufReader, Read, stdout};
+use std::fmt;
+use std::fs::{create_dir_all, read_to_string, File};
+use std::io::{self, stdout, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Duration;
 
 fn main() -> Result<(), Error> {
-    let path = args()
-        .skip(1)
-        .next()
-        .ok_or(format_err!("Expects course as argument"))?;
-    let mut course_toml = String::new();
-    BufReader::new(File::open(&path)?).read_to_string(&mut course_toml)?;
+    let options = Options::from_args(args().skip(1))?;
+    if options.command == Command::Clashes {
+        return report_clashes(&options);
+    }
+
+    if options.command == Command::Schema {
+        println!("{}", schema::course().pretty());
+        return Ok(());
+    }
+
+    if options.command == Command::Format {
+        print!("{}", Course::load(options.path())?.to_toml()?);
+        return Ok(());
+    }
+
+    if options.command == Command::Diff {
+        return report_diff(&options);
+    }
+
+    if options.command == Command::Program {
+        let program = program::Program::from_path(options.path())?;
+        let courses = program.courses()?;
+        calendar::generate_program(&program, &courses, &options.filter).write(output(&options)?)?;
+        return Ok(());
+    }
+
+    if options.command == Command::Serve {
+        let listen = options.listen.as_deref().unwrap_or("127.0.0.1:8080");
+        let access = match &options.config {
+            Some(path) => serve::Access::from_path(path)?,
+            None => serve::Access::default(),
+        };
+        return serve::serve(options.path(), listen, &options.filter, options.public, access);
+    }
+
+    if options.command == Command::Show {
+        let course = load_course(&options)?;
+        let events = course.events().filter(|event| options.filter.matches(event)).collect::<Vec<_>>();
+        let weeks = match options.filter.weeks.is_empty() {
+            true => vec![terminal::current_week(&course)],
+            false => options.filter.weeks.iter().flat_map(|(first, last)| *first..=*last).filter_map(|number| course.week_index(number)).collect(),
+        };
+        for week in weeks {
+            println!("{}", terminal::grid(&course, week, &events, terminal::use_colour()));
+        }
+        return Ok(());
+    }
+
+    if options.command == Command::Today || options.command == Command::Next {
+        let course = load_course(&options)?;
+        let events = course.events().filter(|event| options.filter.matches(event)).collect::<Vec<_>>();
+        if options.command == Command::Today {
+            for event in terminal::today(&events) {
+                println!("{}", terminal::summary(&course, &event, false));
+            }
+        } else if let Some(event) = terminal::next(&events) {
+            println!("{}", terminal::summary(&course, &event, true));
+        }
+        return Ok(());
+    }
+
+    if options.command == Command::Find {
+        let query = options.query.as_deref().unwrap_or_default();
+        let pattern = if options.regex {
+            pattern::Pattern::new(query, true)?
+        } else {
+            pattern::Pattern::new(&pattern::escape(query), true)?
+        };
+        let course = load_course(&options)?;
+        let events = course
+            .events()
+            .filter(|event| options.filter.matches(event))
+            .filter(|event| {
+                pattern.is_match(&event.name())
+                    || event.location().map(|location| pattern.is_match(location)).unwrap_or(false)
+                    || event.presenters().any(|presenter| pattern.is_match(presenter))
+            })
+            .collect::<Vec<_>>();
+        if events.is_empty() {
+            return Err(format_err!("No events match {}", query));
+        }
+        print!("{}", terminal::agenda(&course, &events));
+        return Ok(());
+    }
+
+    if options.command == Command::Stats {
+        let course = load_course(&options)?;
+        let events = course.events().filter(|event| options.filter.matches(event)).collect::<Vec<_>>();
+        print!("{}", stats::Report { stats: stats::Stats::new(&course, &events), course: &course });
+        return Ok(());
+    }
+
+    if options.command == Command::Workload {
+        let course = load_course(&options)?;
+        let events = course.events().filter(|event| options.filter.matches(event)).collect::<Vec<_>>();
+        let workload = stats::Workload::new(&course, &events);
+        print!("{}", stats::WorkloadReport { course: &course, workload, thresholds: options.thresholds });
+        return Ok(());
+    }
+
+    if options.command == Command::Equipment {
+        let course = load_course(&options)?;
+        let events = course.events().filter(|event| options.filter.matches(event)).collect::<Vec<_>>();
+        print!("{}", stats::EquipmentReport { course: &course, equipment: stats::Equipment::new(&course, &events) });
+        return Ok(());
+    }
+
+    if options.command == Command::Agenda {
+        let course = load_course(&options)?;
+        let events = course.events().filter(|event| options.filter.matches(event)).collect::<Vec<_>>();
+        print!("{}", terminal::agenda(&course, &events));
+        return Ok(());
+    }
+
+    if options.command == Command::Notify {
+        let config = notify::Config::from_path(options.config.as_ref().ok_or(format_err!("Notifying requires --config"))?)?;
+        let count = notify::notify(&config, &load_course(&options)?, &options.filter)?;
+        eprintln!("Announced {} events", count);
+        return Ok(());
+    }
+
+    if options.command == Command::Token {
+        let code = options.details.code.as_deref().unwrap_or_default();
+        let (access, name) = (&options.paths[1], &options.paths[2]);
+        if options.paths[0] == "add" {
+            println!("{}", serve::Access::add_feed(access, name, code, &options.filter)?);
+        } else {
+            serve::Access::revoke_feed(access, name, code)?;
+        }
+        return Ok(());
+    }
+
+    if options.command == Command::Import {
+        let course = import::from_ics(&read_to_string(options.path())?, &options.details)?;
+        print!("{}", course.to_toml()?);
+        return Ok(());
+    }
+
+    if options.check {
+        exit(check(&options));
+    }
+
+    if options.command == Command::Validate {
+        return report_diagnostics(&Course::load_offering(options.path(), options.offering.as_deref())?, &options);
+    }
+
+    if options.watch {
+        return watch(&options);
+    }
+
+    generate(&options)
+}
+
+/// Generate the calendar of a course, or publish or distribute it
+fn generate(options: &Options) -> Result<(), Error> {
+    let mut course = load_course(options)?;
+    if let Some(alias) = &options.alias {
+        course.use_alias(alias)?;
+    }
+    if options.public {
+        course.redact();
+    }
+    let mut filter = options.filter.clone();
+    let mut name = course.name().to_owned();
+    if let (Some(enrollment), Some(student)) = (&options.enrollment, &options.student) {
+        let enrollment = Enrollment::from_path(enrollment)?;
+        let student = enrollment.student(student)?;
+        filter.streams.extend(student.streams(course.code()).iter().cloned());
+        name = format!("{} ({})", name, student.name().unwrap_or_else(|| student.id()));
+    }
+
+    let mut state = match &options.state {
+        Some(path) => State::from_path(path)?,
+        None => State::default(),
+    };
+    calendar::update_state(&course, &filter, &mut state);
+
+    if let Some(target) = options.target {
+        let config = publish::Config::from_path(options.config.as_ref().ok_or(format_err!("Publishing requires --config"))?)?;
+        let changes = calendar::pending(&course, &filter, &state, target.name());
+        let summary = publish::publish(target, &config, &course, &filter, &state, &changes)?;
+        eprintln!("Published {} events and deleted {}", summary.published, summary.deleted);
+        calendar::deliver(&mut state, target.name(), &changes);
+        return save_state(options, &state);
+    }
+
+    if let Some(roster) = &options.roster {
+        let destination = Destination::new(options).ok_or(format_err!("--roster requires --out-dir or --zip"))??;
+        generate_roster(&course, options, &state, roster, &destination)?;
+        destination.finish()?;
+        return save_state(options, &state);
+    }
+
+    if let Some(split) = options.split {
+        let destination = Destination::new(options).ok_or(format_err!("{} requires --out-dir or --zip", split.flag()))??;
+        generate_split(&course, options, &state, split, &destination)?;
+        destination.finish()?;
+        return save_state(options, &state);
+    }
+
+    if options.incremental {
+        let changes = calendar::pending(&course, &filter, &state, "incremental");
+        if changes.changed.is_empty() && changes.removed.is_empty() {
+            eprintln!("No events have changed");
+        }
+        let mut output = output(options)?;
+        if !changes.changed.is_empty() {
+            calendar::generate_changes(&course, name.clone(), &filter, &state, &changes).write(&mut output)?;
+        }
+        if !changes.removed.is_empty() {
+            calendar::generate_cancellations(name, &changes).write(&mut output)?;
+        }
+        calendar::deliver(&mut state, "incremental", &changes);
+        return save_state(options, &state);
+    }
+
+    if options.format != export::Format::Ics {
+        export::write(options.format, &course, &filter, &state, &mut output(options)?)?;
+        return save_state(options, &state);
+    }
+
+    let external = course.merged_calendars()?;
+    let mut calendar = calendar::generate(&course, name, &filter, &state);
+    add_optional(&mut calendar, &course, &filter, options);
+    calendar::merge(&mut calendar, &external);
+    calendar.write(output(options)?)?;
+
+    save_state(options, &state)
+}
 
-    let mut course: course::Course = toml::from_str(&course_toml)?;
-    course.generate_repeats()?;
+/// Add the events that are only generated when asked for
+fn add_optional(calendar: &mut ICalendar, course: &Course, filter: &Filter, options: &Options) {
+    if let Some(hours) = options.study_hours {
+        calendar::add_study(calendar, course, filter, hours);
+    }
+    if options.week_summaries {
+        calendar::add_summaries(calendar, course, filter);
+    }
+    if options.travel {
+        calendar::add_travel(calendar, course, filter);
+    }
+}
+
+/// Load the course given on the command line, in the offering chosen with `--offering`
+fn load_course(options: &Options) -> Result<Course, Error> {
+    Course::from_path_offering(options.path(), options.offering.as_deref())
+}
+
+/// Where to write the output, which is standard output unless `--output` is given
+fn output(options: &Options) -> Result<Box<dyn Write>, Error> {
+    match &options.output {
+        Some(path) => Ok(Box::new(BufWriter::new(File::create(path)?))),
+        None => Ok(Box::new(stdout())),
+    }
+}
 
-    let mut calendar = ICalendar::new("2.0", "ics-rs");
-    calendar.push(properties::Name::new(course.name()));
-    calendar.push(properties::CalScale::new("GREGORIAN"));
+/// How often to check watched files for changes
+const WATCH_INTERVAL: Duration = Duration::from_millis(500);
 
-    for event in course.events() {
-        let mut cal_event = Event::new(new_uuid(), time_format(Utc::now()));
+/// Regenerate whenever the course or the files it refers to change
+fn watch(options: &Options) -> Result<(), Error> {
+    let modified = |paths: &[PathBuf]| {
+        paths
+            .iter()
+            .map(|path| path.metadata().and_then(|metadata| metadata.modified()).ok())
+            .collect::<Vec<_>>()
+    };
 
-        let summary = format!("{} {}", course.code().to_owned(), event.title());
-        cal_event.push(properties::Summary::new(summary));
-        cal_event.push(properties::DtStart::new(time_format(event.start())));
-        cal_event.push(properties::DtEnd::new(time_format(event.end())));
-        //cal_event.push(properties::Duration::new(duration_format(event.duration())));
-        if let Some(location) = event.location() {
-            cal_event.push(properties::Location::new(location));
+    loop {
+        match generate(options) {
+            Ok(()) => eprintln!("Regenerated {}", options.path()),
+            Err(error) => eprintln!("error: {}", error),
         }
-        for presenter in event.presenters() {
-            cal_event.push(properties::Contact::new(presenter));
+
+        let mut watched = vec![PathBuf::from(options.path())];
+        if let Ok(course) = Course::load_offering(options.path(), options.offering.as_deref()) {
+            watched.extend(course.dependencies());
         }
-        if let Some(link) = event.link() {
-            cal_event.push(properties::URL::new(link.as_str()));
+        let before = modified(&watched);
+        while modified(&watched) == before {
+            thread::sleep(WATCH_INTERVAL);
         }
-        if let Some(description) = event.description() {
-            let description = description.split('\n').collect::<Vec<_>>();
-            let description = description.join("\\n");
-            cal_event.push(properties::Description::new(description));
+    }
+}
+
+
+/// Write back the state file, if one is in use
+fn save_state(options: &Options, state: &State) -> Result<(), Error> {
+    match &options.state {
+        Some(path) => state.save(path),
+        None => Ok(()),
+    }
+}
+
+/// Ensure that no two calendars would be written to the same file
+fn unique_files(files: &[String]) -> Result<(), Error> {
+    let mut seen = BTreeSet::new();
+    match files.iter().find(|file| !seen.insert(file.as_str())) {
+        Some(file) => Err(format_err!("More than one calendar would be written to {}, add placeholders to --file-name", file)),
+        None => Ok(()),
+    }
+}
+
+/// Generate a calendar for every student in a roster
+fn generate_roster(course: &Course, options: &Options, state: &State, roster: &str, destination: &Destination) -> Result<(), Error> {
+    let entries = roster::from_path(roster)?;
+    let template = options.file_name.as_deref().unwrap_or("{student}.ics");
+    let files = entries
+        .iter()
+        .map(|entry| split::file_name(template, &[("code", course.code()), ("student", &entry.id)]))
+        .collect::<Result<Vec<_>, _>>()?;
+    unique_files(&files)?;
+    let jobs = entries.iter().zip(&files).collect::<Vec<_>>();
+    let external = course.merged_calendars()?;
+
+    let next = AtomicUsize::new(0);
+    let failures = Mutex::new(Vec::new());
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(1).min(entries.len());
+
+    thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                while let Some((entry, file)) = jobs.get(next.fetch_add(1, Ordering::SeqCst)) {
+                    let mut filter = options.filter.clone();
+                    filter.streams.extend(entry.streams.iter().cloned());
+                    let name = format!("{} ({})", course.name(), entry.name.as_ref().unwrap_or(&entry.id));
+                    let mut calendar = calendar::generate(course, name, &filter, state);
+                    add_optional(&mut calendar, course, &filter, options);
+                    calendar::merge(&mut calendar, &external);
+                    if let Err(error) = destination.write(file, &calendar) {
+                        failures.lock().unwrap().push(format!("{}: {}", file, error));
+                    }
+                }
+            });
         }
+    });
+
+    let failures = failures.into_inner().unwrap();
+    for failure in &failures {
+        eprintln!("Failed to write {}", failure);
+    }
+    eprintln!(
+        "Generated {} of {} calendars {} ({} failed)",
+        entries.len() - failures.len(),
+        entries.len(),
+        destination,
+        failures.len(),
+    );
 
-        calendar.add_event(cal_event);
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(format_err!("Failed to generate {} calendars", failures.len()))
     }
+}
 
-    calendar.write(stdout())?;
+/// Generate a calendar for each part of a divided course
+fn generate_split(course: &Course, options: &Options, state: &State, split: Split, destination: &Destination) -> Result<(), Error> {
+    let external = course.merged_calendars()?;
 
+    let parts = split::parts(course, &options.filter, split);
+    let template = options.file_name.clone().unwrap_or_else(|| format!("{{{}}}.ics", split.key()));
+    let files = parts
+        .iter()
+        .map(|part| split::file_name(&template, &[("code", course.code()), (split.key(), &part.stem)]))
+        .collect::<Result<Vec<_>, _>>()?;
+    unique_files(&files)?;
+
+    for (part, file) in parts.iter().zip(&files) {
+        let (first, rest) = match part.filters.split_first() {
+            Some(filters) => filters,
+            None => continue,
+        };
+        let name = format!("{} ({})", course.name(), part.name);
+        let mut calendar = calendar::generate(course, name, first, state);
+        add_optional(&mut calendar, course, first, options);
+        let mut uids = calendar::events(course, first, state).into_iter().map(|(uid, _)| uid).collect::<BTreeSet<_>>();
+        for filter in rest {
+            for (uid, event) in calendar::events(course, filter, state) {
+                if uids.insert(uid) {
+                    calendar.add_event(event);
+                }
+            }
+        }
+        calendar::merge(&mut calendar, &external);
+
+        destination.write(file, &calendar)?;
+    }
+    eprintln!("Generated {} calendars {}", parts.len(), destination);
     Ok(())
 }
 
-fn new_uuid() -> String {
-    let mut buffer = Uuid::encode_buffer();
-    Uuid::new_v4().to_hyphenated().encode_lower(&mut buffer).to_owned()
+/// Where the calendars of a roster or a split course are written
+enum Destination<'o> {
+    /// Each calendar is a file in a directory
+    Directory(&'o str),
+    /// The calendars are bundled into a zip archive at a path, or standard output for `-`
+    Zip(&'o str, Mutex<Archive>),
 }
 
-fn time_format<O>(time: DateTime<O>) -> String
-where
-    O: chrono::TimeZone,
-    DateTime<Utc>: From<DateTime<O>>,
-{
-    let utc_time: DateTime<Utc> = time.into();
-    utc_time.format("%Y%m%dT%H%M%SZ").to_string()
+impl<'o> Destination<'o> {
+    /// The destination given by `--zip` or `--out-dir`, if either is
+    fn new(options: &'o Options) -> Option<Result<Self, Error>> {
+        if let Some(path) = &options.zip {
+            Some(Ok(Destination::Zip(path, Mutex::new(Archive::default()))))
+        } else {
+            let out_dir = options.out_dir.as_ref()?;
+            Some(create_dir_all(out_dir).map(|()| Destination::Directory(out_dir)).map_err(Error::from))
+        }
+    }
+
+    fn write(&self, file: &str, calendar: &ICalendar) -> io::Result<()> {
+        match self {
+            Destination::Directory(out_dir) => calendar.write(BufWriter::new(File::create(Path::new(out_dir).join(file))?)),
+            Destination::Zip(_, archive) => {
+                let mut contents = Vec::new();
+                calendar.write(&mut contents)?;
+                archive.lock().unwrap().add(file.to_owned(), contents);
+                Ok(())
+            }
+        }
+    }
+
+    /// Write the archive, once every calendar has been added
+    fn finish(self) -> Result<(), Error> {
+        if let Destination::Zip(path, archive) = self {
+            let archive = archive.into_inner().unwrap();
+            match path {
+                "-" => archive.write(stdout().lock())?,
+                path => archive.write(BufWriter::new(File::create(path)?))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Destination<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Destination::Directory(out_dir) => write!(f, "in {}", out_dir),
+            Destination::Zip("-", _) => f.write_str("into a zip archive"),
+            Destination::Zip(path, _) => write!(f, "into {}", path),
+        }
+    }
+}
+
+/// Report events that clash between several courses
+fn report_clashes(options: &Options) -> Result<(), Error> {
+    let courses = options.paths.iter().map(Course::from_path).collect::<Result<Vec<_>, _>>()?;
+    let events = courses
+        .iter()
+        .flat_map(|course| course.events().map(move |event| (course, event)))
+        .filter(|(_, event)| options.filter.matches(event))
+        .collect::<Vec<_>>();
+
+    let clashes = clashes::between_courses(&events);
+    for ((first_course, first), (second_course, second)) in &clashes {
+        println!("{}", clashes::describe(first_course, first));
+        println!("    clashes with {}", clashes::describe(second_course, second));
+    }
+    println!("{} clashes found", clashes.len());
+
+    Ok(())
+}
+
+/// Report the events added, removed, or changed between two versions of a course
+fn report_diff(options: &Options) -> Result<(), Error> {
+    let old = diff::load(&options.paths[0], &options.filter)?;
+    let new = diff::load(&options.paths[1], &options.filter)?;
+
+    let changes = diff::compare(old, new);
+    for change in &changes {
+        println!("{}", change);
+    }
+
+    let count = |kind: fn(&diff::Change) -> bool| changes.iter().filter(|change| kind(change)).count();
+    println!(
+        "{} added, {} removed, {} changed",
+        count(|change| matches!(change, diff::Change::Added(_))),
+        count(|change| matches!(change, diff::Change::Removed(_))),
+        count(|change| matches!(change, diff::Change::Changed(_, _))),
+    );
+
+    Ok(())
+}
+
+/// Report problems found in a course
+fn report_diagnostics(course: &Course, options: &Options) -> Result<(), Error> {
+    let diagnostics = validate::validate(course, &options.lints)?;
+    for diagnostic in &diagnostics {
+        println!("{}", diagnostic);
+    }
+
+    let errors = diagnostics.iter().filter(|d| d.severity == validate::Severity::Error).count();
+    println!("{} errors, {} warnings", errors, diagnostics.len() - errors);
+
+    if errors == 0 {
+        Ok(())
+    } else {
+        Err(format_err!("{} failed validation", course.code()))
+    }
 }
 
-fn duration_format(duration: Duration) -> String {
-    let days = duration.num_days();
-    let consumed = Duration::days(days);
-    let hours = (duration - consumed).num_hours();
-    let consumed = consumed + Duration::hours(hours);
-    let minutes = (duration - consumed).num_minutes();
-    let consumed = consumed + Duration::minutes(minutes);
-    let seconds = (duration - consumed).num_seconds();
-    format!("P{}DT{}H{}M{}S", days, hours, minutes, seconds)
+/// Validate a course, returning 0 if clean, 1 for warnings, or 2 for errors
+fn check(options: &Options) -> i32 {
+    let diagnostics = Course::load_offering(options.path(), options.offering.as_deref()).and_then(|course| validate::validate(&course, &options.lints));
+    let diagnostics = match diagnostics {
+        Ok(diagnostics) => diagnostics,
+        Err(error) => {
+            eprintln!("error: {}", error);
+            return 2;
+        }
+    };
+
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+
+    match diagnostics.iter().map(|diagnostic| diagnostic.severity).max() {
+        None => 0,
+        Some(validate::Severity::Warning) => 1,
+        Some(validate::Severity::Error) => 2,
+    }
 }
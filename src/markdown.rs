@@ -0,0 +1,336 @@
+//! A small Markdown renderer for event descriptions
+//!
+//! Only the subset of Markdown that is useful in a calendar description is
+//! supported: paragraphs, headings, bullet and numbered lists, links, emphasis,
+//! and inline code.
+
+/// Render a Markdown description as plain text
+pub fn to_plain(source: &str) -> String {
+    let blocks = parse_blocks(source)
+        .iter()
+        .map(|block| match block {
+            Block::Heading(_, text) | Block::Paragraph(text) => plain_inline(&parse_inline(text)),
+            Block::List(ordered, items) => items
+                .iter()
+                .enumerate()
+                .map(|(index, item)| {
+                    let marker = if *ordered { format!("{}.", index + 1) } else { "-".to_owned() };
+                    format!("{} {}", marker, plain_inline(&parse_inline(item)))
+                })
+                .collect::<Vec<_>>()
+                .join("\n"),
+        })
+        .collect::<Vec<_>>();
+    blocks.join("\n\n")
+}
+
+/// Render a Markdown description as an HTML document
+pub fn to_html(source: &str) -> String {
+    format!("<html><body>{}</body></html>", to_html_fragment(source))
+}
+
+/// Render a Markdown description as HTML to embed in another document
+pub fn to_html_fragment(source: &str) -> String {
+    let mut html = String::new();
+    for block in parse_blocks(source) {
+        match block {
+            Block::Heading(level, text) => {
+                html.push_str(&format!("<h{0}>{1}</h{0}>", level, html_inline(&parse_inline(&text))));
+            }
+            Block::Paragraph(text) => {
+                html.push_str(&format!("<p>{}</p>", html_inline(&parse_inline(&text))));
+            }
+            Block::List(ordered, items) => {
+                let tag = if ordered { "ol" } else { "ul" };
+                html.push_str(&format!("<{}>", tag));
+                for item in items {
+                    html.push_str(&format!("<li>{}</li>", html_inline(&parse_inline(&item))));
+                }
+                html.push_str(&format!("</{}>", tag));
+            }
+        }
+    }
+    html
+}
+
+/// A block-level element of a document
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Block {
+    Heading(usize, String),
+    Paragraph(String),
+    List(bool, Vec<String>),
+}
+
+/// An inline element of a block
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Inline {
+    Text(String),
+    Code(String),
+    Strong(Vec<Inline>),
+    Emphasis(Vec<Inline>),
+    Link(Vec<Inline>, String),
+}
+
+fn parse_blocks(source: &str) -> Vec<Block> {
+    let mut blocks = Vec::new();
+    let mut paragraph: Vec<&str> = Vec::new();
+    let mut in_list = false;
+
+    fn finish_paragraph(blocks: &mut Vec<Block>, paragraph: &mut Vec<&str>) {
+        if !paragraph.is_empty() {
+            blocks.push(Block::Paragraph(paragraph.join(" ")));
+            paragraph.clear();
+        }
+    }
+
+    for line in source.lines() {
+        let line = line.trim();
+        if line.is_empty() {
+            finish_paragraph(&mut blocks, &mut paragraph);
+            in_list = false;
+        } else if let Some((level, text)) = heading(line) {
+            finish_paragraph(&mut blocks, &mut paragraph);
+            blocks.push(Block::Heading(level, text.to_owned()));
+            in_list = false;
+        } else if let Some((ordered, item)) = list_item(line) {
+            finish_paragraph(&mut blocks, &mut paragraph);
+            match blocks.last_mut() {
+                Some(Block::List(list_ordered, items)) if in_list && *list_ordered == ordered => {
+                    items.push(item.to_owned());
+                }
+                _ => blocks.push(Block::List(ordered, vec![item.to_owned()])),
+            }
+            in_list = true;
+        } else if let (true, Some(Block::List(_, items))) = (in_list, blocks.last_mut()) {
+            // Lazy continuation of the last list item
+            if let Some(item) = items.last_mut() {
+                item.push(' ');
+                item.push_str(line);
+            }
+        } else {
+            paragraph.push(line);
+        }
+    }
+
+    finish_paragraph(&mut blocks, &mut paragraph);
+    blocks
+}
+
+fn heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|c| *c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &line[level..];
+    if rest.starts_with(' ') {
+        Some((level, rest.trim()))
+    } else {
+        None
+    }
+}
+
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    for marker in &["- ", "* ", "+ "] {
+        if let Some(item) = line.strip_prefix(marker) {
+            return Some((false, item.trim()));
+        }
+    }
+
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 {
+        let rest = &line[digits..];
+        if rest.starts_with(". ") || rest.starts_with(") ") {
+            return Some((true, rest[2..].trim()));
+        }
+    }
+
+    None
+}
+
+fn parse_inline(text: &str) -> Vec<Inline> {
+    let mut inlines = Vec::new();
+    let mut plain = String::new();
+    let mut rest = text;
+    let mut previous = ' ';
+
+    while let Some(c) = rest.chars().next() {
+        let parsed = match c {
+            // Underscores within words (such as identifiers) are not emphasis
+            '_' if previous.is_alphanumeric() => None,
+            '`' => delimited(rest, "`").map(|(inner, len)| (Inline::Code(inner.to_owned()), len)),
+            '*' | '_' => {
+                let double = if c == '*' { "**" } else { "__" };
+                if rest.starts_with(double) {
+                    delimited(rest, double).map(|(inner, len)| (Inline::Strong(parse_inline(inner)), len))
+                } else {
+                    delimited(rest, &rest[..1]).map(|(inner, len)| (Inline::Emphasis(parse_inline(inner)), len))
+                }
+            }
+            '[' => link(rest).map(|(text, url, len)| (Inline::Link(parse_inline(text), url.to_owned()), len)),
+            '<' => autolink(rest).map(|(url, len)| {
+                (Inline::Link(vec![Inline::Text(url.to_owned())], url.to_owned()), len)
+            }),
+            '\\' => rest[1..].chars().next().filter(|c| c.is_ascii_punctuation()).map(|c| {
+                (Inline::Text(c.to_string()), 1 + c.len_utf8())
+            }),
+            _ => None,
+        };
+
+        if let Some((inline, len)) = parsed {
+            if !plain.is_empty() {
+                inlines.push(Inline::Text(plain.clone()));
+                plain.clear();
+            }
+            match inline {
+                Inline::Text(text) => plain.push_str(&text),
+                inline => inlines.push(inline),
+            }
+            rest = &rest[len..];
+        } else {
+            plain.push(c);
+            rest = &rest[c.len_utf8()..];
+        }
+        previous = c;
+    }
+
+    if !plain.is_empty() {
+        inlines.push(Inline::Text(plain));
+    }
+    inlines
+}
+
+/// Find text between a pair of delimiters, returning it and the consumed length
+fn delimited<'t>(text: &'t str, delimiter: &str) -> Option<(&'t str, usize)> {
+    let inner = &text[delimiter.len()..];
+    let end = inner.find(delimiter)?;
+    if end == 0 {
+        return None;
+    }
+    Some((&inner[..end], end + 2 * delimiter.len()))
+}
+
+fn link(text: &str) -> Option<(&str, &str, usize)> {
+    let close = text.find("](")?;
+    let rest = &text[close + 2..];
+    let end = rest.find(')')?;
+    Some((&text[1..close], rest[..end].trim(), close + 2 + end + 1))
+}
+
+fn autolink(text: &str) -> Option<(&str, usize)> {
+    let end = text.find('>')?;
+    let url = &text[1..end];
+    if url.contains("://") && !url.contains(char::is_whitespace) {
+        Some((url, end + 1))
+    } else {
+        None
+    }
+}
+
+fn plain_inline(inlines: &[Inline]) -> String {
+    let mut plain = String::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) | Inline::Code(text) => plain.push_str(text),
+            Inline::Strong(inner) | Inline::Emphasis(inner) => plain.push_str(&plain_inline(inner)),
+            Inline::Link(inner, url) => {
+                let text = plain_inline(inner);
+                if &text == url {
+                    plain.push_str(url);
+                } else {
+                    plain.push_str(&format!("{} ({})", text, url));
+                }
+            }
+        }
+    }
+    plain
+}
+
+fn html_inline(inlines: &[Inline]) -> String {
+    let mut html = String::new();
+    for inline in inlines {
+        match inline {
+            Inline::Text(text) => html.push_str(&escape_html(text)),
+            Inline::Code(text) => html.push_str(&format!("<code>{}</code>", escape_html(text))),
+            Inline::Strong(inner) => html.push_str(&format!("<strong>{}</strong>", html_inline(inner))),
+            Inline::Emphasis(inner) => html.push_str(&format!("<em>{}</em>", html_inline(inner))),
+            Inline::Link(inner, url) if is_linkable(url) => {
+                html.push_str(&format!("<a href=\"{}\">{}</a>", escape_html(url), html_inline(inner)));
+            }
+            Inline::Link(inner, _) => html.push_str(&html_inline(inner)),
+        }
+    }
+    html
+}
+
+/// Whether a link target uses a scheme that is safe to place in an HTML description
+fn is_linkable(url: &str) -> bool {
+    let scheme = match url.find(':') {
+        Some(end) => url[..end].to_ascii_lowercase(),
+        None => return false,
+    };
+    ["http", "https", "mailto"].contains(&scheme.as_str())
+}
+
+/// Escape text for inclusion in HTML
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "# Week 1\n\nRead **chapter 1** and _skim_ `notes.md`.\n\n- Install the tools\n- Try\n  the lab\n\n1. Log in\n2. Submit";
+
+    #[test]
+    fn plain_text_drops_markup() {
+        assert_eq!(
+            to_plain(SOURCE),
+            "Week 1\n\nRead chapter 1 and skim notes.md.\n\n- Install the tools\n- Try the lab\n\n1. Log in\n2. Submit"
+        );
+    }
+
+    #[test]
+    fn html_wraps_each_block() {
+        assert_eq!(
+            to_html_fragment(SOURCE),
+            "<h1>Week 1</h1><p>Read <strong>chapter 1</strong> and <em>skim</em> <code>notes.md</code>.</p>\
+             <ul><li>Install the tools</li><li>Try the lab</li></ul><ol><li>Log in</li><li>Submit</li></ol>"
+        );
+    }
+
+    #[test]
+    fn links_show_their_targets_in_plain_text() {
+        assert_eq!(to_plain("See [the spec](https://example.edu/spec)"), "See the spec (https://example.edu/spec)");
+        assert_eq!(to_plain("See <https://example.edu/>"), "See https://example.edu/");
+        assert_eq!(
+            to_html_fragment("[a & b](https://example.edu/?a=1&b=2)"),
+            "<p><a href=\"https://example.edu/?a=1&amp;b=2\">a &amp; b</a></p>"
+        );
+    }
+
+    #[test]
+    fn only_web_and_mail_links_are_rendered() {
+        assert_eq!(
+            to_html_fragment("[run](javascript:alert`1`) or [mail](MAILTO:staff@example.edu)"),
+            "<p>run or <a href=\"MAILTO:staff@example.edu\">mail</a></p>"
+        );
+        assert_eq!(to_html_fragment("[notes](notes.html)"), "<p>notes</p>");
+    }
+
+    #[test]
+    fn underscores_within_words_are_kept() {
+        assert_eq!(to_plain("Call snake_case_name"), "Call snake_case_name");
+        assert_eq!(to_plain("A \\*literal\\* star"), "A *literal* star");
+    }
+}
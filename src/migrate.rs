@@ -0,0 +1,123 @@
+//! Upgrading of older course specification layouts
+
+use failure::{bail, format_err, Error};
+use toml::Value;
+
+/// The version of the specification layout understood by `Course`
+pub const CURRENT_VERSION: i64 = 2;
+
+/// An upgrade from one version of the layout to the next
+type Migration = fn(&mut Value) -> Result<(), Error>;
+
+/// Migrations from each version to the next, starting at version 1
+const MIGRATIONS: &[Migration] = &[one_based_weeks];
+
+/// Upgrade a specification of any known version to the current layout
+pub fn upgrade(mut spec: Value) -> Result<Value, Error> {
+    let version = match spec.get("version") {
+        Some(version) => version.as_integer().ok_or(format_err!("Specification version must be an integer"))?,
+        None => 1,
+    };
+
+    if version < 1 {
+        bail!("Unknown specification version {}", version);
+    } else if version > CURRENT_VERSION {
+        bail!("Specification version {} is newer than the supported version {}", version, CURRENT_VERSION);
+    }
+
+    for migration in &MIGRATIONS[(version - 1) as usize..] {
+        migration(&mut spec)?;
+    }
+
+    if let Value::Table(table) = &mut spec {
+        table.insert("version".to_owned(), Value::Integer(CURRENT_VERSION));
+    }
+    Ok(spec)
+}
+
+/// Version 1 referred to weeks by zero-based index rather than by week number
+///
+/// Specifications that choose their week numbering are left as they are.
+fn one_based_weeks(spec: &mut Value) -> Result<(), Error> {
+    fn shift(table: &mut Value) -> Result<(), Error> {
+        if let Some(Value::Array(weeks)) = table.get_mut("weeks") {
+            for week in weeks {
+                match week {
+                    Value::Integer(index) => *index += 1,
+                    // Labels name the same week whatever the numbering
+                    Value::String(_) => {}
+                    _ => bail!("Week references must be integers or labels"),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn tables<'v>(value: &'v mut Value, key: &str) -> impl Iterator<Item = &'v mut Value> {
+        value
+            .get_mut(key)
+            .and_then(Value::as_array_mut)
+            .into_iter()
+            .flat_map(|tables| tables.iter_mut())
+    }
+
+    if spec.get("week_numbering").is_some() {
+        return Ok(());
+    }
+
+    for key in &["session", "quiz", "office_hours"] {
+        for table in tables(spec, key) {
+            shift(table)?;
+        }
+    }
+
+    for assignment in tables(spec, "assignment") {
+        for presentation in tables(assignment, "presentation") {
+            shift(presentation)?;
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn weeks(spec: &Value, key: &str) -> Vec<i64> {
+        spec[key][0]["weeks"].as_array().unwrap().iter().map(|week| week.as_integer().unwrap()).collect()
+    }
+
+    #[test]
+    fn version_one_weeks_become_week_numbers() {
+        let spec: Value = toml::from_str(
+            r#"
+            [[session]]
+            weeks = [0, 2]
+
+            [[assignment]]
+            [[assignment.presentation]]
+            weeks = [1]
+
+            [[quiz]]
+            weeks = [0, 1]
+
+            [[office_hours]]
+            weeks = [3]
+            "#,
+        )
+        .unwrap();
+        let spec = upgrade(spec).unwrap();
+        assert_eq!(spec["version"].as_integer(), Some(CURRENT_VERSION));
+        assert_eq!(weeks(&spec, "session"), vec![1, 3]);
+        assert_eq!(weeks(&spec["assignment"][0], "presentation"), vec![2]);
+        assert_eq!(weeks(&spec, "quiz"), vec![1, 2]);
+        assert_eq!(weeks(&spec, "office_hours"), vec![4]);
+    }
+
+    #[test]
+    fn chosen_numbering_is_left_alone() {
+        let spec: Value = toml::from_str("week_numbering = \"zero-based\"\n[[quiz]]\nweeks = [0]").unwrap();
+        assert_eq!(weeks(&upgrade(spec).unwrap(), "quiz"), vec![0]);
+    }
+}
@@ -0,0 +1,195 @@
+//! Announcements of upcoming events and deadlines by email or chat webhook
+
+use chrono::{offset::FixedOffset, Duration, Utc};
+use failure::{bail, format_err, Error};
+use serde::Deserialize;
+use url::Url;
+
+use crate::course::{deserialize_url, Course, Event};
+use crate::filter::{Bound, Filter};
+use crate::http;
+use crate::json::Json;
+use crate::publish::secret;
+use crate::smtp::{self, Message};
+
+use std::fs::read_to_string;
+use std::path::Path;
+use std::process::Command;
+
+/// Where to send announcements
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Config {
+    smtp: Option<Smtp>,
+    #[serde(default, rename = "webhook")]
+    webhooks: Vec<Webhook>,
+}
+
+impl Config {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(toml::from_str(&read_to_string(path)?)?)
+    }
+}
+
+/// A mail server and the people to email
+#[derive(Debug, Clone, Deserialize)]
+struct Smtp {
+    /// The server, such as `smtp://localhost:25` or `smtps://mail.example.edu`
+    #[serde(deserialize_with = "deserialize_url")]
+    url: Url,
+    username: Option<String>,
+    password: Option<String>,
+    password_env: Option<String>,
+    from: String,
+    to: Vec<String>,
+}
+
+/// A chat channel that accepts messages posted to a URL
+#[derive(Debug, Clone, Deserialize)]
+struct Webhook {
+    #[serde(deserialize_with = "deserialize_url")]
+    url: Url,
+    platform: Platform,
+}
+
+/// The chat service behind a webhook, which determines how messages are formatted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Platform {
+    Slack,
+    Discord,
+}
+
+/// The most Discord accepts in a single message
+const DISCORD_LIMIT: usize = 2000;
+
+/// The markup used to format a digest
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Style {
+    Plain,
+    Slack,
+    Discord,
+}
+
+impl Style {
+    fn heading(self, text: &str) -> String {
+        match self {
+            Style::Plain => text.to_owned(),
+            Style::Slack => format!("*{}*", text),
+            Style::Discord => format!("**{}**", text),
+        }
+    }
+
+    fn link(self, text: &str, url: &Url) -> String {
+        match self {
+            Style::Plain => format!("{} <{}>", text, url),
+            Style::Slack => format!("<{}|{}>", url, text.replace(['<', '>', '|'], "")),
+            Style::Discord => format!("[{}](<{}>)", text.replace(['[', ']'], ""), url),
+        }
+    }
+}
+
+/// Send an announcement of the events in a course that match a filter
+///
+/// Without any bounds in the filter, the events of the coming week are
+/// announced. Returns the number of events announced.
+pub fn notify(config: &Config, course: &Course, filter: &Filter) -> Result<usize, Error> {
+    let mut filter = filter.clone();
+    if filter.from.is_none() && filter.to.is_none() {
+        let now = Utc::now().with_timezone(&FixedOffset::east(0));
+        filter.from = Some(Bound::Time(now));
+        filter.to = Some(Bound::Time(now + Duration::weeks(1)));
+    }
+    let events = course.events().filter(|event| filter.matches(event)).collect::<Vec<_>>();
+    if events.is_empty() {
+        return Ok(0);
+    }
+
+    if config.smtp.is_none() && config.webhooks.is_empty() {
+        bail!("No [smtp] or [[webhook]] sections in the configuration");
+    }
+
+    if let Some(smtp) = &config.smtp {
+        let password = secret(&smtp.password, &smtp.password_env)?;
+        let credentials = smtp.username.as_deref().zip(password.as_deref());
+        let subject = format!("{}: upcoming events and deadlines", course.code());
+        let body = digest(course, &events, Style::Plain);
+        let message = Message { from: &smtp.from, to: &smtp.to, subject: &subject, body: &body };
+        smtp::send(&smtp.url, credentials, &message)?;
+    }
+
+    for webhook in &config.webhooks {
+        let body = match webhook.platform {
+            Platform::Slack => Json::object(vec![("text", digest(course, &events, Style::Slack).into())]),
+            Platform::Discord => {
+                let mut content = digest(course, &events, Style::Discord);
+                if content.chars().count() > DISCORD_LIMIT {
+                    content = content.chars().take(DISCORD_LIMIT - 1).collect::<String>() + "…";
+                }
+                Json::object(vec![("content", content.into())])
+            }
+        };
+        post(&webhook.url, &body)?;
+    }
+
+    Ok(events.len())
+}
+
+/// Post a JSON message to a webhook
+///
+/// As with downloads, HTTPS URLs are handed to curl.
+fn post(url: &Url, body: &Json) -> Result<(), Error> {
+    if url.scheme() == "http" {
+        let headers = [("Content-Type", "application/json".to_owned())];
+        let response = http::request("POST", url, &headers, Some(&body.to_string()))?;
+        if !response.is_success() {
+            bail!("Posting to {} failed with status {}: {}", url, response.status, response.body.trim());
+        }
+        return Ok(());
+    }
+
+    let output = Command::new("curl")
+        .args(["--fail", "--silent", "--show-error", "--max-time", "30"])
+        .args(["--header", "Content-Type: application/json", "--data-binary"])
+        .arg(body.to_string())
+        .arg(url.as_str())
+        .output()
+        .map_err(|error| format_err!("Posting to {} requires curl: {}", url, error))?;
+    if !output.status.success() {
+        bail!("Posting to {} failed: {}", url, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// A list of sessions followed by deadlines
+fn digest(course: &Course, events: &[Event], style: Style) -> String {
+    let (deadlines, sessions): (Vec<_>, Vec<_>) = events.iter().partition(|event| event.is_point_in_time());
+    let mut digest = format!("{}\n", style.heading(&format!("{} {}", course.code(), course.name())));
+
+    if !sessions.is_empty() {
+        digest.push_str(&format!("\n{}\n\n", style.heading("Sessions")));
+        for event in sessions {
+            digest.push_str(&format!(
+                "  {}-{}  {}",
+                event.start().format("%a %-d %b %H:%M"),
+                event.end().format("%H:%M"),
+                event.name(),
+            ));
+            if let Some(location) = event.location() {
+                digest.push_str(&format!(" ({})", location));
+            }
+            digest.push('\n');
+        }
+    }
+
+    if !deadlines.is_empty() {
+        digest.push_str(&format!("\n{}\n\n", style.heading("Deadlines")));
+        for event in deadlines {
+            let name = match event.link() {
+                Some(link) => style.link(&event.name(), link),
+                None => event.name(),
+            };
+            digest.push_str(&format!("  {}  {}\n", event.start().format("%a %-d %b %H:%M"), name));
+        }
+    }
+    digest
+}
@@ -0,0 +1,199 @@
+//! The OAuth 2.0 device authorization flow
+
+use failure::{bail, format_err, Error};
+use url::{form_urlencoded, Url};
+
+use crate::http;
+use crate::json::Json;
+
+use std::fs::{read_to_string, write};
+use std::path::Path;
+use std::thread::sleep;
+use std::time::Duration;
+
+/// The grant type used when polling for a device authorization
+const DEVICE_GRANT: &str = "urn:ietf:params:oauth:grant-type:device_code";
+
+/// An OAuth client that authorises itself through the device flow
+#[derive(Debug, Clone)]
+pub struct DeviceFlow<'a> {
+    pub device_url: Url,
+    pub token_url: Url,
+    pub client_id: &'a str,
+    pub client_secret: Option<&'a str>,
+    pub scope: &'a str,
+}
+
+impl<'a> DeviceFlow<'a> {
+    /// Obtain an access token, reusing a refresh token stored in `cache`
+    ///
+    /// Without a usable refresh token, the user is asked to approve access
+    /// in a browser and the new refresh token is stored for next time.
+    pub fn access_token(&self, cache: Option<&Path>) -> Result<String, Error> {
+        if let Some(refresh) = cache.and_then(|cache| read_to_string(cache).ok()) {
+            let response = self.token(&[("grant_type", "refresh_token"), ("refresh_token", refresh.trim())])?;
+            if let Some(token) = response.get("access_token").and_then(Json::as_str) {
+                // Some servers rotate refresh tokens, invalidating the old one
+                if let (Some(cache), Some(rotated)) = (cache, response.get("refresh_token").and_then(Json::as_str)) {
+                    write(cache, rotated)?;
+                }
+                return Ok(token.to_owned());
+            }
+        }
+
+        let device = post_form(&self.device_url, &[("client_id", self.client_id), ("scope", self.scope)])?;
+        let field = |name: &str| device.get(name).and_then(Json::as_str).map(str::to_owned);
+        let device_code = field("device_code").ok_or(format_err!("No device code in authorization response"))?;
+        let user_code = field("user_code").ok_or(format_err!("No user code in authorization response"))?;
+        let verification = field("verification_uri")
+            .or_else(|| field("verification_url"))
+            .ok_or(format_err!("No verification URL in authorization response"))?;
+        let mut interval = device.get("interval").and_then(Json::as_f64).unwrap_or(5.0) as u64;
+
+        eprintln!("Visit {} and enter the code {}", verification, user_code);
+        loop {
+            sleep(Duration::from_secs(interval));
+            let response = self.token(&[("grant_type", DEVICE_GRANT), ("device_code", &device_code)])?;
+            if let Some(token) = response.get("access_token").and_then(Json::as_str) {
+                if let (Some(cache), Some(refresh)) = (cache, response.get("refresh_token").and_then(Json::as_str)) {
+                    write(cache, refresh)?;
+                }
+                return Ok(token.to_owned());
+            }
+            match response.get("error").and_then(Json::as_str) {
+                Some("authorization_pending") => {}
+                Some("slow_down") => interval += 5,
+                Some(error) => bail!("Authorization failed: {}", error),
+                None => bail!("Unexpected token response: {}", response),
+            }
+        }
+    }
+
+    fn token(&self, parameters: &[(&str, &str)]) -> Result<Json, Error> {
+        let mut form = vec![("client_id", self.client_id)];
+        if let Some(secret) = self.client_secret {
+            form.push(("client_secret", secret));
+        }
+        form.extend_from_slice(parameters);
+        post_form(&self.token_url, &form)
+    }
+}
+
+fn post_form(url: &Url, parameters: &[(&str, &str)]) -> Result<Json, Error> {
+    let body = form_urlencoded::Serializer::new(String::new()).extend_pairs(parameters).finish();
+    let headers = [("Content-Type", "application/x-www-form-urlencoded".to_owned())];
+    let response = http::request("POST", url, &headers, Some(&body))?;
+    Json::parse(&response.body).map_err(|error| format_err!("Invalid response from {}: {}", url, error))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::path::PathBuf;
+    use std::thread;
+
+    /// Answer each form posted to the returned base URL with the next JSON document in turn
+    fn authorization_server(documents: &'static [&'static str]) -> (Url, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let handle = thread::spawn(move || {
+            let mut forms = Vec::new();
+            for document in documents {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut length = 0;
+                let mut request_line = String::new();
+                reader.read_line(&mut request_line).unwrap();
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = header.strip_prefix("Content-Length: ") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut form = vec![0; length];
+                reader.read_exact(&mut form).unwrap();
+                let path = request_line.split(' ').nth(1).unwrap().to_owned();
+                forms.push(format!("{} {}", path, String::from_utf8(form).unwrap()));
+                write!(stream, "HTTP/1.0 200 OK\r\nContent-Type: application/json\r\n\r\n{}", document).unwrap();
+            }
+            forms
+        });
+        (base, handle)
+    }
+
+    fn flow<'a>(base: &Url) -> DeviceFlow<'a> {
+        DeviceFlow {
+            device_url: base.join("device").unwrap(),
+            token_url: base.join("token").unwrap(),
+            client_id: "calendar",
+            client_secret: Some("s3cret"),
+            scope: "calendar.write",
+        }
+    }
+
+    fn cache(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("course-calendar-oauth-{}-{}", name, std::process::id()))
+    }
+
+    #[test]
+    fn stored_refresh_tokens_are_used_and_rotated() {
+        let cache = cache("refresh");
+        write(&cache, "old-refresh\n").unwrap();
+        let (base, server) = authorization_server(&[r#"{"access_token": "access", "refresh_token": "new-refresh"}"#]);
+
+        assert_eq!(flow(&base).access_token(Some(&cache)).unwrap(), "access");
+        assert_eq!(
+            server.join().unwrap(),
+            ["/token client_id=calendar&client_secret=s3cret&grant_type=refresh_token&refresh_token=old-refresh"]
+        );
+        assert_eq!(read_to_string(&cache).unwrap(), "new-refresh");
+        std::fs::remove_file(&cache).unwrap();
+    }
+
+    #[test]
+    fn devices_are_polled_until_authorised() {
+        let cache = cache("device");
+        let (base, server) = authorization_server(&[
+            r#"{"device_code": "dev", "user_code": "ABCD-EFGH", "verification_url": "https://example.edu/device", "interval": 0}"#,
+            r#"{"error": "authorization_pending"}"#,
+            r#"{"access_token": "access", "refresh_token": "refresh"}"#,
+        ]);
+
+        assert_eq!(flow(&base).access_token(Some(&cache)).unwrap(), "access");
+        let forms = server.join().unwrap();
+        assert_eq!(forms[0], "/device client_id=calendar&scope=calendar.write");
+        assert_eq!(forms[1], forms[2]);
+        assert!(forms[2].ends_with("&device_code=dev"));
+        assert!(forms[2].contains("grant_type=urn%3Aietf%3Aparams%3Aoauth%3Agrant-type%3Adevice_code"));
+        assert_eq!(read_to_string(&cache).unwrap(), "refresh");
+        std::fs::remove_file(&cache).unwrap();
+    }
+
+    #[test]
+    fn refused_authorisations_are_reported() {
+        let (base, server) = authorization_server(&[
+            r#"{"device_code": "dev", "user_code": "ABCD-EFGH", "verification_uri": "https://example.edu/device", "interval": 0}"#,
+            r#"{"error": "access_denied"}"#,
+        ]);
+
+        let error = flow(&base).access_token(None).unwrap_err();
+        server.join().unwrap();
+        assert_eq!(error.to_string(), "Authorization failed: access_denied");
+    }
+
+    #[test]
+    fn incomplete_authorization_responses_are_rejected() {
+        let (base, server) = authorization_server(&[r#"{"device_code": "dev", "verification_uri": "https://example.edu/device"}"#]);
+
+        let error = flow(&base).access_token(None).unwrap_err();
+        server.join().unwrap();
+        assert_eq!(error.to_string(), "No user code in authorization response");
+    }
+}
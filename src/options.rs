@@ -0,0 +1,406 @@
+//! Command line options
+
+use chrono::{Duration, FixedOffset, Utc};
+use failure::{bail, format_err, Error};
+
+use crate::export::Format;
+use crate::filter::{parse_weeks, Assignments, Bound, Filter};
+use crate::import::Details;
+use crate::publish::Target;
+use crate::split::Split;
+use crate::stats::Thresholds;
+use crate::validate::Level;
+
+use std::collections::BTreeMap;
+
+/// The action to perform
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Command {
+    /// Generate a calendar for a course
+    #[default]
+    Generate,
+    /// Report overlapping events across several courses
+    Clashes,
+    /// Check a course for likely mistakes
+    Validate,
+    /// Print a JSON Schema for course specifications
+    Schema,
+    /// Print the course specification as normalized TOML
+    Format,
+    /// Convert an iCalendar file into a course specification
+    Import,
+    /// Report changes between two versions of a course
+    Diff,
+    /// Push the events of a course to a remote calendar
+    Publish,
+    /// Serve calendars for a directory of courses over HTTP
+    Serve,
+    /// Add or revoke private feeds for the server
+    Token,
+    /// Announce upcoming events and deadlines
+    Notify,
+    /// Draw a week of the course as a grid in the terminal
+    Show,
+    /// List the events on today
+    Today,
+    /// Describe the next event to start
+    Next,
+    /// List the events in a range of time
+    Agenda,
+    /// Search for events by title, location, or presenter
+    Find,
+    /// Report contact hours, assessments, and presenters
+    Stats,
+    /// Report the load in each week, flagging overloaded weeks
+    Workload,
+    /// List the equipment needed by sessions in each week
+    Equipment,
+    /// Generate a combined calendar for the courses of a degree program
+    Program,
+}
+
+impl Command {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "clashes" => Some(Command::Clashes),
+            "validate" => Some(Command::Validate),
+            "schema" => Some(Command::Schema),
+            "format" => Some(Command::Format),
+            "import" => Some(Command::Import),
+            "diff" => Some(Command::Diff),
+            "publish" => Some(Command::Publish),
+            "serve" => Some(Command::Serve),
+            "token" => Some(Command::Token),
+            "notify" => Some(Command::Notify),
+            "show" => Some(Command::Show),
+            "today" => Some(Command::Today),
+            "next" => Some(Command::Next),
+            "agenda" => Some(Command::Agenda),
+            "find" => Some(Command::Find),
+            "stats" => Some(Command::Stats),
+            "workload" => Some(Command::Workload),
+            "equipment" => Some(Command::Equipment),
+            "program" => Some(Command::Program),
+            _ => None,
+        }
+    }
+}
+
+/// Options controlling how a calendar is generated
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    pub command: Command,
+    /// Paths to the course specifications
+    pub paths: Vec<String>,
+    pub filter: Filter,
+    /// File listing the streams chosen by each student
+    pub enrollment: Option<String>,
+    /// Student for whom to generate a personal calendar
+    pub student: Option<String>,
+    /// CSV roster of students for whom to generate calendars
+    pub roster: Option<String>,
+    /// Directory in which to write generated calendars
+    pub out_dir: Option<String>,
+    /// Zip archive in which to bundle generated calendars, or `-` for standard output
+    pub zip: Option<String>,
+    /// Template for the names of the files in the output directory
+    pub file_name: Option<String>,
+    /// Levels for validation rules, overriding those in the course
+    pub lints: BTreeMap<String, Level>,
+    /// Only validate the course, exiting with 0, 1, or 2 for clean, warnings, or errors
+    pub check: bool,
+    /// File recording event revisions between generations
+    ///
+    /// Events are stamped with the time of their last change, so without a
+    /// state file every event is stamped with the time of generation.
+    pub state: Option<String>,
+    /// Only emit the events that changed since the last incremental calendar
+    pub incremental: bool,
+    /// Where to publish the course
+    pub target: Option<Target>,
+    /// File with connection details for publishing
+    pub config: Option<String>,
+    /// The format in which to write events
+    pub format: Format,
+    /// File to write the calendar to instead of standard output
+    pub output: Option<String>,
+    /// Regenerate whenever the course changes
+    pub watch: bool,
+    /// Address on which to serve calendars
+    pub listen: Option<String>,
+    /// Course details for imported calendars
+    pub details: Details,
+    /// Text to search for in events
+    pub query: Option<String>,
+    /// Treat the query as a regular expression
+    pub regex: bool,
+    /// Limits for the workload of a week
+    pub thresholds: Thresholds,
+    /// Hours of study to schedule before each submission
+    pub study_hours: Option<f64>,
+    /// Add an all-day event summarising each week
+    pub week_summaries: bool,
+    /// Add events for travel between sessions in different buildings
+    pub travel: bool,
+    /// Leave out private sessions and fields
+    pub public: bool,
+    /// Alias code under which to generate the course
+    pub alias: Option<String>,
+    /// Term of the offering to generate, applying its overrides
+    pub offering: Option<String>,
+    /// Write several calendars dividing the course into the output directory
+    pub split: Option<Split>,
+}
+
+impl Options {
+    /// Parse options from the command line arguments (excluding the program name)
+    pub fn from_args<I: IntoIterator<Item = String>>(args: I) -> Result<Self, Error> {
+        let mut options = Options::default();
+        let mut paths = Vec::new();
+        let mut args = args.into_iter().peekable();
+
+        if let Some(command) = args.peek().and_then(|arg| Command::from_name(arg)) {
+            options.command = command;
+            args.next();
+        }
+
+        while let Some(arg) = args.next() {
+            if !arg.starts_with("--") {
+                paths.push(arg);
+                continue;
+            }
+
+            let (flag, inline) = match arg.find('=') {
+                Some(split) => (arg[..split].to_owned(), Some(arg[split + 1..].to_owned())),
+                None => (arg.clone(), None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format_err!("Expects a value for {}", flag))
+            };
+
+            match flag.as_str() {
+                "--check" => options.check = true,
+                "--incremental" => options.incremental = true,
+                "--watch" => options.watch = true,
+                "--regex" => options.regex = true,
+                "--week-summaries" => options.week_summaries = true,
+                "--travel" => options.travel = true,
+                "--public" => options.public = true,
+                "--per-group" => options.split = Some(Split::Group),
+                "--per-presenter" => options.split = Some(Split::Presenter),
+                "--per-room" => options.split = Some(Split::Room),
+                "--max-hours" => {
+                    let hours = value()?;
+                    options.thresholds.hours = hours.parse().map_err(|_| format_err!("Expected a number of hours but found {}", hours))?;
+                }
+                "--max-deadlines" => {
+                    let deadlines = value()?;
+                    options.thresholds.deadlines = deadlines.parse().map_err(|_| format_err!("Expected a number of deadlines but found {}", deadlines))?;
+                }
+                "--study-hours" => {
+                    let hours = value()?;
+                    options.study_hours = Some(hours.parse().map_err(|_| format_err!("Expected a number of hours but found {}", hours))?);
+                }
+                "--split" => options.split = Some(Split::from_name(&value()?)?),
+                "--output" => options.output = Some(value()?),
+                "--format" => options.format = Format::from_name(&value()?)?,
+                "--no-assignments" => options.filter.assignments = Assignments::Exclude,
+                "--assignments-only" => options.filter.assignments = Assignments::Only,
+                "--kind" => options.filter.kinds.push(value()?),
+                "--from" => options.filter.from = Some(Bound::parse(&value()?)?),
+                "--to" => options.filter.to = Some(Bound::parse(&value()?)?),
+                "--upcoming" => {
+                    let weeks = value()?;
+                    let weeks = weeks.parse::<i64>().map_err(|_| format_err!("Expected a number of weeks but found {}", weeks))?;
+                    let now = Utc::now().with_timezone(&FixedOffset::east(0));
+                    options.filter.from = Some(Bound::Time(now));
+                    options.filter.to = Some(Bound::Time(now + Duration::weeks(weeks)));
+                }
+                "--weeks" => options.filter.weeks.extend(parse_weeks(&value()?)?),
+                "--presenter" => options.filter.presenters.push(value()?),
+                "--stream" => options.filter.streams.push(value()?),
+                "--group" => options.filter.groups.push(value()?),
+                "--location" => options.filter.locations.push(value()?),
+                "--enrollment" => options.enrollment = Some(value()?),
+                "--student" => options.student = Some(value()?),
+                "--roster" => options.roster = Some(value()?),
+                "--alias" => options.alias = Some(value()?),
+                "--offering" => options.offering = Some(value()?),
+                "--out-dir" => options.out_dir = Some(value()?),
+                "--file-name" => options.file_name = Some(value()?),
+                "--zip" => options.zip = Some(value()?),
+                "--state" => options.state = Some(value()?),
+                "--config" => options.config = Some(value()?),
+                "--listen" => options.listen = Some(value()?),
+                "--allow" => {
+                    options.lints.insert(value()?, Level::Allow);
+                }
+                "--warn" => {
+                    options.lints.insert(value()?, Level::Warn);
+                }
+                "--deny" => {
+                    options.lints.insert(value()?, Level::Deny);
+                }
+                "--code" => options.details.code = Some(value()?),
+                "--name" => options.details.name = Some(value()?),
+                "--link" => options.details.link = Some(value()?),
+                "--tag" => options.filter.tags.push(value()?),
+                "--exclude-tag" => options.filter.exclude_tags.push(value()?),
+                _ => bail!("Unknown option {}", flag),
+            }
+        }
+
+        match options.command {
+            Command::Generate | Command::Validate | Command::Format | Command::Show | Command::Today | Command::Next | Command::Agenda | Command::Stats | Command::Workload | Command::Equipment => {
+                if paths.is_empty() {
+                    bail!("Expects course as argument");
+                } else if paths.len() > 1 {
+                    bail!("Unexpected argument {}", paths[1]);
+                }
+            }
+            Command::Program => {
+                if paths.len() != 1 {
+                    bail!("Expects a single program manifest");
+                }
+            }
+            Command::Clashes => {
+                if paths.len() < 2 {
+                    bail!("Expects at least two courses to compare");
+                }
+            }
+            Command::Diff => {
+                if paths.len() != 2 {
+                    bail!("Expects an old and a new version of a course to compare");
+                }
+            }
+            Command::Publish => {
+                let name = paths.first().ok_or(format_err!("Expects a target to publish to, such as `publish caldav`, `publish google`, `publish graph`, or `publish todoist`"))?;
+                options.target = Some(Target::from_name(name).ok_or(format_err!("Unknown publishing target {}", name))?);
+                paths.remove(0);
+                if paths.len() != 1 {
+                    bail!("Expects a single course to publish");
+                }
+                if options.config.is_none() {
+                    bail!("Publishing requires --config");
+                }
+            }
+            Command::Notify => {
+                if paths.len() != 1 {
+                    bail!("Expects a single course to announce");
+                }
+                if options.config.is_none() {
+                    bail!("Notifying requires --config");
+                }
+            }
+            Command::Find => {
+                if paths.len() != 2 {
+                    bail!("Expects the text to search for and a course");
+                }
+                options.query = Some(paths.remove(0));
+            }
+            Command::Serve => {
+                if paths.len() != 1 {
+                    bail!("Expects a directory of courses to serve");
+                }
+            }
+            Command::Token => {
+                if !matches!(paths.first().map(String::as_str), Some("add") | Some("revoke")) {
+                    bail!("Expects `token add` or `token revoke`");
+                }
+                if paths.len() != 3 {
+                    bail!("Expects the access file and a feed name");
+                }
+                if options.details.code.is_none() {
+                    bail!("Expects the course of the feed with --code");
+                }
+            }
+            Command::Import => {
+                if paths.first().map(String::as_str) != Some("ics") {
+                    bail!("Expects the format to import from, such as `import ics`");
+                }
+                paths.remove(0);
+                if paths.len() != 1 {
+                    bail!("Expects a single calendar to import");
+                }
+            }
+            Command::Schema => {
+                if let Some(path) = paths.first() {
+                    bail!("Unexpected argument {}", path);
+                }
+            }
+        }
+        options.paths = paths;
+
+        if options.student.is_some() != options.enrollment.is_some() {
+            bail!("--student and --enrollment must be used together");
+        }
+
+        if options.format != Format::Ics && (options.incremental || options.roster.is_some() || options.target.is_some()) {
+            bail!("--format only applies when writing a single calendar");
+        }
+
+        if options.study_hours.is_some() && (options.format != Format::Ics || options.incremental || options.target.is_some()) {
+            bail!("--study-hours only applies when writing full calendars");
+        } else if options.week_summaries && (options.format != Format::Ics || options.incremental || options.target.is_some()) {
+            bail!("--week-summaries only applies when writing full calendars");
+        } else if options.travel && (options.format != Format::Ics || options.incremental || options.target.is_some()) {
+            bail!("--travel only applies when writing full calendars");
+        }
+
+        if let Some(split) = options.split {
+            if options.out_dir.is_none() && options.zip.is_none() {
+                bail!("{} requires --out-dir or --zip", split.flag());
+            } else if options.roster.is_some() || options.format != Format::Ics {
+                bail!("{} cannot be used with --roster or --format", split.flag());
+            }
+        }
+
+        if options.file_name.is_some() && options.out_dir.is_none() && options.zip.is_none() {
+            bail!("--file-name requires --out-dir or --zip");
+        }
+
+        if options.zip.is_some() {
+            if options.out_dir.is_some() {
+                bail!("--zip cannot be used with --out-dir");
+            } else if options.roster.is_none() && options.split.is_none() {
+                bail!("--zip only applies when writing several calendars with --roster or a split");
+            } else if options.watch {
+                bail!("--zip cannot be used with --watch");
+            }
+        }
+
+        if options.incremental && options.state.is_none() {
+            bail!("--incremental requires --state");
+        } else if options.incremental && options.roster.is_some() {
+            bail!("--incremental cannot be used with --roster");
+        }
+
+        Ok(options)
+    }
+
+    /// The path to the only course specification
+    pub fn path(&self) -> &str {
+        &self.paths[0]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &str) -> Result<Options, Error> {
+        Options::from_args(args.split_whitespace().map(str::to_owned))
+    }
+
+    #[test]
+    fn watch_writes_anywhere_but_archives() {
+        assert!(parse("course.toml --watch").is_ok());
+        assert!(parse("course.toml --watch --output course.ics").is_ok());
+        assert!(parse("course.toml --watch --per-room --out-dir rooms").is_ok());
+        let error = parse("course.toml --watch --per-room --zip rooms.zip").unwrap_err();
+        assert_eq!(error.to_string(), "--zip cannot be used with --watch");
+    }
+}
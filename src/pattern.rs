@@ -0,0 +1,290 @@
+//! A small regular expression engine for searching events
+//!
+//! Patterns support literals, `.`, character classes such as `[a-z]` and
+//! `[^0-9]`, the escapes `\d`, `\w`, and `\s`, the anchors `^` and `$`,
+//! groups, alternation, and the repetitions `*`, `+`, `?`, and `{m,n}`.
+
+use failure::{bail, format_err, Error};
+
+use std::iter::Peekable;
+use std::str::Chars;
+
+/// A compiled regular expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    root: Alternation,
+    case_insensitive: bool,
+}
+
+/// A choice between sequences of repeated nodes
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Alternation(Vec<Vec<(Node, Repeat)>>);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Char(char),
+    Any,
+    /// Inclusive ranges of characters, and whether the class is negated
+    Class(Vec<(char, char)>, bool),
+    Group(Alternation),
+    Start,
+    End,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Repeat {
+    min: usize,
+    max: Option<usize>,
+}
+
+const ONCE: Repeat = Repeat { min: 1, max: Some(1) };
+
+impl Pattern {
+    /// Compile a pattern, optionally ignoring the case of letters
+    pub fn new(source: &str, case_insensitive: bool) -> Result<Self, Error> {
+        let source = if case_insensitive { source.to_lowercase() } else { source.to_owned() };
+        let mut chars = source.chars().peekable();
+        let root = parse_alternation(&mut chars)?;
+        if let Some(c) = chars.next() {
+            bail!("Unexpected {} in pattern {}", c, source);
+        }
+        Ok(Pattern { root, case_insensitive })
+    }
+
+    /// Whether the pattern matches anywhere in some text
+    pub fn is_match(&self, text: &str) -> bool {
+        let text = if self.case_insensitive { text.to_lowercase() } else { text.to_owned() };
+        let chars = text.chars().collect::<Vec<_>>();
+        (0..=chars.len()).any(|start| match_alternation(&self.root, &chars, start, &|_| true))
+    }
+}
+
+/// Escape the characters with special meaning so that text matches literally
+pub fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if "\\.^$|()[]{}*+?".contains(c) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+fn parse_alternation(chars: &mut Peekable<Chars>) -> Result<Alternation, Error> {
+    let mut branches = vec![Vec::new()];
+    while let Some(&c) = chars.peek() {
+        match c {
+            ')' => break,
+            '|' => {
+                chars.next();
+                branches.push(Vec::new());
+            }
+            _ => {
+                let node = parse_node(chars)?;
+                let repeat = parse_repeat(chars)?;
+                branches.last_mut().expect("there is always a branch").push((node, repeat));
+            }
+        }
+    }
+    Ok(Alternation(branches))
+}
+
+fn parse_node(chars: &mut Peekable<Chars>) -> Result<Node, Error> {
+    Ok(match chars.next().ok_or(format_err!("Unexpected end of pattern"))? {
+        '.' => Node::Any,
+        '^' => Node::Start,
+        '$' => Node::End,
+        '(' => {
+            let group = parse_alternation(chars)?;
+            if chars.next() != Some(')') {
+                bail!("Unclosed group in pattern");
+            }
+            Node::Group(group)
+        }
+        '[' => {
+            let negated = chars.peek() == Some(&'^');
+            if negated {
+                chars.next();
+            }
+            let mut ranges = Vec::new();
+            let mut first = true;
+            loop {
+                let c = chars.next().ok_or(format_err!("Unclosed character class in pattern"))?;
+                match c {
+                    ']' if !first => break,
+                    '\\' => ranges.extend(parse_escape(chars)?),
+                    c if chars.peek() == Some(&'-') => {
+                        chars.next();
+                        match chars.next() {
+                            Some(']') => {
+                                ranges.extend([(c, c), ('-', '-')]);
+                                break;
+                            }
+                            Some(end) => ranges.push((c, end)),
+                            None => bail!("Unclosed character class in pattern"),
+                        }
+                    }
+                    c => ranges.push((c, c)),
+                }
+                first = false;
+            }
+            Node::Class(ranges, negated)
+        }
+        '\\' => match parse_escape(chars)?.as_slice() {
+            [(start, end)] if start == end => Node::Char(*start),
+            ranges => Node::Class(ranges.to_vec(), false),
+        },
+        c @ ('*' | '+' | '?') => bail!("Nothing to repeat before {} in pattern", c),
+        c => Node::Char(c),
+    })
+}
+
+/// The characters matched by an escape sequence
+fn parse_escape(chars: &mut Peekable<Chars>) -> Result<Vec<(char, char)>, Error> {
+    Ok(match chars.next().ok_or(format_err!("Unexpected end of pattern"))? {
+        'd' => vec![('0', '9')],
+        'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+        's' => vec![(' ', ' '), ('\t', '\t'), ('\n', '\n'), ('\r', '\r')],
+        c => vec![(c, c)],
+    })
+}
+
+fn parse_repeat(chars: &mut Peekable<Chars>) -> Result<Repeat, Error> {
+    let repeat = match chars.peek() {
+        Some('*') => Repeat { min: 0, max: None },
+        Some('+') => Repeat { min: 1, max: None },
+        Some('?') => Repeat { min: 0, max: Some(1) },
+        Some('{') => {
+            chars.next();
+            let mut bounds = String::new();
+            for c in chars.by_ref() {
+                if c == '}' {
+                    break;
+                }
+                bounds.push(c);
+            }
+            let number = |bound: &str| bound.trim().parse::<usize>().map_err(|_| format_err!("Invalid repetition {{{}}}", bounds));
+            return Ok(match bounds.split_once(',') {
+                Some((min, "")) => Repeat { min: number(min)?, max: None },
+                Some((min, max)) => Repeat { min: number(min)?, max: Some(number(max)?) },
+                None => Repeat { min: number(&bounds)?, max: Some(number(&bounds)?) },
+            });
+        }
+        _ => return Ok(ONCE),
+    };
+    chars.next();
+    Ok(repeat)
+}
+
+/// Match any branch starting at `position`, then whatever follows through `rest`
+fn match_alternation(alternation: &Alternation, text: &[char], position: usize, rest: &dyn Fn(usize) -> bool) -> bool {
+    alternation.0.iter().any(|sequence| match_sequence(sequence, text, position, rest))
+}
+
+fn match_sequence(sequence: &[(Node, Repeat)], text: &[char], position: usize, rest: &dyn Fn(usize) -> bool) -> bool {
+    match sequence.split_first() {
+        None => rest(position),
+        Some(((node, repeat), tail)) => {
+            match_repeat(node, *repeat, 0, text, position, &|next| match_sequence(tail, text, next, rest))
+        }
+    }
+}
+
+/// Match a node greedily, backtracking to fewer repetitions when the rest fails
+fn match_repeat(
+    node: &Node,
+    repeat: Repeat,
+    count: usize,
+    text: &[char],
+    position: usize,
+    rest: &dyn Fn(usize) -> bool,
+) -> bool {
+    if repeat.max.map(|max| count < max).unwrap_or(true) {
+        // Repetitions that consume nothing would never end
+        let more = match_node(node, text, position, &|next| {
+            (next != position || count < repeat.min) && match_repeat(node, repeat, count + 1, text, next, rest)
+        });
+        if more {
+            return true;
+        }
+    }
+    count >= repeat.min && rest(position)
+}
+
+fn match_node(node: &Node, text: &[char], position: usize, rest: &dyn Fn(usize) -> bool) -> bool {
+    match node {
+        Node::Start => position == 0 && rest(position),
+        Node::End => position == text.len() && rest(position),
+        Node::Group(alternation) => match_alternation(alternation, text, position, rest),
+        Node::Char(expected) => text.get(position) == Some(expected) && rest(position + 1),
+        Node::Any => position < text.len() && rest(position + 1),
+        Node::Class(ranges, negated) => match text.get(position) {
+            Some(c) => ranges.iter().any(|(start, end)| start <= c && c <= end) != *negated && rest(position + 1),
+            None => false,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(pattern: &str, text: &str) -> bool {
+        Pattern::new(pattern, false).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn literals_match_anywhere() {
+        assert!(matches("lab", "COMP1000 lab"));
+        assert!(!matches("lab", "COMP1000 lecture"));
+        assert!(matches("", "anything"));
+    }
+
+    #[test]
+    fn anchors_classes_and_escapes() {
+        assert!(matches("^COMP\\d{4}$", "COMP1000"));
+        assert!(!matches("^COMP\\d{4}$", "COMP10000"));
+        assert!(matches("[a-c]x", "bx"));
+        assert!(!matches("[^a-c]x", "bx"));
+        assert!(matches("a\\.b", "a.b"));
+        assert!(!matches("a\\.b", "axb"));
+        assert!(matches("[x-]$", "a-"));
+    }
+
+    #[test]
+    fn repetition_backtracks() {
+        assert!(matches("^a*ab$", "aaab"));
+        assert!(matches("^(ab)+c?$", "ababc"));
+        assert!(matches("^x{2,3}$", "xxx"));
+        assert!(!matches("^x{2,3}$", "xxxx"));
+        assert!(matches("^x{2,}$", "xxxxx"));
+    }
+
+    #[test]
+    fn alternation_within_groups() {
+        assert!(matches("^(lab|tut)orial$", "tutorial"));
+        assert!(!matches("^(lab|tut)orial$", "lecture"));
+        assert!(matches("week 1|week 2", "in week 2"));
+    }
+
+    #[test]
+    fn case_can_be_ignored() {
+        assert!(Pattern::new("LAB", true).unwrap().is_match("Lab 1"));
+        assert!(!Pattern::new("LAB", false).unwrap().is_match("Lab 1"));
+    }
+
+    #[test]
+    fn escaped_text_matches_literally() {
+        let text = "C++ (advanced) [1]?";
+        assert!(matches(&escape(text), text));
+        assert!(!matches(&escape("a.c"), "abc"));
+    }
+
+    #[test]
+    fn malformed_patterns_are_rejected() {
+        for pattern in &["(a", "a)", "[ab", "*a", "a{x}"] {
+            assert!(Pattern::new(pattern, false).is_err(), "accepted {}", pattern);
+        }
+    }
+}
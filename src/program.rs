@@ -0,0 +1,83 @@
+//! A degree program made up of several courses and key dates
+
+use chrono::NaiveDate;
+use failure::{format_err, Error};
+use serde::{de, Deserialize, Deserializer};
+
+use crate::course::Course;
+
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+/// A manifest listing the courses of a program
+#[derive(Debug, Clone, Deserialize)]
+pub struct Program {
+    name: String,
+    /// Paths to course specifications, relative to the manifest
+    courses: Vec<String>,
+    #[serde(rename = "date", default)]
+    dates: Vec<KeyDate>,
+    /// The directory against which course paths are resolved
+    #[serde(skip)]
+    base: PathBuf,
+}
+
+impl Program {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let mut program: Program = toml::from_str(&read_to_string(path)?)?;
+        program.base = path.parent().map(Path::to_owned).unwrap_or_default();
+        Ok(program)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Load every course in the program
+    pub fn courses(&self) -> Result<Vec<Course>, Error> {
+        self.courses
+            .iter()
+            .map(|course| Course::from_path(self.base.join(course)).map_err(|error| format_err!("{}: {}", course, error)))
+            .collect()
+    }
+
+    pub fn dates(&self) -> &[KeyDate] {
+        &self.dates
+    }
+}
+
+/// A date that matters to every course, such as census or the exam period
+#[derive(Debug, Clone, Deserialize)]
+pub struct KeyDate {
+    name: String,
+    #[serde(deserialize_with = "deserialize_date")]
+    date: NaiveDate,
+    /// The last day of a period of several days
+    #[serde(default, deserialize_with = "deserialize_optional_date")]
+    end: Option<NaiveDate>,
+}
+
+impl KeyDate {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn start(&self) -> NaiveDate {
+        self.date
+    }
+
+    /// The last day, which is the start unless an end is given
+    pub fn end(&self) -> NaiveDate {
+        self.end.unwrap_or(self.date)
+    }
+}
+
+fn deserialize_date<'de, D: Deserializer<'de>>(deserializer: D) -> Result<NaiveDate, D::Error> {
+    let date = toml::value::Datetime::deserialize(deserializer)?;
+    NaiveDate::parse_from_str(&date.to_string(), "%Y-%m-%d").map_err(|_| de::Error::custom(format!("Expected a date without a time but found {}", date)))
+}
+
+fn deserialize_optional_date<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<NaiveDate>, D::Error> {
+    deserialize_date(deserializer).map(Some)
+}
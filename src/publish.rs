@@ -0,0 +1,858 @@
+//! Publishing of generated events to remote calendars
+
+use chrono::{offset::FixedOffset, DateTime};
+use failure::{bail, format_err, Error};
+use serde::Deserialize;
+use url::Url;
+
+use crate::calendar::{self, Changes};
+use crate::course::{deserialize_url, Course};
+use crate::filter::Filter;
+use crate::http;
+use crate::json::Json;
+use crate::markdown;
+use crate::oauth::DeviceFlow;
+use crate::state::State;
+
+use std::env;
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// A service to which calendars can be published
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    CalDav,
+    Google,
+    Graph,
+    Todoist,
+}
+
+impl Target {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "caldav" => Some(Target::CalDav),
+            "google" => Some(Target::Google),
+            "graph" => Some(Target::Graph),
+            "todoist" => Some(Target::Todoist),
+            _ => None,
+        }
+    }
+
+    /// The name of the target, as given on the command line
+    pub fn name(self) -> &'static str {
+        match self {
+            Target::CalDav => "caldav",
+            Target::Google => "google",
+            Target::Graph => "graph",
+            Target::Todoist => "todoist",
+        }
+    }
+}
+
+/// Connection details for each publishing target
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Config {
+    caldav: Option<CalDav>,
+    google: Option<Google>,
+    graph: Option<Graph>,
+    todoist: Option<Todoist>,
+}
+
+impl Config {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(toml::from_str(&read_to_string(path)?)?)
+    }
+}
+
+/// A CalDAV calendar collection
+#[derive(Debug, Clone, Deserialize)]
+struct CalDav {
+    #[serde(deserialize_with = "deserialize_url")]
+    url: Url,
+    username: Option<String>,
+    password: Option<String>,
+    /// An environment variable holding the password, to keep it out of the file
+    password_env: Option<String>,
+}
+
+impl CalDav {
+    fn authorization(&self) -> Result<Option<String>, Error> {
+        let username = match &self.username {
+            Some(username) => username,
+            None => return Ok(None),
+        };
+        let password = secret(&self.password, &self.password_env)?
+            .ok_or(format_err!("No password configured for {}", username))?;
+        Ok(Some(http::basic_auth(username, &password)))
+    }
+
+    /// The resource holding the event with a particular UID
+    fn resource(&self, uid: &str) -> Result<Url, Error> {
+        let mut collection = self.url.clone();
+        if !collection.path().ends_with('/') {
+            collection.set_path(&format!("{}/", collection.path()));
+        }
+        Ok(collection.join(&format!("{}.ics", uid))?)
+    }
+}
+
+/// A Google calendar, accessed through the Calendar API
+#[derive(Debug, Clone, Deserialize)]
+struct Google {
+    /// The calendar ID, such as `primary`
+    calendar: String,
+    client_id: String,
+    client_secret: Option<String>,
+    client_secret_env: Option<String>,
+    /// A file in which to keep the refresh token between runs
+    token_cache: Option<String>,
+    #[serde(default = "Google::default_oauth_url", deserialize_with = "deserialize_url")]
+    oauth_url: Url,
+    #[serde(default = "Google::default_api_url", deserialize_with = "deserialize_url")]
+    api_url: Url,
+}
+
+impl Google {
+    fn default_oauth_url() -> Url {
+        Url::parse("https://oauth2.googleapis.com/").unwrap()
+    }
+
+    fn default_api_url() -> Url {
+        Url::parse("https://www.googleapis.com/calendar/v3/").unwrap()
+    }
+
+    /// A URL for the events of the calendar, followed by `path`
+    fn events_url(&self, path: &[&str]) -> Result<Url, Error> {
+        let mut url = self.api_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| format_err!("Invalid API URL {}", self.api_url))?
+            .pop_if_empty()
+            .extend(&["calendars", &self.calendar, "events"])
+            .extend(path);
+        Ok(url)
+    }
+}
+
+/// A Microsoft 365 calendar, accessed through Microsoft Graph
+#[derive(Debug, Clone, Deserialize)]
+struct Graph {
+    /// The directory tenant, or `organizations` for any work account
+    #[serde(default = "Graph::default_tenant")]
+    tenant: String,
+    client_id: String,
+    /// The user or shared mailbox that owns the calendar
+    mailbox: String,
+    /// The calendar ID, or the mailbox's default calendar if absent
+    calendar: Option<String>,
+    /// A file in which to keep the refresh token between runs
+    token_cache: Option<String>,
+    #[serde(default = "Graph::default_login_url", deserialize_with = "deserialize_url")]
+    login_url: Url,
+    #[serde(default = "Graph::default_api_url", deserialize_with = "deserialize_url")]
+    api_url: Url,
+}
+
+/// The extended property in which Graph events record their UID
+const GRAPH_UID_PROPERTY: &str = "String {0b7a9c4e-5f83-4d26-9a1e-3c6f2d8b4a71} Name CourseCalendarUid";
+
+impl Graph {
+    fn default_tenant() -> String {
+        "organizations".to_owned()
+    }
+
+    fn default_login_url() -> Url {
+        Url::parse("https://login.microsoftonline.com/").unwrap()
+    }
+
+    fn default_api_url() -> Url {
+        Url::parse("https://graph.microsoft.com/v1.0/").unwrap()
+    }
+
+    /// A URL for the events of the calendar, followed by `path`
+    fn events_url(&self, path: &[&str]) -> Result<Url, Error> {
+        let mut url = self.api_url.clone();
+        {
+            let mut segments = url.path_segments_mut().map_err(|_| format_err!("Invalid API URL {}", self.api_url))?;
+            segments.pop_if_empty().extend(&["users", &self.mailbox]);
+            match &self.calendar {
+                Some(calendar) => segments.extend(&["calendars", calendar]),
+                None => segments.push("calendar"),
+            };
+            segments.push("events").extend(path);
+        }
+        Ok(url)
+    }
+
+    /// The Graph ID of the event with a particular UID
+    fn find(&self, uid: &str, token: &str) -> Result<Option<String>, Error> {
+        let mut url = self.events_url(&[])?;
+        let filter = format!(
+            "singleValueExtendedProperties/Any(ep: ep/id eq '{}' and ep/value eq '{}')",
+            GRAPH_UID_PROPERTY, uid,
+        );
+        url.query_pairs_mut().append_pair("$filter", &filter).append_pair("$select", "id");
+        let found = send_json("GET", &url, token, None)?;
+        Ok(found
+            .get("value")
+            .and_then(Json::as_array)
+            .and_then(|items| items.first())
+            .and_then(|item| item.get("id"))
+            .and_then(Json::as_str)
+            .map(str::to_owned))
+    }
+}
+
+/// A Todoist project, accessed through the REST API with a personal token
+#[derive(Debug, Clone, Deserialize)]
+struct Todoist {
+    token: Option<String>,
+    token_env: Option<String>,
+    /// The project for the tasks, or the inbox if absent
+    project_id: Option<String>,
+    #[serde(default = "Todoist::default_api_url", deserialize_with = "deserialize_url")]
+    api_url: Url,
+}
+
+impl Todoist {
+    fn default_api_url() -> Url {
+        Url::parse("https://api.todoist.com/rest/v2/").unwrap()
+    }
+
+    /// A URL for the tasks of the account, followed by `path`
+    fn tasks_url(&self, path: &[&str]) -> Result<Url, Error> {
+        let mut url = self.api_url.clone();
+        url.path_segments_mut()
+            .map_err(|_| format_err!("Invalid API URL {}", self.api_url))?
+            .pop_if_empty()
+            .push("tasks")
+            .extend(path);
+        Ok(url)
+    }
+
+    /// The IDs of existing tasks, keyed by the UID recorded in their descriptions
+    fn existing(&self, token: &str) -> Result<Vec<(String, String)>, Error> {
+        let mut url = self.tasks_url(&[])?;
+        if let Some(project) = &self.project_id {
+            url.query_pairs_mut().append_pair("project_id", project);
+        }
+        let tasks = send_json("GET", &url, token, None)?;
+        Ok(tasks
+            .as_array()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|task| {
+                let id = task.get("id")?.as_str()?;
+                let description = task.get("description")?.as_str()?;
+                let uid = description.lines().last()?.strip_prefix(TODOIST_UID_PREFIX)?;
+                Some((uid.to_owned(), id.to_owned()))
+            })
+            .collect())
+    }
+}
+
+/// Todoist has no field for external IDs, so the last line of each task's
+/// description records the UID of its event
+const TODOIST_UID_PREFIX: &str = "course-calendar: ";
+
+/// The number of events sent to and removed from a remote calendar
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Summary {
+    pub published: usize,
+    pub deleted: usize,
+}
+
+/// Publish the changed events in a course, deleting removed events
+pub fn publish(
+    target: Target,
+    config: &Config,
+    course: &Course,
+    filter: &Filter,
+    state: &State,
+    changes: &Changes,
+) -> Result<Summary, Error> {
+    match target {
+        Target::CalDav => {
+            let caldav = config.caldav.as_ref().ok_or(format_err!("No [caldav] section in the configuration"))?;
+            publish_caldav(caldav, course, filter, state, changes)
+        }
+        Target::Google => {
+            let google = config.google.as_ref().ok_or(format_err!("No [google] section in the configuration"))?;
+            publish_google(google, course, filter, state, changes)
+        }
+        Target::Graph => {
+            let graph = config.graph.as_ref().ok_or(format_err!("No [graph] section in the configuration"))?;
+            publish_graph(graph, course, filter, changes)
+        }
+        Target::Todoist => {
+            let todoist = config.todoist.as_ref().ok_or(format_err!("No [todoist] section in the configuration"))?;
+            publish_todoist(todoist, course, filter, changes)
+        }
+    }
+}
+
+/// Store each event as its own resource in a CalDAV collection
+fn publish_caldav(
+    caldav: &CalDav,
+    course: &Course,
+    filter: &Filter,
+    state: &State,
+    changes: &Changes,
+) -> Result<Summary, Error> {
+    let mut headers = Vec::new();
+    if let Some(authorization) = caldav.authorization()? {
+        headers.push(("Authorization", authorization));
+    }
+    let mut summary = Summary::default();
+
+    for (uid, event) in calendar::events(course, filter, state) {
+        if !changes.changed.contains(&uid) {
+            continue;
+        }
+        let mut body = Vec::new();
+        calendar::generate_single(course, course.name().to_owned(), event).write(&mut body)?;
+
+        let mut headers = headers.clone();
+        headers.push(("Content-Type", "text/calendar; charset=utf-8".to_owned()));
+        let url = caldav.resource(&uid)?;
+        let response = http::request("PUT", &url, &headers, Some(&String::from_utf8(body)?))?;
+        if !response.is_success() {
+            bail!("Publishing {} failed with status {}", url, response.status);
+        }
+        summary.published += 1;
+    }
+
+    for (uid, _) in &changes.removed {
+        let url = caldav.resource(uid)?;
+        let response = http::request("DELETE", &url, &headers, None)?;
+        if !response.is_success() && response.status != 404 {
+            bail!("Deleting {} failed with status {}", url, response.status);
+        }
+        summary.deleted += 1;
+    }
+
+    Ok(summary)
+}
+
+/// Import each event into a Google calendar, which updates events by UID
+fn publish_google(
+    google: &Google,
+    course: &Course,
+    filter: &Filter,
+    state: &State,
+    changes: &Changes,
+) -> Result<Summary, Error> {
+    let client_secret = secret(&google.client_secret, &google.client_secret_env)?;
+    let flow = DeviceFlow {
+        device_url: google.oauth_url.join("device/code")?,
+        token_url: google.oauth_url.join("token")?,
+        client_id: &google.client_id,
+        client_secret: client_secret.as_deref(),
+        scope: "https://www.googleapis.com/auth/calendar",
+    };
+    let token = flow.access_token(google.token_cache.as_ref().map(Path::new))?;
+    let mut summary = Summary::default();
+
+    for (uid, event) in calendar::identified(course) {
+        if !filter.matches(&event) || !changes.changed.contains(&uid) {
+            continue;
+        }
+        let time = |time: DateTime<FixedOffset>| Json::object(vec![("dateTime", time.to_rfc3339().into())]);
+        let body = Json::object(vec![
+            ("iCalUID", uid.as_str().into()),
+            ("summary", format!("{} {}", course.code(), event.title()).into()),
+            ("start", time(event.start())),
+            ("end", time(event.end())),
+            ("location", event.location().into()),
+            ("description", event.description().map(|description| markdown::to_plain(&description)).into()),
+            ("sequence", i64::from(state.revision(&uid).map(|revision| revision.sequence).unwrap_or(0)).into()),
+        ]);
+        send_json("POST", &google.events_url(&["import"])?, &token, Some(&body))?;
+        summary.published += 1;
+    }
+
+    for (uid, _) in &changes.removed {
+        let mut url = google.events_url(&[])?;
+        url.query_pairs_mut().append_pair("iCalUID", uid);
+        let found = send_json("GET", &url, &token, None)?;
+        for item in found.get("items").and_then(Json::as_array).unwrap_or_default() {
+            if let Some(id) = item.get("id").and_then(Json::as_str) {
+                send_json("DELETE", &google.events_url(&[id])?, &token, None)?;
+                summary.deleted += 1;
+            }
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Create or update each event in an Outlook calendar, found by its UID
+fn publish_graph(graph: &Graph, course: &Course, filter: &Filter, changes: &Changes) -> Result<Summary, Error> {
+    let login = graph.login_url.join(&format!("{}/oauth2/v2.0/", graph.tenant))?;
+    let flow = DeviceFlow {
+        device_url: login.join("devicecode")?,
+        token_url: login.join("token")?,
+        client_id: &graph.client_id,
+        client_secret: None,
+        scope: "https://graph.microsoft.com/Calendars.ReadWrite.Shared offline_access",
+    };
+    let token = flow.access_token(graph.token_cache.as_ref().map(Path::new))?;
+    let mut summary = Summary::default();
+
+    for (uid, event) in calendar::identified(course) {
+        if !filter.matches(&event) || !changes.changed.contains(&uid) {
+            continue;
+        }
+        let time = |time: DateTime<FixedOffset>| {
+            Json::object(vec![
+                ("dateTime", time.naive_utc().format("%Y-%m-%dT%H:%M:%S").to_string().into()),
+                ("timeZone", "UTC".into()),
+            ])
+        };
+        let mut body = vec![
+            ("subject", format!("{} {}", course.code(), event.title()).into()),
+            ("start", time(event.start())),
+            ("end", time(event.end())),
+            (
+                "singleValueExtendedProperties",
+                Json::Array(vec![Json::object(vec![("id", GRAPH_UID_PROPERTY.into()), ("value", uid.as_str().into())])]),
+            ),
+        ];
+        if let Some(location) = event.location() {
+            body.push(("location", Json::object(vec![("displayName", location.into())])));
+        }
+        if let Some(description) = event.description() {
+            let content = markdown::to_html(&description);
+            body.push(("body", Json::object(vec![("contentType", "HTML".into()), ("content", content.into())])));
+        }
+
+        let body = Json::object(body);
+        match graph.find(&uid, &token)? {
+            Some(id) => send_json("PATCH", &graph.events_url(&[&id])?, &token, Some(&body))?,
+            None => send_json("POST", &graph.events_url(&[])?, &token, Some(&body))?,
+        };
+        summary.published += 1;
+    }
+
+    for (uid, _) in &changes.removed {
+        if let Some(id) = graph.find(uid, &token)? {
+            send_json("DELETE", &graph.events_url(&[&id])?, &token, None)?;
+            summary.deleted += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Create or update a task for each submission, found by the UID in its description
+fn publish_todoist(todoist: &Todoist, course: &Course, filter: &Filter, changes: &Changes) -> Result<Summary, Error> {
+    let token = secret(&todoist.token, &todoist.token_env)?.ok_or(format_err!("No token configured for Todoist"))?;
+    let existing = todoist.existing(&token)?;
+    let find = |uid: &str| existing.iter().find(|(task, _)| task == uid).map(|(_, id)| id.as_str());
+    let mut summary = Summary::default();
+
+    for (uid, event) in calendar::identified(course) {
+        if event.kind() != "submission" || !filter.matches(&event) || !changes.changed.contains(&uid) {
+            continue;
+        }
+        let mut description = String::new();
+        if let Some(link) = event.link() {
+            description.push_str(&format!("{}\n\n", link));
+        }
+        description.push_str(&format!("{}{}", TODOIST_UID_PREFIX, uid));
+
+        let mut body = vec![
+            ("content", format!("{} {}", course.code(), event.name()).into()),
+            ("description", description.into()),
+            ("due_datetime", event.start().to_rfc3339().into()),
+            ("labels", vec![course.code()].into()),
+        ];
+        match find(&uid) {
+            Some(id) => {
+                send_json("POST", &todoist.tasks_url(&[id])?, &token, Some(&Json::object(body)))?;
+            }
+            None => {
+                if let Some(project) = &todoist.project_id {
+                    body.push(("project_id", project.as_str().into()));
+                }
+                send_json("POST", &todoist.tasks_url(&[])?, &token, Some(&Json::object(body)))?;
+            }
+        }
+        summary.published += 1;
+    }
+
+    for (uid, _) in &changes.removed {
+        if let Some(id) = find(uid) {
+            send_json("DELETE", &todoist.tasks_url(&[id])?, &token, None)?;
+            summary.deleted += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+/// Send a JSON request with a bearer token, returning the decoded response
+fn send_json(method: &str, url: &Url, token: &str, body: Option<&Json>) -> Result<Json, Error> {
+    let mut headers = vec![("Authorization", format!("Bearer {}", token))];
+    if body.is_some() {
+        headers.push(("Content-Type", "application/json".to_owned()));
+    }
+    let response = http::request(method, url, &headers, body.map(Json::to_string).as_deref())?;
+    if !response.is_success() {
+        bail!("{} {} failed with status {}: {}", method, url, response.status, response.body.trim());
+    }
+    if response.body.trim().is_empty() {
+        Ok(Json::Null)
+    } else {
+        Json::parse(&response.body)
+    }
+}
+
+/// A secret given directly or through an environment variable
+pub fn secret(value: &Option<String>, variable: &Option<String>) -> Result<Option<String>, Error> {
+    match (value, variable) {
+        (Some(value), _) => Ok(Some(value.clone())),
+        (None, Some(variable)) => Ok(Some(env::var(variable).map_err(|_| format_err!("{} is not set", variable))?)),
+        (None, None) => Ok(None),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::calendar::update_state;
+
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    /// A request received by a remote service
+    #[derive(Debug, Clone)]
+    struct Received {
+        method: String,
+        target: String,
+        authorization: Option<String>,
+        body: String,
+    }
+
+    type Log = Arc<Mutex<Vec<Received>>>;
+
+    /// Run a remote service that answers every request through `respond`
+    fn remote<F>(respond: F) -> (Url, Log)
+    where
+        F: Fn(&Received) -> (u16, String) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = Url::parse(&format!("http://{}/", listener.local_addr().unwrap())).unwrap();
+        let log = Log::default();
+        let received = log.clone();
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut words = line.split(' ');
+                let (method, target) = (words.next().unwrap().to_owned(), words.next().unwrap().to_owned());
+                let (mut length, mut authorization) = (0, None);
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    match header.trim_end().split_once(": ") {
+                        Some(("Content-Length", value)) => length = value.parse().unwrap(),
+                        Some(("Authorization", value)) => authorization = Some(value.to_owned()),
+                        Some(_) => {}
+                        None => break,
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let request = Received { method, target, authorization, body: String::from_utf8(body).unwrap() };
+                let (status, body) = respond(&request);
+                received.lock().unwrap().push(request);
+                write!(stream, "HTTP/1.0 {} Status\r\n\r\n{}", status, body).unwrap();
+            }
+        });
+        (base, log)
+    }
+
+    /// A course with a lecture in each of `weeks` and a submission in the first
+    fn course(weeks: &[usize]) -> Course {
+        let weeks = weeks.iter().map(usize::to_string).collect::<Vec<_>>().join(", ");
+        let spec = format!(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/"
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[week]]
+            start = 2024-02-19T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-12T10:00:00+11:00
+            kind = "lecture"
+            location = "Quad 1001"
+            duration = 3600
+            weeks = [{}]
+
+            [[assignment]]
+            name = "Assignment"
+            link = "https://example.edu/assignment"
+
+            [[assignment.submission]]
+            time = 2024-02-16T17:00:00+11:00
+            name = "Final"
+            "#,
+            weeks
+        );
+        let mut course = Course::from_value(toml::from_str(&spec).unwrap()).unwrap();
+        course.generate_repeats().unwrap();
+        course
+    }
+
+    /// A course that has since dropped its second lecture, with every remaining event still to be sent
+    fn lecture_dropped() -> (Course, State, Changes) {
+        let mut state = State::default();
+        update_state(&course(&[1, 2]), &Filter::default(), &mut state);
+        let course = course(&[1]);
+        let changes = update_state(&course, &Filter::default(), &mut state);
+        let changes = Changes { changed: calendar::identified(&course).into_iter().map(|(uid, _)| uid).collect(), ..changes };
+        (course, state, changes)
+    }
+
+    fn uids(course: &Course) -> Vec<String> {
+        calendar::identified(course).into_iter().map(|(uid, _)| uid).collect()
+    }
+
+    #[test]
+    fn targets_are_named() {
+        for target in &[Target::CalDav, Target::Google, Target::Graph, Target::Todoist] {
+            assert_eq!(Target::from_name(target.name()), Some(*target));
+        }
+        assert_eq!(Target::from_name("outlook"), None);
+    }
+
+    #[test]
+    fn secrets_come_from_the_value_before_the_environment() {
+        env::set_var("COURSE_CALENDAR_TEST_SECRET", "from the environment");
+        let variable = Some("COURSE_CALENDAR_TEST_SECRET".to_owned());
+        assert_eq!(secret(&Some("given".to_owned()), &variable).unwrap().as_deref(), Some("given"));
+        assert_eq!(secret(&None, &variable).unwrap().as_deref(), Some("from the environment"));
+        assert_eq!(secret(&None, &None).unwrap(), None);
+        let missing = secret(&None, &Some("COURSE_CALENDAR_TEST_UNSET".to_owned())).unwrap_err();
+        assert_eq!(missing.to_string(), "COURSE_CALENDAR_TEST_UNSET is not set");
+    }
+
+    #[test]
+    fn caldav_resources_are_inside_the_collection() {
+        let caldav: CalDav = toml::from_str(r#"url = "https://dav.example.edu/calendars/course""#).unwrap();
+        assert_eq!(caldav.resource("a-1@course-calendar").unwrap().as_str(), "https://dav.example.edu/calendars/course/a-1@course-calendar.ics");
+        assert_eq!(caldav.authorization().unwrap(), None);
+
+        let caldav: CalDav = toml::from_str("url = \"https://dav.example.edu/\"\nusername = \"tutor\"").unwrap();
+        assert_eq!(caldav.authorization().unwrap_err().to_string(), "No password configured for tutor");
+    }
+
+    #[test]
+    fn caldav_events_are_stored_and_removed() {
+        let (course, state, changes) = lecture_dropped();
+        let (base, log) = remote(|request| (if request.method == "DELETE" { 404 } else { 201 }, String::new()));
+        let config: Config = toml::from_str(&format!("[caldav]\nurl = \"{}course\"\nusername = \"tutor\"\npassword = \"pass\"", base)).unwrap();
+
+        let summary = publish(Target::CalDav, &config, &course, &Filter::default(), &state, &changes).unwrap();
+        assert_eq!((summary.published, summary.deleted), (2, 1));
+
+        let log = log.lock().unwrap();
+        let (removed, _) = &changes.removed[0];
+        let targets = log.iter().map(|request| format!("{} {}", request.method, request.target)).collect::<Vec<_>>();
+        let mut expected = uids(&course).iter().map(|uid| format!("PUT /course/{}.ics", uid)).collect::<Vec<_>>();
+        expected.push(format!("DELETE /course/{}.ics", removed));
+        assert_eq!(targets, expected);
+        assert!(log.iter().all(|request| request.authorization.as_deref() == Some(http::basic_auth("tutor", "pass").as_str())));
+        assert!(log[0].body.starts_with("BEGIN:VCALENDAR"));
+        assert!(log[0].body.contains(&format!("UID:{}", uids(&course)[0])));
+    }
+
+    #[test]
+    fn caldav_failures_are_reported() {
+        let (course, state, changes) = lecture_dropped();
+        let (base, _) = remote(|_| (403, String::new()));
+        let config: Config = toml::from_str(&format!("[caldav]\nurl = \"{}course/\"", base)).unwrap();
+
+        let error = publish(Target::CalDav, &config, &course, &Filter::default(), &state, &changes).unwrap_err();
+        assert_eq!(error.to_string(), format!("Publishing {}course/{}.ics failed with status 403", base, uids(&course)[0]));
+        let error = publish(Target::CalDav, &Config::default(), &course, &Filter::default(), &state, &changes).unwrap_err();
+        assert_eq!(error.to_string(), "No [caldav] section in the configuration");
+    }
+
+    /// A file holding a refresh token, so that publishing needs no approval
+    fn token_cache(name: &str) -> String {
+        let path = env::temp_dir().join(format!("course-calendar-publish-{}-{}", name, std::process::id()));
+        std::fs::write(&path, "refresh").unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    /// Answer token requests with an access token, and other requests through `respond`
+    fn authorised<F>(respond: F) -> (Url, Log)
+    where
+        F: Fn(&Received) -> (u16, String) + Send + 'static,
+    {
+        remote(move |request| match request.target.ends_with("/token") {
+            true => (200, r#"{"access_token": "access"}"#.to_owned()),
+            false => respond(request),
+        })
+    }
+
+    #[test]
+    fn google_calendar_ids_are_escaped() {
+        let google: Google = toml::from_str("calendar = \"course#1@group.calendar.google.com\"\nclient_id = \"calendar\"").unwrap();
+        assert_eq!(
+            google.events_url(&["import"]).unwrap().as_str(),
+            "https://www.googleapis.com/calendar/v3/calendars/course%231@group.calendar.google.com/events/import"
+        );
+    }
+
+    #[test]
+    fn google_events_are_imported_and_deleted_by_uid() {
+        let (course, state, changes) = lecture_dropped();
+        let (base, log) = authorised(|request| match request.method.as_str() {
+            "GET" => (200, r#"{"items": [{"id": "google1"}]}"#.to_owned()),
+            _ => (200, String::new()),
+        });
+        let cache = token_cache("google");
+        let config: Config = toml::from_str(&format!(
+            "[google]\ncalendar = \"primary\"\nclient_id = \"calendar\"\ntoken_cache = '{}'\noauth_url = \"{}\"\napi_url = \"{}v3/\"",
+            cache, base, base
+        ))
+        .unwrap();
+
+        let summary = publish(Target::Google, &config, &course, &Filter::default(), &state, &changes).unwrap();
+        assert_eq!((summary.published, summary.deleted), (2, 1));
+
+        let log = log.lock().unwrap();
+        let (removed, _) = &changes.removed[0];
+        let targets = log.iter().map(|request| format!("{} {}", request.method, request.target)).collect::<Vec<_>>();
+        assert_eq!(
+            targets,
+            [
+                "POST /token".to_owned(),
+                "POST /v3/calendars/primary/events/import".to_owned(),
+                "POST /v3/calendars/primary/events/import".to_owned(),
+                format!("GET /v3/calendars/primary/events?iCalUID={}", removed.replace('@', "%40")),
+                "DELETE /v3/calendars/primary/events/google1".to_owned(),
+            ]
+        );
+        assert!(log[1..].iter().all(|request| request.authorization.as_deref() == Some("Bearer access")));
+
+        let import = Json::parse(&log[1].body).unwrap();
+        assert_eq!(import.get("iCalUID").and_then(Json::as_str), Some(uids(&course)[0].as_str()));
+        assert_eq!(import.get("location").and_then(Json::as_str), Some("Quad 1001"));
+        assert_eq!(import.get("sequence").and_then(Json::as_f64), Some(0.0));
+        let start = import.get("start").and_then(|start| start.get("dateTime")).and_then(Json::as_str);
+        assert_eq!(start, Some("2024-02-12T10:00:00+11:00"));
+        std::fs::remove_file(&cache).unwrap();
+    }
+
+    #[test]
+    fn graph_events_belong_to_the_mailbox_calendar() {
+        let graph: Graph = toml::from_str("client_id = \"calendar\"\nmailbox = \"course@example.edu\"").unwrap();
+        assert_eq!(graph.events_url(&[]).unwrap().as_str(), "https://graph.microsoft.com/v1.0/users/course@example.edu/calendar/events");
+
+        let graph = Graph { calendar: Some("AAMk=".to_owned()), ..graph };
+        assert_eq!(
+            graph.events_url(&["1"]).unwrap().as_str(),
+            "https://graph.microsoft.com/v1.0/users/course@example.edu/calendars/AAMk=/events/1"
+        );
+    }
+
+    #[test]
+    fn graph_events_are_updated_when_found_by_uid() {
+        let (course, state, changes) = lecture_dropped();
+        // Only lectures exist in the calendar already
+        let (base, log) = authorised(|request| match (request.method.as_str(), request.target.contains("lecture")) {
+            ("GET", true) => (200, r#"{"value": [{"id": "graph1"}]}"#.to_owned()),
+            ("GET", false) => (200, r#"{"value": []}"#.to_owned()),
+            _ => (200, "{}".to_owned()),
+        });
+        let cache = token_cache("graph");
+        let config: Config = toml::from_str(&format!(
+            "[graph]\nclient_id = \"calendar\"\nmailbox = \"course@example.edu\"\ntoken_cache = '{}'\nlogin_url = \"{}\"\napi_url = \"{}v1.0/\"",
+            cache, base, base
+        ))
+        .unwrap();
+
+        let summary = publish(Target::Graph, &config, &course, &Filter::default(), &state, &changes).unwrap();
+        assert_eq!((summary.published, summary.deleted), (2, 1));
+
+        let log = log.lock().unwrap();
+        assert_eq!(log[0].target, "/organizations/oauth2/v2.0/token");
+        let sent = log.iter().filter(|request| request.method != "GET").map(|request| format!("{} {}", request.method, request.target)).collect::<Vec<_>>();
+        assert_eq!(
+            sent[1..],
+            [
+                "PATCH /v1.0/users/course@example.edu/calendar/events/graph1",
+                "POST /v1.0/users/course@example.edu/calendar/events",
+                "DELETE /v1.0/users/course@example.edu/calendar/events/graph1",
+            ]
+        );
+
+        let lecture = Json::parse(&log.iter().find(|request| request.method == "PATCH").unwrap().body).unwrap();
+        let start = lecture.get("start").unwrap();
+        assert_eq!(start.get("dateTime").and_then(Json::as_str), Some("2024-02-11T23:00:00"));
+        assert_eq!(start.get("timeZone").and_then(Json::as_str), Some("UTC"));
+        let location = lecture.get("location").and_then(|location| location.get("displayName"));
+        assert_eq!(location.and_then(Json::as_str), Some("Quad 1001"));
+        let property = &lecture.get("singleValueExtendedProperties").and_then(Json::as_array).unwrap()[0];
+        assert_eq!(property.get("value").and_then(Json::as_str), Some(uids(&course)[0].as_str()));
+        std::fs::remove_file(&cache).unwrap();
+    }
+
+    /// Publish a course to a Todoist project in which `existing` tasks already exist
+    fn todoist(existing: String) -> (Summary, Vec<Received>, String) {
+        let (course, state, changes) = lecture_dropped();
+        let submission = uids(&course).into_iter().find(|uid| uid.contains("submission")).unwrap();
+        let (base, log) = remote(move |request| match request.method.as_str() {
+            "GET" => (200, existing.clone()),
+            _ => (200, "{}".to_owned()),
+        });
+        let config: Config = toml::from_str(&format!("[todoist]\ntoken = \"personal\"\nproject_id = \"42\"\napi_url = \"{}\"", base)).unwrap();
+
+        let summary = publish(Target::Todoist, &config, &course, &Filter::default(), &state, &changes).unwrap();
+        let log = log.lock().unwrap().clone();
+        (summary, log, submission)
+    }
+
+    #[test]
+    fn todoist_tasks_are_created_for_submissions() {
+        let (summary, log, submission) = todoist("[]".to_owned());
+        assert_eq!((summary.published, summary.deleted), (1, 0));
+        assert_eq!(log.iter().map(|request| request.target.as_str()).collect::<Vec<_>>(), ["/tasks?project_id=42", "/tasks"]);
+        assert!(log.iter().all(|request| request.authorization.as_deref() == Some("Bearer personal")));
+
+        let task = Json::parse(&log[1].body).unwrap();
+        assert_eq!(task.get("content").and_then(Json::as_str), Some("COMP1000 Assignment: Final"));
+        assert_eq!(task.get("project_id").and_then(Json::as_str), Some("42"));
+        assert_eq!(task.get("due_datetime").and_then(Json::as_str), Some("2024-02-16T17:00:00+11:00"));
+        let description = format!("https://example.edu/assignment\n\ncourse-calendar: {}", submission);
+        assert_eq!(task.get("description").and_then(Json::as_str), Some(description.as_str()));
+    }
+
+    #[test]
+    fn todoist_tasks_are_found_by_the_uid_in_their_description() {
+        let submission = uids(&course(&[1])).into_iter().find(|uid| uid.contains("submission")).unwrap();
+        let existing = format!(
+            r#"[{{"id": "7", "description": "Notes\ncourse-calendar: {}"}}, {{"id": "8", "description": "Unrelated"}}]"#,
+            submission
+        );
+        let (summary, log, _) = todoist(existing);
+        assert_eq!((summary.published, summary.deleted), (1, 0));
+        assert_eq!(log[1].method, "POST");
+        assert_eq!(log[1].target, "/tasks/7");
+        assert_eq!(Json::parse(&log[1].body).unwrap().get("project_id"), None);
+    }
+}
@@ -0,0 +1,132 @@
+//! Class rosters listing each student's streams
+
+use failure::{bail, format_err, Error};
+
+use std::fs::read_to_string;
+use std::path::Path;
+
+/// A student and their chosen streams from a roster
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub id: String,
+    pub name: Option<String>,
+    pub streams: Vec<String>,
+}
+
+/// Read a CSV roster with `id`, `name`, and `streams` columns
+///
+/// Multiple streams within the `streams` column are separated by spaces or
+/// semicolons. Only the `id` column is required.
+pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Vec<Entry>, Error> {
+    let contents = read_to_string(path)?;
+    let mut rows = contents.lines().filter(|line| !line.trim().is_empty()).map(parse_row);
+
+    let header = rows.next().ok_or(format_err!("Roster is empty"))??;
+    let column = |name: &str| header.iter().position(|field| field.trim().eq_ignore_ascii_case(name));
+    let id = column("id").ok_or(format_err!("Roster has no id column"))?;
+    let name = column("name");
+    let streams = column("streams");
+
+    let mut entries = Vec::new();
+    for (line, row) in rows.enumerate() {
+        let row = row?;
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|index| row.get(index))
+                .map(|field| field.trim())
+                .filter(|field| !field.is_empty())
+        };
+        let id = match field(Some(id)) {
+            Some(id) => id.to_owned(),
+            None => bail!("Roster row {} has no id", line + 2),
+        };
+
+        entries.push(Entry {
+            id,
+            name: field(name).map(str::to_owned),
+            streams: field(streams)
+                .map(|streams| {
+                    streams
+                        .split(|c: char| c == ';' || c.is_whitespace())
+                        .filter(|stream| !stream.is_empty())
+                        .map(str::to_owned)
+                        .collect()
+                })
+                .unwrap_or_default(),
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Split a CSV row into fields, handling double-quoted fields
+fn parse_row(line: &str) -> Result<Vec<String>, Error> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, quoted) {
+            ('"', true) if chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            ('"', true) => quoted = false,
+            ('"', false) if field.trim().is_empty() => {
+                field.clear();
+                quoted = true;
+            }
+            (',', false) => fields.push(std::mem::take(&mut field)),
+            (c, _) => field.push(c),
+        }
+    }
+
+    if quoted {
+        bail!("Unterminated quoted field in roster row: {}", line);
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::{remove_file, write};
+
+    /// Read a roster written to a temporary file
+    fn read(name: &str, contents: &str) -> Result<Vec<Entry>, Error> {
+        let path = std::env::temp_dir().join(format!("course-calendar-roster-{}-{}.csv", name, std::process::id()));
+        write(&path, contents).unwrap();
+        let roster = from_path(&path);
+        remove_file(&path).unwrap();
+        roster
+    }
+
+    #[test]
+    fn quoted_fields_may_hold_commas_and_quotes() {
+        assert_eq!(parse_row("z1,\"Lovelace, Ada\",T10A").unwrap(), ["z1", "Lovelace, Ada", "T10A"]);
+        assert_eq!(parse_row("z2, \"Grace \"\"Amazing\"\" Hopper\",").unwrap(), ["z2", "Grace \"Amazing\" Hopper", ""]);
+        assert_eq!(parse_row("z3,\"open").unwrap_err().to_string(), "Unterminated quoted field in roster row: z3,\"open");
+    }
+
+    #[test]
+    fn columns_are_found_by_name() {
+        let roster = read("columns", "Streams,ID,Name\nT10A; L1 ,z1,\"Lovelace, Ada\"\n\n,z2,\n").unwrap();
+        assert_eq!(
+            roster,
+            [
+                Entry { id: "z1".to_owned(), name: Some("Lovelace, Ada".to_owned()), streams: vec!["T10A".to_owned(), "L1".to_owned()] },
+                Entry { id: "z2".to_owned(), name: None, streams: Vec::new() },
+            ]
+        );
+    }
+
+    #[test]
+    fn every_row_needs_an_id() {
+        assert_eq!(read("empty", "").unwrap_err().to_string(), "Roster is empty");
+        assert_eq!(read("header", "name,streams\nAda,T10A\n").unwrap_err().to_string(), "Roster has no id column");
+        assert_eq!(read("id", "id,name\nz1,Ada\n ,Grace\n").unwrap_err().to_string(), "Roster row 3 has no id");
+    }
+}
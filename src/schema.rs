@@ -0,0 +1,671 @@
+//! A JSON Schema describing the course specification format
+//!
+//! This must be kept in step with the structures in `course`.
+
+use crate::json::Json;
+
+/// Generate the JSON Schema for a course specification
+pub fn course() -> Json {
+    let mut schema = object(
+        "A course and all of its events",
+        vec![
+            ("version", integer("The version of the specification layout, assumed to be 1 if absent")),
+            ("extends", string("A course specification whose fields this one overrides, relative to this one or a URL")),
+            ("include", array("Files whose fields are added to this specification", string("A path relative to the specification, or a URL"))),
+            ("code", string("The course code used to prefix event titles")),
+            ("aliases", array("Other codes under which the course is offered", string("A course code, selected with --alias"))),
+            ("name", string("The name of the course")),
+            ("link", uri("The course homepage")),
+            ("merge", array("Calendars whose events are merged into the output", string("A path relative to the specification, or a URL"))),
+            ("assessment_total", integer("The total that assignment values should add up to, 100 if absent")),
+            ("travel_time", integer("The seconds needed to move between buildings, 600 if absent")),
+            ("private", private_fields()),
+            ("shared", array("Files of sessions shared with cross-listed courses, numbering weeks as the course does unless they set week_numbering", string("A path relative to the specification, or a URL"))),
+            ("week_numbering", week_numbering()),
+            ("week", array("Teaching weeks in chronological order", week())),
+            ("session", array("Sessions that repeat in multiple weeks", repeat_session())),
+            ("assignment", array("Assessments with submissions and presentations", assignment())),
+            ("quiz", array("Quizzes that open and close in several weeks", quiz())),
+            ("office_hours", array("Consultation held on the same weekday every week", office_hours())),
+            ("location", locations()),
+            ("transparency", transparency()),
+            ("extra", extra("Additional properties emitted verbatim on the calendar")),
+            ("vars", vars()),
+            ("defaults", defaults("Fields filled in for sessions that leave them out")),
+            ("kind", kinds()),
+            ("lints", lints()),
+            ("strings", extra("Generated text in the language of the course, such as kinds and Week, keyed by the English text")),
+            ("offering", array("Deliveries of the course in different terms, selected with --offering", offering())),
+        ],
+        &[],
+    );
+    // Courses that extend another may leave the required fields to their parent
+    add_member(
+        &mut schema,
+        "anyOf",
+        Json::Array(vec![
+            Json::object(vec![("required", vec!["code", "name", "link"].into())]),
+            Json::object(vec![("required", vec!["extends"].into())]),
+        ]),
+    );
+
+    if let Json::Object(members) = &mut schema {
+        members.insert(0, ("$schema".to_owned(), "http://json-schema.org/draft-07/schema#".into()));
+        members.insert(1, ("title".to_owned(), "Course calendar specification".into()));
+    }
+    schema
+}
+
+fn week() -> Json {
+    object(
+        "A teaching week",
+        vec![
+            ("start", datetime("The start of the week")),
+            ("label", string("A name for the week, such as Flexibility Week, by which it may be referred to")),
+            ("topic", string("What the week covers")),
+            ("tentative", typed("boolean", "The times and rooms of every session in the week are yet to be confirmed")),
+            ("prep", array("Reading and preparation to do during the week", string("A task, such as Read chapter 4"))),
+            ("session", array("Sessions that occur only in this week", week_session())),
+        ],
+        &["start"],
+    )
+}
+
+fn session_fields() -> Vec<(&'static str, Json)> {
+    vec![
+        ("title", string("The title of the session")),
+        ("description", string("A Markdown description of the session")),
+        ("link", uri("A page for the session, such as a streaming link")),
+        ("attachments", array("Links to attached files", uri("An attached file"))),
+        ("recording", uri("A link to the recording of the session")),
+        ("slides", uri("A link to the slides for the session")),
+        ("meeting_url", uri("A link to join the session online, such as a video meeting")),
+        ("location", string("Where the session is held")),
+        ("presenters", array("People running the session", string("A presenter"))),
+        ("kind", string("The kind of session, such as lecture, tutorial, or lab")),
+        ("stream", string("The group of alternative classes this session belongs to")),
+        ("duration", integer("The length of the session in seconds, required unless there is a default")),
+        ("tags", tags()),
+        ("resources", array("Equipment needed for the session", string("A resource, such as a projector"))),
+        ("reminders", reminders()),
+        ("geo", geo()),
+        ("private", typed("boolean", "Only staff see the session, and it is left out of public feeds")),
+        ("tentative", typed("boolean", "The time and room are yet to be confirmed")),
+        ("extra", extra("Additional properties emitted verbatim on the event")),
+    ]
+}
+
+fn session(time: &'static str, description: &str) -> Json {
+    let mut fields = vec![(time, datetime(description))];
+    fields.extend(session_fields());
+    object("An interactive session", fields, &[time, "kind"])
+}
+
+fn week_session() -> Json {
+    let mut schema = session("time", "When the session starts");
+    add_property(&mut schema, "topic", string("What the session covers, recorded in a journal entry"));
+    add_property(&mut schema, "notes", string("Notes from the session, recorded in a journal entry"));
+    schema
+}
+
+fn repeat_session() -> Json {
+    let mut schema = session("first", "When the session occurs in the first of its weeks");
+    add_property(&mut schema, "weeks", array("The weeks in which the session repeats", week_reference()));
+    add_property(
+        &mut schema,
+        "extra",
+        Json::object(vec![(
+            "anyOf",
+            Json::Array(vec![
+                extra("Additional properties emitted verbatim on each event of the series"),
+                array("Single sessions of the series outside of its weeks, such as a revision lecture", additional_session()),
+            ]),
+        )]),
+    );
+    add_property(&mut schema, "additional", array("Single sessions of the series outside of its weeks, for a series that also has extra properties", additional_session()));
+    add_required(&mut schema, "weeks");
+    schema
+}
+
+fn additional_session() -> Json {
+    object(
+        "A session of a series at a time not tied to its weeks, taking the other fields of the series",
+        vec![
+            ("time", datetime("When the session starts")),
+            ("title", string("The title of the session")),
+            ("description", string("A Markdown description of the session")),
+            ("location", string("Where the session is held")),
+            ("duration", integer("The length of the session in seconds")),
+        ],
+        &["time"],
+    )
+}
+
+fn assignment() -> Json {
+    object(
+        "An assessment item",
+        vec![
+            ("name", string("The name of the assignment")),
+            ("description", string("A Markdown description of the assignment")),
+            ("link", uri("The assignment specification")),
+            ("value", integer("The weight of the assignment")),
+            ("priority", priority()),
+            ("tags", tags()),
+            ("released", datetime("When the specification becomes available")),
+            ("submission", array("Submission deadlines", submission())),
+            ("presentation", array("Presentations given in sessions", presentation())),
+            ("milestone", array("Checkpoints before the final submission", milestone())),
+        ],
+        &["name", "link"],
+    )
+}
+
+fn quiz() -> Json {
+    object(
+        "A quiz held at the same times in several weeks",
+        vec![
+            ("name", string("The name of the quiz")),
+            ("description", string("A Markdown description of the quiz")),
+            ("link", uri("Where the quiz is taken")),
+            ("value", integer("The weight of the quiz across all weeks")),
+            ("priority", priority()),
+            ("tags", tags()),
+            ("opens", datetime("When the quiz opens in the first of its weeks")),
+            ("closes", datetime("When the quiz closes in the first of its weeks")),
+            ("weeks", array("The weeks in which the quiz is held", week_reference())),
+        ],
+        &["name", "opens", "closes", "weeks"],
+    )
+}
+
+fn office_hours() -> Json {
+    object(
+        "Office hours held on a weekday in every teaching week",
+        vec![
+            ("title", string("The title of the event, which is Office hours if absent")),
+            ("weekday", string("The day of the week, such as Monday or Mon")),
+            ("time", typed("string", "The local time at which office hours start, such as 14:00")),
+            ("duration", integer("The length of the office hours in seconds")),
+            ("presenters", array("The people holding the office hours", string("A presenter"))),
+            ("location", string("Where the office hours are held")),
+            ("link", uri("A link to join online, also used as the location if there is none")),
+            ("description", string("A Markdown description of the office hours")),
+            ("tags", tags()),
+            ("weeks", array("The weeks with office hours, or every week if absent", week_reference())),
+            ("private", typed("boolean", "Only staff see the office hours, and they are left out of public feeds")),
+        ],
+        &["weekday", "time", "duration"],
+    )
+}
+
+fn submission() -> Json {
+    object(
+        "A submission deadline",
+        vec![
+            ("time", datetime("When the submission is due")),
+            ("name", string("The name of the submission")),
+            ("description", string("A Markdown description of the submission")),
+            ("attachments", array("Links to attached files", uri("An attached file"))),
+            ("countdown", array("Reminder events before the deadline", countdown())),
+            ("late_until", datetime("The last time late submissions are accepted")),
+            ("grace", period("How long after the deadline late submissions are accepted, unless late_until is given")),
+            ("penalty", string("The penalty applied to late submissions")),
+        ],
+        &["time", "name"],
+    )
+}
+
+fn milestone() -> Json {
+    object(
+        "An intermediate checkpoint such as a design review",
+        vec![
+            ("time", datetime("When the milestone takes place")),
+            ("name", string("The name of the milestone")),
+            ("description", string("A Markdown description of the milestone")),
+        ],
+        &["time", "name"],
+    )
+}
+
+fn countdown() -> Json {
+    period("How long before the deadline, such as 7d or 12h")
+}
+
+fn period(description: &str) -> Json {
+    let mut schema = string(description);
+    add_member(&mut schema, "pattern", "^[0-9]+[wdhm]$".into());
+    schema
+}
+
+fn presentation() -> Json {
+    object(
+        "A presentation held in every session of a kind in some weeks",
+        vec![
+            ("name", string("The name of the presentation")),
+            ("session", string("The kind of session in which the presentation is held")),
+            ("description", string("A Markdown description of the presentation")),
+            ("attachments", array("Links to attached files", uri("An attached file"))),
+            ("weeks", array("The weeks in which the presentation is held", week_reference())),
+            ("slot", integer("The length in seconds of the slot given to each group, dividing sessions into slots")),
+            ("groups", array("The groups presenting, in the order in which they are given slots", string("The name of a group"))),
+            ("participants", participants()),
+        ],
+        &["name", "session", "weeks"],
+    )
+}
+
+fn participants() -> Json {
+    let mut schema = typed("object", "The members of each group, by the name of the group");
+    add_member(&mut schema, "additionalProperties", array("The members of the group", string("A member of the group")));
+    schema
+}
+
+fn locations() -> Json {
+    let location = object(
+        "A room, referred to by its identifier in the location of sessions",
+        vec![
+            ("name", string("The name to show, such as the room code, defaulting to the identifier")),
+            ("building", string("The building containing the room")),
+            ("address", string("The street address of the building")),
+            ("map", uri("A map showing the room")),
+            ("geo", geo()),
+        ],
+        &[],
+    );
+    let mut schema = typed("object", "Rooms keyed by identifier");
+    add_member(&mut schema, "additionalProperties", location);
+    schema
+}
+
+fn geo() -> Json {
+    let mut schema = array("The latitude and longitude in degrees", typed("number", "A coordinate in degrees"));
+    add_member(&mut schema, "minItems", 2.into());
+    add_member(&mut schema, "maxItems", 2.into());
+    schema
+}
+
+fn offering() -> Json {
+    Json::object(vec![
+        ("type", "object".into()),
+        ("description", "A delivery of the course, whose other fields override those of the course".into()),
+        (
+            "properties",
+            Json::object(vec![
+                ("term", string("The term of the offering, such as T1")),
+                ("weeks", array("The starts of the weeks in the offering, to which every time moves", datetime("The start of a week"))),
+            ]),
+        ),
+        ("required", vec!["term"].into()),
+    ])
+}
+
+fn lints() -> Json {
+    let level = Json::object(vec![
+        ("type", "string".into()),
+        ("enum", vec!["allow", "warn", "deny"].into()),
+    ]);
+    Json::object(vec![
+        ("type", "object".into()),
+        ("description", "Levels for validation rules, keyed by rule name".into()),
+        ("additionalProperties", level),
+    ])
+}
+
+fn private_fields() -> Json {
+    let field = Json::object(vec![
+        ("type", "string".into()),
+        (
+            "enum",
+            vec!["location", "presenters", "description", "link", "meeting_url", "notes", "attachments", "slides", "recording", "participants"].into(),
+        ),
+    ]);
+    array("Fields of events withheld from public feeds, with the links of assignments pointing to the course instead", field)
+}
+
+fn priority() -> Json {
+    let mut schema = integer("The priority from 1 (highest) to 9, derived from the value if absent");
+    add_member(&mut schema, "minimum", 1.into());
+    add_member(&mut schema, "maximum", 9.into());
+    schema
+}
+
+fn transparency() -> Json {
+    let value = Json::object(vec![
+        ("type", "string".into()),
+        ("enum", vec!["opaque", "transparent"].into()),
+    ]);
+    Json::object(vec![
+        ("type", "object".into()),
+        ("description", "Whether each kind of event shows as busy, keyed by kind".into()),
+        ("additionalProperties", value),
+    ])
+}
+
+fn week_reference() -> Json {
+    Json::object(vec![
+        ("type", vec!["integer", "string"].into()),
+        ("description", "A week number, counting from 1 unless weeks are numbered from 0, or the label of a week, though TOML keeps each array to one or the other".into()),
+    ])
+}
+
+fn week_numbering() -> Json {
+    Json::object(vec![
+        ("type", "string".into()),
+        ("description", "Whether week references count from 0 or 1, which is the default".into()),
+        ("enum", vec!["zero-based", "one-based"].into()),
+    ])
+}
+
+fn vars() -> Json {
+    let value = Json::object(vec![("type", vec!["string", "number", "boolean"].into())]);
+    Json::object(vec![
+        ("type", "object".into()),
+        ("description", "Values referred to as {{name}} in the text of the specification".into()),
+        ("additionalProperties", value),
+    ])
+}
+
+fn defaults(description: &str) -> Json {
+    object(
+        description,
+        vec![
+            ("location", string("Where sessions are held")),
+            ("duration", integer("The length of sessions in seconds")),
+            ("presenters", array("People running sessions", string("A presenter"))),
+            ("reminders", reminders()),
+        ],
+        &[],
+    )
+}
+
+fn kinds() -> Json {
+    Json::object(vec![
+        ("type", "object".into()),
+        ("description", "Defaults for each kind of session, keyed by kind, which take precedence over the other defaults".into()),
+        ("additionalProperties", defaults("Fields filled in for sessions of the kind that leave them out")),
+    ])
+}
+
+fn reminders() -> Json {
+    array("How long before the session to remind attendees", integer("A number of seconds"))
+}
+
+fn tags() -> Json {
+    array("Free-form tags emitted as categories", string("A tag"))
+}
+
+fn extra(description: &str) -> Json {
+    Json::object(vec![
+        ("type", "object".into()),
+        ("description", description.into()),
+        ("additionalProperties", Json::object(vec![("type", "string".into())])),
+    ])
+}
+
+fn typed(kind: &str, description: &str) -> Json {
+    Json::object(vec![("type", kind.into()), ("description", description.into())])
+}
+
+fn string(description: &str) -> Json {
+    typed("string", description)
+}
+
+fn integer(description: &str) -> Json {
+    typed("integer", description)
+}
+
+fn uri(description: &str) -> Json {
+    let mut schema = string(description);
+    add_member(&mut schema, "format", "uri".into());
+    schema
+}
+
+fn datetime(description: &str) -> Json {
+    let mut schema = string(description);
+    add_member(&mut schema, "format", "date-time".into());
+    schema
+}
+
+fn array(description: &str, items: Json) -> Json {
+    Json::object(vec![
+        ("type", "array".into()),
+        ("description", description.into()),
+        ("items", items),
+    ])
+}
+
+fn object(description: &str, properties: Vec<(&str, Json)>, required: &[&str]) -> Json {
+    Json::object(vec![
+        ("type", "object".into()),
+        ("description", description.into()),
+        ("properties", Json::object(properties)),
+        ("required", required.to_vec().into()),
+        ("additionalProperties", false.into()),
+    ])
+}
+
+fn add_member(schema: &mut Json, key: &str, value: Json) {
+    if let Json::Object(members) = schema {
+        members.push((key.to_owned(), value));
+    }
+}
+
+fn add_property(schema: &mut Json, name: &str, property: Json) {
+    if let Json::Object(members) = schema {
+        for (key, value) in members.iter_mut() {
+            if let ("properties", Json::Object(properties)) = (key.as_str(), value) {
+                properties.retain(|(existing, _)| existing != name);
+                properties.push((name.to_owned(), property.clone()));
+            }
+        }
+    }
+}
+
+fn add_required(schema: &mut Json, name: &str) {
+    if let Json::Object(members) = schema {
+        for (key, value) in members.iter_mut() {
+            if let ("required", Json::Array(required)) = (key.as_str(), value) {
+                required.push(name.into());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::course::Course;
+    use crate::spec;
+
+    use toml::Value;
+
+    const EXAMPLE: &str = r#"
+        version = 2
+        code = "COMP1000"
+        name = "Introduction to {{subject}}"
+        link = "https://example.edu/comp1000"
+        private = ["notes"]
+        week_numbering = "one-based"
+
+        [vars]
+        subject = "Computing"
+
+        [defaults]
+        duration = 3600
+
+        [kind.lab]
+        duration = 7200
+
+        [transparency]
+        consultation = "transparent"
+
+        [location.quad1001]
+        name = "Quadrangle 1001"
+
+        [[week]]
+        start = 2024-02-12T00:00:00+11:00
+        topic = "Getting started"
+        prep = ["Read chapter 1"]
+
+        [[week.session]]
+        time = 2024-02-16T10:00:00+11:00
+        kind = "consultation"
+        notes = "Bring questions"
+
+        [[week]]
+        start = 2024-02-19T00:00:00+11:00
+        label = "Flexibility Week"
+
+        [[session]]
+        first = 2024-02-12T10:00:00+11:00
+        kind = "lecture"
+        location = "quad1001"
+        presenters = ["Ada"]
+        weeks = [1, 2]
+        extra = { "X-ROOM-CAPACITY" = "200" }
+
+        [[session.additional]]
+        time = 2024-02-23T10:00:00+11:00
+        title = "Revision"
+
+        [[session]]
+        first = 2024-02-13T14:00:00+11:00
+        kind = "lab"
+        stream = "T14A"
+        tags = ["hands-on"]
+        reminders = [900]
+        weeks = [1, 2]
+
+        [[session.extra]]
+        time = 2024-02-22T14:00:00+11:00
+        location = "quad1001"
+
+        [[assignment]]
+        name = "Assignment 1"
+        link = "https://example.edu/comp1000/ass1"
+        value = 20
+        released = 2024-02-12T09:00:00+11:00
+
+        [[assignment.submission]]
+        time = 2024-02-23T17:00:00+11:00
+        name = "Final"
+        countdown = ["7d"]
+        grace = "2d"
+        penalty = "5% per day"
+
+        [[assignment.milestone]]
+        time = 2024-02-16T17:00:00+11:00
+        name = "Design review"
+
+        [[assignment.presentation]]
+        name = "Demo"
+        session = "lab"
+        weeks = ["Flexibility Week"]
+        groups = ["Red", "Blue"]
+
+        [[quiz]]
+        name = "Weekly quiz"
+        value = 10
+        opens = 2024-02-12T09:00:00+11:00
+        closes = 2024-02-14T17:00:00+11:00
+        weeks = [1, 2]
+
+        [[office_hours]]
+        weekday = "Wednesday"
+        time = "14:00"
+        duration = 3600
+        presenters = ["Ada"]
+    "#;
+
+    /// Check a value against the parts of JSON Schema used here, listing the problems
+    fn check(schema: &Json, value: &Value, path: &str, problems: &mut Vec<String>) {
+        let types = match schema.get("type") {
+            Some(Json::String(kind)) => vec![kind.as_str()],
+            Some(kinds) => kinds.as_array().unwrap_or_default().iter().filter_map(Json::as_str).collect(),
+            None => Vec::new(),
+        };
+        let matches = |kind: &str| {
+            matches!(
+                (kind, value),
+                ("object", Value::Table(_))
+                    | ("array", Value::Array(_))
+                    | ("integer", Value::Integer(_))
+                    | ("number", Value::Integer(_))
+                    | ("number", Value::Float(_))
+                    | ("string", Value::String(_))
+                    | ("string", Value::Datetime(_))
+                    | ("boolean", Value::Boolean(_))
+            )
+        };
+        if !types.is_empty() && !types.iter().any(|kind| matches(kind)) {
+            problems.push(format!("{} is not of type {}", path, types.join(" or ")));
+            return;
+        }
+        if let (Some(allowed), Some(value)) = (schema.get("enum").and_then(Json::as_array), value.as_str()) {
+            if !allowed.iter().any(|allowed| allowed.as_str() == Some(value)) {
+                problems.push(format!("{} is not one of the allowed values", path));
+            }
+        }
+        if let Some(options) = schema.get("anyOf").and_then(Json::as_array) {
+            let failing = |option: &Json| {
+                let mut problems = Vec::new();
+                check(option, value, path, &mut problems);
+                !problems.is_empty()
+            };
+            if options.iter().all(failing) {
+                problems.push(format!("{} matches none of its alternatives", path));
+            }
+        }
+
+        match value {
+            Value::Table(table) => {
+                for required in schema.get("required").and_then(Json::as_array).unwrap_or_default() {
+                    if !table.contains_key(required.as_str().unwrap()) {
+                        problems.push(format!("{} is missing {}", path, required.as_str().unwrap()));
+                    }
+                }
+                for (key, value) in table {
+                    let path = format!("{}.{}", path, key);
+                    match schema.get("properties").and_then(|properties| properties.get(key)) {
+                        Some(property) => check(property, value, &path, problems),
+                        None => match schema.get("additionalProperties") {
+                            Some(Json::Bool(false)) => problems.push(format!("{} is not in the schema", path)),
+                            Some(Json::Bool(true)) | None => {}
+                            Some(additional) => check(additional, value, &path, problems),
+                        },
+                    }
+                }
+            }
+            Value::Array(values) => {
+                if let Some(items) = schema.get("items") {
+                    for (index, value) in values.iter().enumerate() {
+                        check(items, value, &format!("{}[{}]", path, index), problems);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    #[test]
+    fn example_matches_schema() {
+        let mut example: Value = toml::from_str(EXAMPLE).unwrap();
+        let mut problems = Vec::new();
+        check(&course(), &example, "course", &mut problems);
+        assert!(problems.is_empty(), "{:#?}", problems);
+
+        spec::interpolate(&mut example).unwrap();
+        spec::apply_defaults(&mut example).unwrap();
+        let mut course = Course::from_value(example).unwrap();
+        course.generate_repeats().unwrap();
+        assert!(course.events().count() > 0);
+    }
+
+    #[test]
+    fn unknown_fields_are_rejected() {
+        let example: Value = toml::from_str("code = \"COMP1000\"\nname = \"Testing\"\nlink = \"https://example.edu/\"\n[[week]]\nstart = 2024-02-12T00:00:00+11:00\nlength = 7").unwrap();
+        let mut problems = Vec::new();
+        check(&course(), &example, "course", &mut problems);
+        assert_eq!(problems, vec!["course.week[0].length is not in the schema".to_owned()]);
+    }
+}
@@ -0,0 +1,619 @@
+//! A small HTTP server publishing calendars for a directory of courses
+
+use chrono::{offset::Utc, DateTime};
+use failure::{bail, format_err, Error};
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+
+use crate::calendar;
+use crate::course::Course;
+use crate::filter::Filter;
+use crate::state::{self, State};
+
+use std::collections::BTreeMap;
+use std::fs::{read_dir, read_to_string, write, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How long to wait on a client before giving up on it
+const TIMEOUT: Duration = Duration::from_secs(10);
+/// The most bytes read from a request line and its headers
+const MAX_REQUEST: u64 = 16 * 1024;
+/// The most connections handled at once, beyond which clients are turned away
+const MAX_CONNECTIONS: usize = 32;
+
+/// Private feeds and whether public feeds are disabled
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Access {
+    /// Only serve feeds that have a token
+    #[serde(default)]
+    private: bool,
+    #[serde(rename = "feed", default)]
+    feeds: Vec<PrivateFeed>,
+}
+
+/// A filtered calendar of a course served only to holders of its token
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct PrivateFeed {
+    name: String,
+    /// The code of the course
+    course: String,
+    token: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    streams: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    kinds: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    tags: Vec<String>,
+}
+
+impl Access {
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        Ok(toml::from_str(&read_to_string(path)?)?)
+    }
+
+    /// Add a private feed with a new random token, returning its path on the server
+    ///
+    /// The feed includes the streams, kinds, and tags listed in `filter`.
+    pub fn add_feed<P: AsRef<Path>>(path: P, name: &str, course: &str, filter: &Filter) -> Result<String, Error> {
+        let path = path.as_ref();
+        let mut access = if path.exists() { Access::from_path(path)? } else { Access::default() };
+        if access.feeds.iter().any(|feed| feed.course == course && feed.name == name) {
+            bail!("A feed named {} already exists for {}", name, course);
+        }
+
+        let feed = PrivateFeed {
+            name: name.to_owned(),
+            course: course.to_owned(),
+            token: new_token()?,
+            streams: filter.streams.clone(),
+            kinds: filter.kinds.clone(),
+            tags: filter.tags.clone(),
+        };
+        let url = format!("/{}/{}.ics?token={}", feed.course, feed.name, feed.token);
+        access.feeds.push(feed);
+        write(path, toml::to_string(&access)?)?;
+        Ok(url)
+    }
+
+    /// Remove a private feed, invalidating its token
+    pub fn revoke_feed<P: AsRef<Path>>(path: P, name: &str, course: &str) -> Result<(), Error> {
+        let path = path.as_ref();
+        let mut access = Access::from_path(path)?;
+        let count = access.feeds.len();
+        access.feeds.retain(|feed| !(feed.course == course && feed.name == name));
+        if access.feeds.len() == count {
+            bail!("No feed named {} for {}", name, course);
+        }
+        write(path, toml::to_string(&access)?)?;
+        Ok(())
+    }
+
+    /// The private feed requested at `/{course}/{name}.ics` with a token
+    fn feed(&self, course: &str, name: &str, token: Option<&str>) -> Option<&PrivateFeed> {
+        let token = token?;
+        self.feeds
+            .iter()
+            .find(|feed| feed.course == course && feed.name == name && constant_time_eq(&feed.token, token))
+    }
+}
+
+/// Generate a random token from the system's entropy source
+fn new_token() -> Result<String, Error> {
+    let mut bytes = [0u8; 16];
+    File::open("/dev/urandom")?.read_exact(&mut bytes)?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// Compare tokens without revealing how much of them matched
+fn constant_time_eq(expected: &str, actual: &str) -> bool {
+    expected.len() == actual.len()
+        && expected.bytes().zip(actual.bytes()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+}
+
+/// The generated calendar for a single course
+#[derive(Debug, Clone)]
+struct Feed {
+    source: PathBuf,
+    /// When the specification was last modified
+    modified: SystemTime,
+    course: Course,
+    state: State,
+    calendar: Calendar,
+}
+
+/// A generated calendar with the details needed for caching
+#[derive(Debug, Clone)]
+struct Calendar {
+    content: String,
+    /// A hash of the content of the calendar
+    etag: String,
+    /// When the content of the calendar last changed
+    changed: DateTime<Utc>,
+}
+
+impl Calendar {
+    fn generate(course: &Course, filter: &Filter, state: &State, changed: DateTime<Utc>) -> Result<Self, Error> {
+        let external = course.merged_calendars()?;
+        let mut output = calendar::generate(course, course.name().to_owned(), filter, state);
+        calendar::merge(&mut output, &external);
+        let mut buffer = Vec::new();
+        output.write(&mut buffer)?;
+        let content = String::from_utf8(buffer)?;
+
+        // Timestamps change on every generation, so they aren't part of the content
+        let lines = content.lines().filter(|line| !line.starts_with("DTSTAMP:")).collect::<Vec<_>>();
+        let etag = format!("\"{:016x}\"", state::hash(&lines.join("\n")));
+        Ok(Calendar { content, etag, changed })
+    }
+
+    /// Whether a client already holds the current calendar
+    fn is_fresh(&self, request: &Request) -> bool {
+        if let Some(tags) = request.header("If-None-Match") {
+            return tags.split(',').any(|tag| tag.trim() == self.etag || tag.trim() == "*");
+        }
+        request
+            .header("If-Modified-Since")
+            .and_then(|since| DateTime::parse_from_rfc2822(since).ok())
+            .map(|since| self.changed.timestamp() <= since.timestamp())
+            .unwrap_or(false)
+    }
+}
+
+/// Calendars for every course in a directory, regenerated as they change
+#[derive(Debug, Default)]
+struct Feeds {
+    /// Feeds keyed by course code
+    feeds: BTreeMap<String, Feed>,
+    /// Leave out private sessions and fields of every course
+    public: bool,
+}
+
+impl Feeds {
+    /// Regenerate the calendar of any course whose specification has changed
+    fn refresh(&mut self, directory: &Path, filter: &Filter) -> Result<(), Error> {
+        for entry in read_dir(directory)? {
+            let path = entry?.path();
+            if path.extension().map(|extension| extension != "toml").unwrap_or(true) {
+                continue;
+            }
+            let modified = path.metadata()?.modified()?;
+            let current = self.feeds.values().any(|feed| feed.source == path && feed.modified == modified);
+            if current {
+                continue;
+            }
+
+            match self.generate(&path, modified, filter) {
+                Ok(feed) => {
+                    eprintln!("Generated {}.ics from {}", feed.course.code(), path.display());
+                    self.feeds.retain(|_, feed| feed.source != path);
+                    self.feeds.insert(feed.course.code().to_owned(), feed);
+                }
+                Err(error) => {
+                    // Keep serving the previous calendar until the error is fixed
+                    eprintln!("Failed to generate {}: {}", path.display(), error);
+                    if let Some(feed) = self.feeds.values_mut().find(|feed| feed.source == path) {
+                        feed.modified = modified;
+                    }
+                }
+            }
+        }
+
+        self.feeds.retain(|_, feed| feed.source.exists());
+        Ok(())
+    }
+
+    fn generate(&self, path: &Path, modified: SystemTime, filter: &Filter) -> Result<Feed, Error> {
+        let mut course = Course::from_path(path)?;
+        if self.public {
+            course.redact();
+        }
+        let previous = self.feeds.get(course.code()).filter(|feed| feed.source == path);
+        let mut state = previous.map(|feed| feed.state.clone()).unwrap_or_default();
+        calendar::update_state(&course, filter, &mut state);
+
+        let mut calendar = Calendar::generate(&course, filter, &state, Utc::now())?;
+        if let Some(previous) = previous.filter(|feed| feed.calendar.etag == calendar.etag) {
+            calendar.changed = previous.calendar.changed;
+        }
+        Ok(Feed { source: path.to_path_buf(), modified, course, state, calendar })
+    }
+}
+
+/// Serve the calendar of each course in `directory` at `/{code}.ics`
+///
+/// Private feeds are served at `/{code}/{name}.ics`, with their token given
+/// either as the `token` query parameter or as a bearer token. With `public`,
+/// every feed is redacted as for a public calendar.
+pub fn serve(directory: &str, listen: &str, filter: &Filter, public: bool, access: Access) -> Result<(), Error> {
+    let directory = PathBuf::from(directory);
+    let feeds = Arc::new(Mutex::new(Feeds { public, ..Feeds::default() }));
+    let access = Arc::new(access);
+    let active = Arc::new(AtomicUsize::new(0));
+    feeds.lock().unwrap().refresh(&directory, filter)?;
+
+    let listener = TcpListener::bind(listen)?;
+    eprintln!("Serving calendars from {} on http://{}/", directory.display(), listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        let mut stream = match stream.and_then(limit) {
+            Ok(stream) => stream,
+            Err(error) => {
+                eprintln!("Failed to accept connection: {}", error);
+                continue;
+            }
+        };
+        if !admit(&active) {
+            if let Err(error) = write_response(&mut stream, "503 Service Unavailable", &[], "Too many connections\n", true) {
+                eprintln!("Failed to respond: {}", error);
+            }
+            continue;
+        }
+        let feeds = Arc::clone(&feeds);
+        let access = Arc::clone(&access);
+        let active = Arc::clone(&active);
+        let directory = directory.clone();
+        let filter = filter.clone();
+        thread::spawn(move || {
+            if let Err(error) = respond(stream, &feeds, &access, &directory, &filter) {
+                eprintln!("Failed to respond: {}", error);
+            }
+            active.fetch_sub(1, Ordering::SeqCst);
+        });
+    }
+    Ok(())
+}
+
+/// Give up on a client that stalls for longer than `TIMEOUT`
+fn limit(stream: TcpStream) -> io::Result<TcpStream> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    Ok(stream)
+}
+
+/// Count a new connection, unless `MAX_CONNECTIONS` are already being handled
+fn admit(active: &AtomicUsize) -> bool {
+    active
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| if count < MAX_CONNECTIONS { Some(count + 1) } else { None })
+        .is_ok()
+}
+
+/// A request read from a client
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The token from either the query or an `Authorization` header
+    fn token(&self) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == "token")
+            .map(|(_, value)| value.as_str())
+            .or_else(|| self.header("Authorization").and_then(|value| value.strip_prefix("Bearer ")))
+    }
+}
+
+/// Read the request line and headers, up to `MAX_REQUEST` bytes
+fn read_request(stream: &TcpStream) -> Result<Request, Error> {
+    let mut reader = BufReader::new(stream.take(MAX_REQUEST));
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_owned();
+    let target = parts.next().ok_or(format_err!("Malformed request line"))?;
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+    let query = form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+
+    let mut headers = Vec::new();
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            bail!("Request was incomplete or longer than {} bytes", MAX_REQUEST);
+        } else if header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_owned(), value.trim().to_owned()));
+        }
+    }
+
+    Ok(Request { method, path: path.to_owned(), query, headers })
+}
+
+fn respond(
+    mut stream: TcpStream,
+    feeds: &Mutex<Feeds>,
+    access: &Access,
+    directory: &Path,
+    filter: &Filter,
+) -> Result<(), Error> {
+    let request = match read_request(&stream) {
+        Ok(request) => request,
+        Err(error) => {
+            write_response(&mut stream, "400 Bad Request", &[], "Bad request\n", true)?;
+            return Err(error);
+        }
+    };
+    if request.method != "GET" && request.method != "HEAD" {
+        return write_response(&mut stream, "405 Method Not Allowed", &[], "Method not allowed\n", true);
+    }
+    let body = request.method == "GET";
+    let not_found = |stream: &mut TcpStream| write_response(stream, "404 Not Found", &[], "Not found\n", body);
+
+    let path = match request.path.trim_start_matches('/').strip_suffix(".ics") {
+        Some(path) => path.to_owned(),
+        None => return not_found(&mut stream),
+    };
+    let (code, private) = match path.split_once('/') {
+        Some((code, name)) => match access.feed(code, name, request.token()) {
+            Some(feed) => (code, Some(feed)),
+            // Unknown feeds and wrong tokens are indistinguishable
+            None => return not_found(&mut stream),
+        },
+        None if access.private => return not_found(&mut stream),
+        None => (path.as_str(), None),
+    };
+
+    let failed = |stream: &mut TcpStream, error: Error| {
+        write_response(stream, "500 Internal Server Error", &[], "Failed to generate the calendar\n", body)?;
+        Err(error)
+    };
+
+    let feed = {
+        let mut feeds = feeds.lock().unwrap();
+        feeds.refresh(directory, filter).map(|()| feeds.feeds.get(code).cloned())
+    };
+    let feed = match feed {
+        Ok(Some(feed)) => feed,
+        Ok(None) => return not_found(&mut stream),
+        Err(error) => return failed(&mut stream, error),
+    };
+
+    let calendar = match private {
+        Some(private) => {
+            let mut filter = filter.clone();
+            filter.streams.extend(private.streams.iter().cloned());
+            filter.kinds.extend(private.kinds.iter().cloned());
+            filter.tags.extend(private.tags.iter().cloned());
+            match Calendar::generate(&feed.course, &filter, &feed.state, feed.calendar.changed) {
+                Ok(calendar) => calendar,
+                Err(error) => return failed(&mut stream, error),
+            }
+        }
+        None => feed.calendar,
+    };
+
+    let headers = [
+        ("ETag", calendar.etag.clone()),
+        ("Last-Modified", calendar.changed.format("%a, %d %b %Y %H:%M:%S GMT").to_string()),
+        ("Cache-Control", if private.is_some() { "private, no-cache" } else { "no-cache" }.to_owned()),
+    ];
+    if calendar.is_fresh(&request) {
+        write_response(&mut stream, "304 Not Modified", &headers, "", false)
+    } else {
+        let mut headers = headers.to_vec();
+        headers.push(("Content-Type", "text/calendar; charset=utf-8".to_owned()));
+        write_response(&mut stream, "200 OK", &headers, &calendar.content, body)
+    }
+}
+
+/// Write a response, which is plain text unless another content type is given
+fn write_response(
+    stream: &mut TcpStream,
+    status: &str,
+    headers: &[(&str, String)],
+    content: &str,
+    body: bool,
+) -> Result<(), Error> {
+    write!(stream, "HTTP/1.1 {}\r\n", status)?;
+    if !headers.iter().any(|(name, _)| *name == "Content-Type") {
+        write!(stream, "Content-Type: text/plain\r\n")?;
+    }
+    for (name, value) in headers {
+        write!(stream, "{}: {}\r\n", name, value)?;
+    }
+    write!(stream, "Content-Length: {}\r\nConnection: close\r\n\r\n", content.len())?;
+    if body {
+        stream.write_all(content.as_bytes())?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::{create_dir_all, remove_dir_all, remove_file};
+    use std::net::Shutdown;
+
+    /// Send a request to a handler over a local connection, returning the response
+    fn exchange(request: &[u8], handler: impl FnOnce(TcpStream)) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap()).unwrap();
+        let (server, _) = listener.accept().unwrap();
+        client.write_all(request).unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        handler(server);
+        let mut response = String::new();
+        // A server that leaves part of the request unread resets the connection
+        let _ = client.read_to_string(&mut response);
+        response
+    }
+
+    /// The response to a request for the courses in a directory
+    fn get(directory: &Path, request: &str) -> String {
+        let feeds = Mutex::new(Feeds::default());
+        exchange(request.as_bytes(), |server| {
+            let _ = respond(server, &feeds, &Access::default(), directory, &Filter::default());
+        })
+    }
+
+    #[test]
+    fn calendars_are_served_by_course_code() {
+        let directory = std::env::temp_dir().join(format!("course-calendar-serve-{}", std::process::id()));
+        create_dir_all(&directory).unwrap();
+        write(
+            directory.join("course.toml"),
+            "version = 2\ncode = \"COMP1000\"\nname = \"Testing\"\nlink = \"https://example.edu/\"\n",
+        )
+        .unwrap();
+        let found = get(&directory, "GET /COMP1000.ics HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let head = get(&directory, "HEAD /COMP1000.ics HTTP/1.1\r\n\r\n");
+        let missing = get(&directory, "GET /COMP9999.ics HTTP/1.1\r\n\r\n");
+        let posted = get(&directory, "POST /COMP1000.ics HTTP/1.1\r\n\r\n");
+        remove_dir_all(&directory).unwrap();
+
+        assert!(found.starts_with("HTTP/1.1 200 OK\r\n"), "{}", found);
+        assert!(found.contains("Content-Type: text/calendar; charset=utf-8\r\n"));
+        assert!(found.ends_with("END:VCALENDAR\r\n"));
+        assert!(head.starts_with("HTTP/1.1 200 OK\r\n") && head.ends_with("\r\n\r\n"), "{}", head);
+        assert!(missing.starts_with("HTTP/1.1 404 Not Found\r\n"));
+        assert!(posted.starts_with("HTTP/1.1 405 Method Not Allowed\r\n"));
+    }
+
+    #[test]
+    fn requests_are_bounded_in_size() {
+        let mut request = b"GET /COMP1000.ics HTTP/1.1\r\nX-Padding: ".to_vec();
+        request.resize(MAX_REQUEST as usize + 1, b'a');
+        request.extend(b"\r\n\r\n");
+        exchange(&request, |server| {
+            let error = read_request(&server).err().unwrap();
+            assert_eq!(error.to_string(), format!("Request was incomplete or longer than {} bytes", MAX_REQUEST));
+        });
+
+        let response = get(Path::new("."), "GET /COMP1000.ics HTTP/1.1\r\nHost: localhost\r\n");
+        assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"), "{}", response);
+    }
+
+    #[test]
+    fn stalled_clients_time_out() {
+        exchange(b"", |server| {
+            let server = limit(server).unwrap();
+            assert_eq!(server.read_timeout().unwrap(), Some(TIMEOUT));
+            assert_eq!(server.write_timeout().unwrap(), Some(TIMEOUT));
+        });
+    }
+
+    #[test]
+    fn connections_beyond_the_limit_are_refused() {
+        let active = AtomicUsize::new(0);
+        assert!((0..MAX_CONNECTIONS).all(|_| admit(&active)));
+        assert!(!admit(&active));
+        active.fetch_sub(1, Ordering::SeqCst);
+        assert!(admit(&active));
+    }
+
+    fn request(headers: &[(&str, &str)]) -> Request {
+        Request {
+            method: "GET".to_owned(),
+            path: "/COMP1000.ics".to_owned(),
+            query: Vec::new(),
+            headers: headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect(),
+        }
+    }
+
+    #[test]
+    fn clients_holding_the_calendar_are_not_sent_it_again() {
+        let changed = DateTime::parse_from_rfc3339("2024-02-12T10:00:00Z").unwrap().with_timezone(&Utc);
+        let calendar = Calendar { content: String::new(), etag: "\"abc\"".to_owned(), changed };
+
+        assert!(calendar.is_fresh(&request(&[("If-None-Match", "\"xyz\", \"abc\"")])));
+        assert!(calendar.is_fresh(&request(&[("if-none-match", "*")])));
+        assert!(!calendar.is_fresh(&request(&[("If-None-Match", "\"xyz\"")])));
+        assert!(calendar.is_fresh(&request(&[("If-Modified-Since", "Mon, 12 Feb 2024 10:00:00 GMT")])));
+        assert!(!calendar.is_fresh(&request(&[("If-Modified-Since", "Mon, 12 Feb 2024 09:59:59 GMT")])));
+        // Entity tags take precedence over dates
+        assert!(!calendar.is_fresh(&request(&[("If-None-Match", "\"xyz\""), ("If-Modified-Since", "Mon, 12 Feb 2024 10:00:00 GMT")])));
+        assert!(!calendar.is_fresh(&request(&[])));
+    }
+
+    #[test]
+    fn entity_tags_ignore_timestamps() {
+        let spec = "version = 2\ncode = \"COMP1000\"\nname = \"Testing\"\nlink = \"https://example.edu/\"\n\
+                    [[week]]\nstart = 2024-02-12T00:00:00+11:00\n\
+                    [[week.session]]\ntime = 2024-02-12T10:00:00+11:00\nkind = \"lecture\"\nduration = 3600\n";
+        let course = Course::from_value(toml::from_str(spec).unwrap()).unwrap();
+        let generate = || Calendar::generate(&course, &Filter::default(), &State::default(), Utc::now()).unwrap();
+        let first = generate();
+        thread::sleep(Duration::from_millis(1100));
+        let second = generate();
+        assert_ne!(first.content, second.content);
+        assert_eq!(first.etag, second.etag);
+    }
+
+    #[test]
+    fn tokens_must_match_exactly() {
+        assert!(constant_time_eq("0123abcd", "0123abcd"));
+        assert!(!constant_time_eq("0123abcd", "0123abce"));
+        assert!(!constant_time_eq("0123abcd", "0123abc"));
+        assert!(!constant_time_eq("0123abcd", ""));
+        assert_eq!(new_token().unwrap().len(), 32);
+        assert_ne!(new_token().unwrap(), new_token().unwrap());
+    }
+
+    #[test]
+    fn private_feeds_are_found_by_token() {
+        let access: Access = toml::from_str(
+            "private = true\n[[feed]]\nname = \"tutors\"\ncourse = \"COMP1000\"\ntoken = \"secret\"\nkinds = [\"tutorial\"]\n",
+        )
+        .unwrap();
+        assert!(access.feed("COMP1000", "tutors", Some("secret")).is_some());
+        assert!(access.feed("COMP1000", "tutors", Some("guess")).is_none());
+        assert!(access.feed("COMP1000", "tutors", None).is_none());
+        assert!(access.feed("COMP2000", "tutors", Some("secret")).is_none());
+
+        let mut by_query = request(&[]);
+        by_query.query.push(("token".to_owned(), "secret".to_owned()));
+        assert_eq!(by_query.token(), Some("secret"));
+        assert_eq!(request(&[("Authorization", "Bearer secret")]).token(), Some("secret"));
+        assert_eq!(request(&[("Authorization", "Basic secret")]).token(), None);
+    }
+
+    #[test]
+    fn wrong_tokens_look_like_missing_feeds() {
+        let access: Access = toml::from_str("private = true\n[[feed]]\nname = \"tutors\"\ncourse = \"COMP1000\"\ntoken = \"secret\"\n").unwrap();
+        let feeds = Mutex::new(Feeds::default());
+        let get = |request: &str| {
+            exchange(request.as_bytes(), |server| {
+                let _ = respond(server, &feeds, &access, Path::new("."), &Filter::default());
+            })
+        };
+        let wrong = get("GET /COMP1000/tutors.ics?token=guess HTTP/1.1\r\n\r\n");
+        let public = get("GET /COMP1000.ics HTTP/1.1\r\n\r\n");
+        assert!(wrong.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", wrong);
+        assert!(public.starts_with("HTTP/1.1 404 Not Found\r\n"), "{}", public);
+    }
+
+    #[test]
+    fn feeds_are_added_and_revoked() {
+        let path = std::env::temp_dir().join(format!("course-calendar-access-{}.toml", std::process::id()));
+        let filter = Filter { streams: vec!["T14A".to_owned()], ..Filter::default() };
+        let url = Access::add_feed(&path, "tutors", "COMP1000", &filter).unwrap();
+        let again = Access::add_feed(&path, "tutors", "COMP1000", &filter).unwrap_err();
+        let access = Access::from_path(&path).unwrap();
+        Access::revoke_feed(&path, "tutors", "COMP1000").unwrap();
+        let revoked = Access::from_path(&path).unwrap();
+        remove_file(&path).unwrap();
+
+        let token = url.strip_prefix("/COMP1000/tutors.ics?token=").unwrap();
+        assert_eq!(access.feed("COMP1000", "tutors", Some(token)).unwrap().streams, vec!["T14A"]);
+        assert_eq!(again.to_string(), "A feed named tutors already exists for COMP1000");
+        assert!(revoked.feeds.is_empty());
+    }
+}
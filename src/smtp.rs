@@ -0,0 +1,248 @@
+//! A minimal SMTP client for sending plain text mail
+
+use failure::{bail, format_err, Error};
+use url::Url;
+
+use crate::http::base64;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// How long to wait to connect, or for the server to respond
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A message ready to send
+#[derive(Debug, Clone)]
+pub struct Message<'m> {
+    pub from: &'m str,
+    pub to: &'m [String],
+    pub subject: &'m str,
+    pub body: &'m str,
+}
+
+impl Message<'_> {
+    /// The message with its headers, using CRLF line endings
+    fn format(&self) -> String {
+        let mut message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nMIME-Version: 1.0\r\nContent-Type: text/plain; charset=utf-8\r\nContent-Transfer-Encoding: 8bit\r\n\r\n",
+            self.from,
+            self.to.join(", "),
+            self.subject,
+            chrono::Local::now().to_rfc2822(),
+        );
+        for line in self.body.lines() {
+            message.push_str(line);
+            message.push_str("\r\n");
+        }
+        message
+    }
+}
+
+/// Send a message through a server given as an `smtp:` or `smtps:` URL
+///
+/// Plain SMTP is spoken directly, without STARTTLS, while `smtps:` defers to
+/// curl as there is no TLS implementation available.
+pub fn send(server: &Url, credentials: Option<(&str, &str)>, message: &Message) -> Result<(), Error> {
+    match server.scheme() {
+        "smtp" => send_plain(server, credentials, message),
+        "smtps" => send_curl(server, credentials, message),
+        scheme => bail!("Unsupported mail server scheme {}", scheme),
+    }
+}
+
+fn send_plain(server: &Url, credentials: Option<(&str, &str)>, message: &Message) -> Result<(), Error> {
+    let host = server.host_str().ok_or(format_err!("No host in {}", server))?;
+    let address = (host, server.port().unwrap_or(25))
+        .to_socket_addrs()?
+        .next()
+        .ok_or(format_err!("Could not resolve {}", host))?;
+    let stream = TcpStream::connect_timeout(&address, TIMEOUT)?;
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut session = Session { reader: BufReader::new(stream.try_clone()?), writer: stream };
+
+    session.expect(220)?;
+    session.command("EHLO localhost", 250)?;
+    if let Some((username, password)) = credentials {
+        let token = base64(format!("\0{}\0{}", username, password).as_bytes());
+        session.command(&format!("AUTH PLAIN {}", token), 235)?;
+    }
+    session.command(&format!("MAIL FROM:<{}>", address_of(message.from)), 250)?;
+    for recipient in message.to {
+        session.command(&format!("RCPT TO:<{}>", address_of(recipient)), 250)?;
+    }
+    session.command("DATA", 354)?;
+
+    // Lines starting with a dot are doubled so they can't end the message
+    let mut data = String::new();
+    for line in message.format().split_terminator("\r\n") {
+        if line.starts_with('.') {
+            data.push('.');
+        }
+        data.push_str(line);
+        data.push_str("\r\n");
+    }
+    data.push_str(".\r\n");
+    session.writer.write_all(data.as_bytes())?;
+    session.expect(250)?;
+    session.command("QUIT", 221)?;
+    Ok(())
+}
+
+fn send_curl(server: &Url, credentials: Option<(&str, &str)>, message: &Message) -> Result<(), Error> {
+    let mut command = Command::new("curl");
+    command
+        .args(["--fail", "--silent", "--show-error", "--ssl-reqd", "--upload-file", "-"])
+        .arg("--url")
+        .arg(server.as_str())
+        .arg("--mail-from")
+        .arg(address_of(message.from));
+    for recipient in message.to {
+        command.arg("--mail-rcpt").arg(address_of(recipient));
+    }
+    if let Some((username, password)) = credentials {
+        command.arg("--user").arg(format!("{}:{}", username, password));
+    }
+
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|error| format_err!("Sending mail through {} requires curl: {}", server, error))?;
+    child.stdin.take().expect("stdin is piped").write_all(message.format().as_bytes())?;
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!("Could not send mail through {}: {}", server, String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// The bare address in a mailbox such as `Course Admin <admin@example.edu>`
+fn address_of(mailbox: &str) -> &str {
+    match (mailbox.rfind('<'), mailbox.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &mailbox[start + 1..end],
+        _ => mailbox.trim(),
+    }
+}
+
+/// A connection to an SMTP server
+struct Session {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Session {
+    fn command(&mut self, command: &str, code: u16) -> Result<(), Error> {
+        self.writer.write_all(format!("{}\r\n", command).as_bytes())?;
+        self.expect(code)
+    }
+
+    /// Read a possibly multi-line reply, failing unless it has a particular code
+    fn expect(&mut self, code: u16) -> Result<(), Error> {
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                bail!("The mail server closed the connection");
+            }
+            let reply = line.get(..3).and_then(|reply| reply.parse::<u16>().ok());
+            if reply != Some(code) {
+                bail!("Unexpected reply from the mail server: {}", line.trim());
+            }
+            if line.as_bytes().get(3) != Some(&b'-') {
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Run a mail server that answers each command with the next reply, returning what it was sent
+    fn server(replies: &'static [&'static str]) -> (Url, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("smtp://{}", listener.local_addr().unwrap())).unwrap();
+        let handle = thread::spawn(move || {
+            let (mut stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut received = Vec::new();
+            stream.write_all(b"220 mail.example.edu ready\r\n").unwrap();
+            let mut replies = replies.iter();
+            let mut data = false;
+            loop {
+                let mut line = String::new();
+                if reader.read_line(&mut line).unwrap() == 0 {
+                    break;
+                }
+                let line = line.trim_end_matches("\r\n").to_owned();
+                let finished = data && line == ".";
+                received.push(line.clone());
+                if data && !finished {
+                    continue;
+                }
+                data = line == "DATA";
+                match replies.next() {
+                    Some(reply) => stream.write_all(reply.as_bytes()).unwrap(),
+                    None => break,
+                }
+            }
+            received
+        });
+        (url, handle)
+    }
+
+    #[test]
+    fn mailboxes_are_reduced_to_their_addresses() {
+        assert_eq!(address_of("Course Admin <admin@example.edu>"), "admin@example.edu");
+        assert_eq!(address_of(" tutor@example.edu "), "tutor@example.edu");
+        assert_eq!(address_of("broken > <"), "broken > <");
+    }
+
+    #[test]
+    fn messages_are_sent_with_dots_doubled() {
+        let (url, handle) = server(&[
+            "250-mail.example.edu\r\n250 AUTH PLAIN\r\n",
+            "235 Authenticated\r\n",
+            "250 OK\r\n",
+            "250 OK\r\n",
+            "250 OK\r\n",
+            "354 Go ahead\r\n",
+            "250 Queued\r\n",
+            "221 Bye\r\n",
+        ]);
+        let to = vec!["Ada <ada@example.edu>".to_owned(), "grace@example.edu".to_owned()];
+        let message = Message { from: "Course <course@example.edu>", to: &to, subject: "This week", body: "Hello\n.hidden\nBye" };
+        send(&url, Some(("user", "pass")), &message).unwrap();
+        let received = handle.join().unwrap();
+
+        assert_eq!(received[..5], ["EHLO localhost", "AUTH PLAIN AHVzZXIAcGFzcw==", "MAIL FROM:<course@example.edu>", "RCPT TO:<ada@example.edu>", "RCPT TO:<grace@example.edu>"]);
+        assert!(received.contains(&"To: Ada <ada@example.edu>, grace@example.edu".to_owned()));
+        assert!(received.contains(&"Subject: This week".to_owned()));
+        let body = received.iter().position(|line| line == "Hello").unwrap();
+        assert_eq!(received[body..], ["Hello", "..hidden", "Bye", ".", "QUIT"]);
+    }
+
+    #[test]
+    fn rejections_are_reported() {
+        let (url, handle) = server(&["250 mail.example.edu\r\n", "550 No such sender\r\n"]);
+        let to = vec!["ada@example.edu".to_owned()];
+        let message = Message { from: "nobody@example.edu", to: &to, subject: "", body: "" };
+        let error = send(&url, None, &message).unwrap_err();
+        handle.join().unwrap();
+        assert_eq!(error.to_string(), "Unexpected reply from the mail server: 550 No such sender");
+    }
+
+    #[test]
+    fn other_schemes_are_unsupported() {
+        let message = Message { from: "", to: &[], subject: "", body: "" };
+        let error = send(&Url::parse("imap://example.edu").unwrap(), None, &message).unwrap_err();
+        assert_eq!(error.to_string(), "Unsupported mail server scheme imap");
+    }
+}
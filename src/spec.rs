@@ -0,0 +1,555 @@
+//! Preparation of course specifications before they are interpreted
+
+use chrono::{DateTime, FixedOffset, TimeZone};
+use failure::{bail, format_err, Error};
+use toml::Value;
+use url::Url;
+
+use crate::fetch::{fetch, is_url};
+
+use std::collections::BTreeMap;
+use std::env;
+use std::path::Path;
+
+/// Replace the fields of a specification with those of another, merging tables
+///
+/// Tables are merged key by key, while arrays and other values are replaced
+/// outright.
+pub fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Table(base), Value::Table(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+/// Parse the source of a specification, then substitute environment variables
+///
+/// Each `${NAME}` in a string is replaced by the value of the variable, taken
+/// verbatim, while comments and keys are left alone. A time from a variable is
+/// written as a string, such as `first = "${FIRST}"`. `$${` is a literal `${`.
+pub fn parse(source: &str) -> Result<Value, Error> {
+    let mut spec = toml::from_str(source)?;
+    substitute_env(&mut spec)?;
+    Ok(spec)
+}
+
+fn substitute_env(value: &mut Value) -> Result<(), Error> {
+    match value {
+        Value::Table(table) => table.iter_mut().try_for_each(|(_, value)| substitute_env(value)),
+        Value::Array(array) => array.iter_mut().try_for_each(substitute_env),
+        Value::String(text) => {
+            *text = expand(text)?;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Replace each `${NAME}` in some text with the value of the environment variable
+fn expand(text: &str) -> Result<String, Error> {
+    let mut expanded = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("${") {
+        if rest[..start].ends_with('$') {
+            expanded.push_str(&rest[..start - 1]);
+            expanded.push_str("${");
+            rest = &rest[start + 2..];
+            continue;
+        }
+        let end = start + rest[start..].find('}').ok_or(format_err!("Unterminated environment variable {}", &rest[start..]))?;
+        let name = &rest[start + 2..end];
+        let value = env::var(name).map_err(|_| format_err!("Environment variable {} is not set", name))?;
+        expanded.push_str(&rest[..start]);
+        expanded.push_str(&value);
+        rest = &rest[end + 1..];
+    }
+    expanded.push_str(rest);
+    Ok(expanded)
+}
+
+/// Combine a specification with the files it includes
+///
+/// Included files are relative to `base` and may include others in turn. The
+/// arrays of every file are joined in order and their tables are combined, but
+/// other fields may only be set once. The locations of every included file are
+/// returned along with the combined specification.
+pub fn include(spec: Value, base: &str) -> Result<(Value, Vec<String>), Error> {
+    let mut sources = Vec::new();
+    let spec = include_within(spec, base, &mut Vec::new(), &mut sources)?;
+    Ok((spec, sources))
+}
+
+fn include_within(mut spec: Value, base: &str, including: &mut Vec<String>, sources: &mut Vec<String>) -> Result<Value, Error> {
+    let includes = match spec.as_table_mut().and_then(|table| table.remove("include")) {
+        Some(Value::Array(includes)) => includes,
+        Some(_) => bail!("Includes must be an array of locations"),
+        None => return Ok(spec),
+    };
+
+    for location in includes {
+        let location = resolve(location.as_str().ok_or(format_err!("Includes must be an array of locations"))?, base)?;
+        if including.contains(&location) {
+            bail!("{} is included in a cycle", location);
+        }
+
+        let included = parse(&fetch(&location, "")?).map_err(|error| format_err!("{}: {}", location, error))?;
+        including.push(location.clone());
+        let included = include_within(included, &directory(&location), including, sources)?;
+        including.pop();
+
+        combine(&mut spec, included, "").map_err(|error| format_err!("{}: {}", location, error))?;
+        sources.push(location);
+    }
+    Ok(spec)
+}
+
+/// Add the fields of an included file to a specification
+fn combine(spec: &mut Value, included: Value, path: &str) -> Result<(), Error> {
+    match (spec, included) {
+        (Value::Table(spec), Value::Table(included)) => {
+            for (key, value) in included {
+                let path = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match spec.get_mut(&key) {
+                    Some(existing) => combine(existing, value, &path)?,
+                    None => {
+                        spec.insert(key, value);
+                    }
+                }
+            }
+            Ok(())
+        }
+        (Value::Array(spec), Value::Array(included)) => {
+            spec.extend(included);
+            Ok(())
+        }
+        _ => bail!("{} is already set by the including file", path),
+    }
+}
+
+/// Merge a specification over the specifications it extends
+///
+/// The location of the parent is relative to `base`, and a parent may extend
+/// another in turn. The locations of every parent and the files they include
+/// are returned along with the merged specification.
+pub fn extend(mut spec: Value, base: &str) -> Result<(Value, Vec<String>), Error> {
+    let mut parents = Vec::new();
+    let mut base = base.to_owned();
+    while let Some(extends) = spec.as_table_mut().and_then(|table| table.remove("extends")) {
+        let location = extends.as_str().ok_or(format_err!("A course must extend the location of another"))?;
+        let location = resolve(location, &base)?;
+        if parents.contains(&location) {
+            bail!("{} is extended in a cycle", location);
+        }
+
+        let parent = parse(&fetch(&location, "")?).map_err(|error| format_err!("{}: {}", location, error))?;
+        let (mut parent, included) = include(parent, &directory(&location))?;
+        let extended = parent.as_table_mut().and_then(|table| table.remove("extends"));
+        merge(&mut parent, spec);
+        if let (Some(extended), Some(table)) = (extended, parent.as_table_mut()) {
+            table.insert("extends".to_owned(), extended);
+        }
+        spec = parent;
+
+        base = directory(&location);
+        parents.push(location);
+        parents.extend(included);
+    }
+    Ok((spec, parents))
+}
+
+/// The base against which locations in a file are resolved
+pub fn directory(location: &str) -> String {
+    if is_url(location) {
+        location.to_owned()
+    } else {
+        Path::new(location).parent().map(|parent| parent.to_string_lossy().into_owned()).unwrap_or_default()
+    }
+}
+
+/// The location of a file relative to a directory or the URL of another file
+pub fn resolve(location: &str, base: &str) -> Result<String, Error> {
+    if is_url(location) {
+        Ok(location.to_owned())
+    } else if is_url(base) {
+        Ok(Url::parse(base)?.join(location)?.into_string())
+    } else {
+        let path = Path::new(base).join(location);
+        Ok(path.canonicalize().unwrap_or(path).to_string_lossy().into_owned())
+    }
+}
+
+/// Apply the offering for a term to a specification
+///
+/// The weeks of the offering replace the starts of the weeks of the course, and
+/// every time in the course moves with the week it falls in, keeping its local
+/// time of day. The other fields of the offering then override those of the
+/// course.
+pub fn select_offering(spec: &mut Value, term: &str) -> Result<(), Error> {
+    let offerings = match spec.get("offering") {
+        Some(Value::Array(offerings)) => offerings.clone(),
+        Some(_) => bail!("Offerings must be an array of tables"),
+        None => Vec::new(),
+    };
+    let terms = offerings.iter().filter_map(|offering| offering.get("term")?.as_str()).collect::<Vec<_>>();
+    if terms.is_empty() {
+        bail!("No offering for term {}, as the course has no offerings", term);
+    }
+    let mut offering = offerings
+        .iter()
+        .find(|offering| offering.get("term").and_then(Value::as_str) == Some(term))
+        .cloned()
+        .ok_or(format_err!("No offering for term {}, expected one of {}", term, terms.join(", ")))?;
+
+    let weeks = match &mut offering {
+        Value::Table(table) => {
+            table.remove("term");
+            table.remove("weeks")
+        }
+        _ => bail!("Offerings must be tables"),
+    };
+
+    if let Some(weeks) = weeks {
+        let new = weeks
+            .as_array()
+            .ok_or(format_err!("The weeks of an offering must be an array of times"))?
+            .iter()
+            .map(datetime)
+            .collect::<Option<Vec<_>>>()
+            .ok_or(format_err!("The weeks of an offering must be times with offsets"))?;
+        let old = spec
+            .get("week")
+            .and_then(Value::as_array)
+            .map(|weeks| weeks.iter().filter_map(|week| week.get("start").and_then(datetime)).collect::<Vec<_>>())
+            .unwrap_or_default();
+        if old.len() != new.len() {
+            bail!("The offering for {} has {} weeks but the course has {}", term, new.len(), old.len());
+        }
+
+        if let Value::Table(table) = spec {
+            for (key, value) in table.iter_mut() {
+                if key != "offering" {
+                    move_times(value, &old, &new)?;
+                }
+            }
+        }
+    }
+
+    merge(spec, offering);
+    Ok(())
+}
+
+/// Move every time in a value from its week in the old weeks to the same week in the new
+fn move_times(value: &mut Value, old: &[DateTime<FixedOffset>], new: &[DateTime<FixedOffset>]) -> Result<(), Error> {
+    match value {
+        Value::Table(table) => table.iter_mut().try_for_each(|(_, value)| move_times(value, old, new)),
+        Value::Array(array) => array.iter_mut().try_for_each(|value| move_times(value, old, new)),
+        Value::Datetime(_) => {
+            let time = match datetime(value) {
+                Some(time) => time,
+                None => return Ok(()),
+            };
+            let week = old.iter().rposition(|start| *start <= time).unwrap_or(0);
+            let local = new[week].naive_local() + (time.naive_local() - old[week].naive_local());
+            let moved = new[week]
+                .offset()
+                .from_local_datetime(&local)
+                .single()
+                .ok_or(format_err!("{} has no equivalent in the offering", time))?;
+            *value = Value::Datetime(moved.to_rfc3339().parse().map_err(|_| format_err!("Unable to represent {}", moved))?);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The time of a TOML datetime with an offset
+fn datetime(value: &Value) -> Option<DateTime<FixedOffset>> {
+    DateTime::parse_from_rfc3339(&value.as_datetime()?.to_string()).ok()
+}
+
+/// Replace references to the variables of a specification with their values
+///
+/// Variables are defined in the `vars` table and referred to as `{{name}}` in
+/// any text of the specification.
+pub fn interpolate(spec: &mut Value) -> Result<(), Error> {
+    let vars = match spec.as_table_mut().and_then(|table| table.remove("vars")) {
+        Some(Value::Table(vars)) => vars,
+        Some(_) => bail!("Variables must be a table"),
+        None => return Ok(()),
+    };
+    let vars = vars
+        .into_iter()
+        .map(|(name, value)| match value {
+            Value::String(value) => Ok((name, value)),
+            Value::Table(_) | Value::Array(_) => Err(format_err!("Variable {} must be text, a number, or a date", name)),
+            value => Ok((name, value.to_string())),
+        })
+        .collect::<Result<BTreeMap<_, _>, Error>>()?;
+    substitute(spec, &|name| vars.get(name).cloned())
+}
+
+/// Replace every `{{name}}` in the text of a value, failing for unknown names
+fn substitute(value: &mut Value, lookup: &dyn Fn(&str) -> Option<String>) -> Result<(), Error> {
+    match value {
+        Value::Table(table) => table.iter_mut().try_for_each(|(_, value)| substitute(value, lookup)),
+        Value::Array(array) => array.iter_mut().try_for_each(|value| substitute(value, lookup)),
+        Value::String(text) => {
+            let mut result = String::new();
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("{{") {
+                let end = match rest[start..].find("}}") {
+                    Some(end) => start + end,
+                    None => break,
+                };
+                let name = rest[start + 2..end].trim();
+                let value = lookup(name).ok_or(format_err!("Unknown variable {} in \"{}\"", name, text))?;
+                result.push_str(&rest[..start]);
+                result.push_str(&value);
+                rest = &rest[end + 2..];
+            }
+            result.push_str(rest);
+            *text = result;
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+/// The fields of sessions that may be given defaults
+const DEFAULT_FIELDS: &[&str] = &["location", "duration", "presenters", "reminders"];
+
+/// Fill in the fields that sessions leave out
+///
+/// Defaults for the kind of a session, from the `kind` table, take precedence
+/// over those for every session, from the `defaults` table.
+pub fn apply_defaults(spec: &mut Value) -> Result<(), Error> {
+    let (defaults, kinds) = match spec.as_table_mut() {
+        Some(table) => (table.remove("defaults"), table.remove("kind")),
+        None => return Ok(()),
+    };
+    let defaults = defaults_table(defaults, "Defaults")?;
+    let kinds = match kinds {
+        Some(Value::Table(kinds)) => kinds
+            .into_iter()
+            .map(|(kind, defaults)| Ok((kind.clone(), defaults_table(Some(defaults), &format!("Defaults for {}", kind))?)))
+            .collect::<Result<BTreeMap<_, _>, Error>>()?,
+        Some(_) => bail!("Kinds must be a table of defaults keyed by kind"),
+        None => BTreeMap::new(),
+    };
+    if defaults.is_empty() && kinds.is_empty() {
+        return Ok(());
+    }
+
+    let fill = |sessions: Option<&mut Value>| {
+        for session in sessions.and_then(Value::as_array_mut).into_iter().flatten().filter_map(Value::as_table_mut) {
+            let kind = session.get("kind").and_then(Value::as_str).and_then(|kind| kinds.get(kind)).cloned().unwrap_or_default();
+            for (field, value) in kind.iter().chain(&defaults) {
+                session.entry(field.clone()).or_insert_with(|| value.clone());
+            }
+        }
+    };
+    fill(spec.get_mut("session"));
+    if let Some(weeks) = spec.get_mut("week").and_then(Value::as_array_mut) {
+        for week in weeks {
+            fill(week.get_mut("session"));
+        }
+    }
+    Ok(())
+}
+
+/// Check that a table of defaults only gives fields that may have defaults
+fn defaults_table(defaults: Option<Value>, name: &str) -> Result<toml::value::Table, Error> {
+    let defaults = match defaults {
+        Some(Value::Table(defaults)) => defaults,
+        Some(_) => bail!("{} must be a table", name),
+        None => return Ok(Default::default()),
+    };
+    if let Some(field) = defaults.keys().find(|field| !DEFAULT_FIELDS.contains(&field.as_str())) {
+        bail!("Unknown field {} in {}, expected one of {}", field, name.to_lowercase(), DEFAULT_FIELDS.join(", "));
+    }
+    Ok(defaults)
+}
+
+/// Replace references to weeks by their labels with their numbers
+pub fn resolve_week_labels(spec: &mut Value) -> Result<(), Error> {
+    let first = match spec.get("week_numbering").and_then(Value::as_str) {
+        Some("zero-based") => 0,
+        _ => 1,
+    };
+    let mut labels = BTreeMap::new();
+    for (index, week) in spec.get("week").and_then(Value::as_array).into_iter().flatten().enumerate() {
+        if let Some(label) = week.get("label").and_then(Value::as_str) {
+            if labels.insert(label.to_owned(), index as i64 + first).is_some() {
+                bail!("More than one week is labelled {}", label);
+            }
+        }
+    }
+
+    resolve_labels(spec, &labels)
+}
+
+/// Replace references to weeks by their labels with the numbers given for each label
+pub fn resolve_labels(spec: &mut Value, labels: &BTreeMap<String, i64>) -> Result<(), Error> {
+    let resolve = |table: &mut Value| -> Result<(), Error> {
+        for week in table.get_mut("weeks").and_then(Value::as_array_mut).into_iter().flatten() {
+            if let Value::String(label) = week {
+                let number = labels.get(label.as_str()).ok_or(format_err!("No week is labelled {}", label))?;
+                *week = Value::Integer(*number);
+            }
+        }
+        Ok(())
+    };
+    for key in &["session", "quiz", "office_hours"] {
+        for table in spec.get_mut(*key).and_then(Value::as_array_mut).into_iter().flatten() {
+            resolve(table)?;
+        }
+    }
+    for assignment in spec.get_mut("assignment").and_then(Value::as_array_mut).into_iter().flatten() {
+        for presentation in assignment.get_mut("presentation").and_then(Value::as_array_mut).into_iter().flatten() {
+            resolve(presentation)?;
+        }
+    }
+    Ok(())
+}
+
+/// Move single sessions of a series given as [[session.extra]] into session.additional
+///
+/// A table under extra holds iCalendar properties, whereas an array holds the sessions.
+pub fn separate_instances(spec: &mut Value) -> Result<(), Error> {
+    for session in spec.get_mut("session").and_then(Value::as_array_mut).into_iter().flatten() {
+        let session = match session.as_table_mut() {
+            Some(session) => session,
+            None => continue,
+        };
+        let instances = match session.remove("extra") {
+            Some(Value::Array(instances)) => instances,
+            Some(extra) => {
+                session.insert("extra".to_owned(), extra);
+                continue;
+            }
+            None => continue,
+        };
+        match session.entry("additional").or_insert_with(|| Value::Array(Vec::new())) {
+            Value::Array(additional) => additional.extend(instances),
+            _ => bail!("session.additional must be an array of sessions"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs::{create_dir_all, read_to_string, remove_dir_all, write};
+    use std::path::PathBuf;
+
+    /// Write files into a directory of their own, returning the directory
+    fn files(name: &str, files: &[(&str, &str)]) -> PathBuf {
+        let directory = env::temp_dir().join(format!("course-calendar-{}-{}", name, std::process::id()));
+        create_dir_all(&directory).unwrap();
+        for (file, contents) in files {
+            write(directory.join(file), contents).unwrap();
+        }
+        directory
+    }
+
+    fn load(directory: &Path, file: &str) -> Value {
+        parse(&read_to_string(directory.join(file)).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn includes_join_arrays_in_order() {
+        let directory = files(
+            "include",
+            &[
+                ("course.toml", "include = [\"first.toml\"]\ncode = \"COMP1000\"\n[[week]]\nstart = 1\n"),
+                ("first.toml", "include = [\"second.toml\"]\n[[week]]\nstart = 2\n[strings]\nWeek = \"Semaine\"\n"),
+                ("second.toml", "[[week]]\nstart = 3\n[strings]\nTBC = \"A confirmer\"\n"),
+            ],
+        );
+        let (spec, sources) = include(load(&directory, "course.toml"), &directory.to_string_lossy()).unwrap();
+        remove_dir_all(&directory).unwrap();
+        let starts = spec["week"].as_array().unwrap().iter().map(|week| week["start"].as_integer().unwrap()).collect::<Vec<_>>();
+        assert_eq!(starts, vec![1, 2, 3]);
+        assert_eq!(spec["strings"].as_table().unwrap().len(), 2);
+        assert!(spec.get("include").is_none());
+        assert_eq!(sources.len(), 2);
+    }
+
+    #[test]
+    fn includes_may_not_set_fields_twice() {
+        let directory = files("include-twice", &[("course.toml", "include = [\"other.toml\"]\ncode = \"A\"\n"), ("other.toml", "code = \"B\"\n")]);
+        let error = include(load(&directory, "course.toml"), &directory.to_string_lossy()).unwrap_err();
+        remove_dir_all(&directory).unwrap();
+        assert!(error.to_string().contains("code is already set"), "{}", error);
+    }
+
+    #[test]
+    fn include_cycles_are_rejected() {
+        let directory = files("include-cycle", &[("a.toml", "include = [\"b.toml\"]\n"), ("b.toml", "include = [\"a.toml\"]\n")]);
+        let error = include(load(&directory, "a.toml"), &directory.to_string_lossy()).unwrap_err();
+        remove_dir_all(&directory).unwrap();
+        assert!(error.to_string().contains("cycle"), "{}", error);
+    }
+
+    #[test]
+    fn extensions_override_their_parents() {
+        let directory = files(
+            "extend",
+            &[
+                ("base.toml", "code = \"COMP1000\"\nname = \"Base\"\nprivate = [\"notes\"]\n[strings]\nWeek = \"Semaine\"\n"),
+                ("course.toml", "extends = \"base.toml\"\nname = \"Derived\"\nprivate = [\"location\"]\n[strings]\nTBC = \"A confirmer\"\n"),
+            ],
+        );
+        let (spec, parents) = extend(load(&directory, "course.toml"), &directory.to_string_lossy()).unwrap();
+        remove_dir_all(&directory).unwrap();
+        assert_eq!(spec["code"].as_str(), Some("COMP1000"));
+        assert_eq!(spec["name"].as_str(), Some("Derived"));
+        assert_eq!(spec["private"].as_array().unwrap().len(), 1);
+        assert_eq!(spec["strings"].as_table().unwrap().len(), 2);
+        assert!(spec.get("extends").is_none());
+        assert_eq!(parents.len(), 1);
+    }
+
+    #[test]
+    fn variables_are_interpolated_into_text() {
+        let mut spec: Value = toml::from_str(
+            "name = \"{{ subject }} in {{year}}\"\n[[week]]\ntopic = \"{{subject}}\"\n[vars]\nsubject = \"Computing\"\nyear = 2024\n",
+        )
+        .unwrap();
+        interpolate(&mut spec).unwrap();
+        assert_eq!(spec["name"].as_str(), Some("Computing in 2024"));
+        assert_eq!(spec["week"][0]["topic"].as_str(), Some("Computing"));
+        assert!(spec.get("vars").is_none());
+
+        let mut spec: Value = toml::from_str("name = \"{{missing}}\"\n[vars]\n").unwrap();
+        assert!(interpolate(&mut spec).is_err());
+    }
+
+    #[test]
+    fn environment_values_are_taken_verbatim() {
+        env::set_var("COURSE_CALENDAR_TEST_NAME", "A \"quoted\" \\ name\nlink = \"injected\"");
+        let spec = parse(
+            r#"
+            # ${COURSE_CALENDAR_TEST_UNSET} in a comment is ignored
+            name = "${COURSE_CALENDAR_TEST_NAME}"
+            literal = "$${HOME}"
+            "#,
+        )
+        .unwrap();
+        assert_eq!(spec["name"].as_str(), Some("A \"quoted\" \\ name\nlink = \"injected\""));
+        assert_eq!(spec["literal"].as_str(), Some("${HOME}"));
+        assert!(spec.get("link").is_none());
+        assert!(parse("name = \"${COURSE_CALENDAR_TEST_UNSET}\"").is_err());
+    }
+}
@@ -0,0 +1,363 @@
+//! Division of a course into several calendars
+
+use failure::{bail, format_err, Error};
+
+use crate::course::Course;
+use crate::filter::{Assignments, Filter};
+
+use std::collections::BTreeSet;
+
+/// How to divide a course into several calendars
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Split {
+    /// One calendar for each presentation group, without the presentations of other groups
+    Group,
+    /// One calendar for each presenter, with the sessions they run and the deadlines
+    Presenter,
+    /// One calendar for each location, with the events held there
+    Room,
+    /// One calendar for each teaching week
+    Week,
+    /// One calendar for each kind of session, and one for all assessments
+    Kind,
+    /// One calendar for each stream, with the events outside of any stream
+    Stream,
+}
+
+impl Split {
+    /// The split named by `--split`
+    pub fn from_name(name: &str) -> Result<Self, Error> {
+        match name {
+            "group" => Ok(Split::Group),
+            "presenter" => Ok(Split::Presenter),
+            "room" => Ok(Split::Room),
+            "week" => Ok(Split::Week),
+            "kind" => Ok(Split::Kind),
+            "stream" => Ok(Split::Stream),
+            _ => Err(format_err!("Unknown split {}, expected group, presenter, room, week, kind, or stream", name)),
+        }
+    }
+
+    /// The option that selects the split, for messages
+    pub fn flag(self) -> &'static str {
+        match self {
+            Split::Group => "--per-group",
+            Split::Presenter => "--per-presenter",
+            Split::Room => "--per-room",
+            Split::Week => "--split week",
+            Split::Kind => "--split kind",
+            Split::Stream => "--split stream",
+        }
+    }
+
+    /// The placeholder for the part in file name templates
+    pub fn key(self) -> &'static str {
+        match self {
+            Split::Group => "group",
+            Split::Presenter => "presenter",
+            Split::Room => "room",
+            Split::Week => "week",
+            Split::Kind => "kind",
+            Split::Stream => "stream",
+        }
+    }
+}
+
+/// One of the calendars into which a course is divided
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub name: String,
+    /// The name of the file, without its extension
+    pub stem: String,
+    /// The calendar includes the events matching any of these filters
+    pub filters: Vec<Filter>,
+}
+
+impl Part {
+    fn new(name: &str, filters: Vec<Filter>) -> Self {
+        Part { name: name.to_owned(), stem: sanitize(name), filters }
+    }
+}
+
+/// Divide the events of a course that match a filter into parts
+pub fn parts(course: &Course, filter: &Filter, split: Split) -> Vec<Part> {
+    match split {
+        Split::Group => course
+            .groups()
+            .into_iter()
+            .map(|group| {
+                let mut filter = filter.clone();
+                filter.groups.push(group.to_owned());
+                Part::new(group, vec![filter])
+            })
+            .collect(),
+        Split::Presenter => {
+            let presenters = course
+                .events()
+                .filter(|event| filter.matches(event))
+                .flat_map(|event| event.presenters().collect::<Vec<_>>())
+                .collect::<BTreeSet<_>>();
+            presenters
+                .into_iter()
+                .map(|presenter| {
+                    let mut sessions = filter.clone();
+                    sessions.presenters = vec![presenter.to_owned()];
+                    let mut filters = vec![sessions];
+                    if filter.kinds.is_empty() || filter.kinds.iter().any(|kind| kind == "submission") {
+                        let mut deadlines = filter.clone();
+                        deadlines.kinds = vec!["submission".to_owned()];
+                        filters.push(deadlines);
+                    }
+                    Part::new(presenter, filters)
+                })
+                .collect()
+        }
+        Split::Room => {
+            let rooms = course.events().filter(|event| filter.matches(event)).filter_map(|event| event.room()).collect::<BTreeSet<_>>();
+            rooms
+                .into_iter()
+                .map(|room| {
+                    let mut filter = filter.clone();
+                    filter.locations.push(room.to_owned());
+                    Part::new(room, vec![filter])
+                })
+                .collect()
+        }
+        Split::Week => (0..course.week_count())
+            .map(|week| course.week_number(week))
+            .filter(|number| filter.includes_week(*number))
+            .map(|number| {
+                let mut filter = filter.clone();
+                filter.weeks = vec![(number, number)];
+                Part { name: format!("Week {}", number), stem: format!("week-{:02}", number), filters: vec![filter] }
+            })
+            .collect(),
+        Split::Kind => {
+            let kinds = course
+                .events()
+                .filter(|event| !event.is_assignment() && filter.matches(event))
+                .map(|event| event.kind())
+                .collect::<BTreeSet<_>>();
+            let mut parts = kinds
+                .into_iter()
+                .map(|kind| {
+                    let mut filter = filter.clone();
+                    filter.kinds = vec![kind.to_owned()];
+                    filter.assignments = Assignments::Exclude;
+                    Part::new(kind, vec![filter])
+                })
+                .collect::<Vec<_>>();
+            if course.events().any(|event| event.is_assignment() && filter.matches(&event)) {
+                let mut deadlines = filter.clone();
+                deadlines.assignments = Assignments::Only;
+                parts.push(Part::new("deadlines", vec![deadlines]));
+            }
+            parts
+        }
+        Split::Stream => {
+            let streams = course.events().filter(|event| filter.matches(event)).filter_map(|event| event.stream()).collect::<BTreeSet<_>>();
+            streams
+                .into_iter()
+                .map(|stream| {
+                    let mut filter = filter.clone();
+                    filter.streams = vec![stream.to_owned()];
+                    Part::new(stream, vec![filter])
+                })
+                .collect()
+        }
+    }
+}
+
+/// Fill in the placeholders of a file name template, such as `{code}-{stream}.ics`
+///
+/// Values are made safe to use in a file name, and a placeholder without a
+/// value is an error.
+pub fn file_name(template: &str, values: &[(&str, &str)]) -> Result<String, Error> {
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        let close = rest[open..].find('}').ok_or(format_err!("Unclosed placeholder in file name template {}", template))? + open;
+        let key = &rest[open + 1..close];
+        let value = match values.iter().find(|(name, _)| *name == key) {
+            Some((_, value)) => value,
+            None => {
+                let keys = values.iter().map(|(name, _)| format!("{{{}}}", name)).collect::<Vec<_>>();
+                bail!("Unknown placeholder {{{}}} in file name template, expected one of {}", key, keys.join(", "));
+            }
+        };
+        name.push_str(&rest[..open]);
+        name.push_str(&sanitize(value));
+        rest = &rest[close + 1..];
+    }
+    name.push_str(rest);
+    Ok(name)
+}
+
+/// Replace the characters that would be awkward in a file name
+fn sanitize(text: &str) -> String {
+    text.chars().map(|c| if c.is_alphanumeric() || c == '-' || c == '_' { c } else { '-' }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Two weeks of teaching, where Ada gives the lectures and takes over a tutorial in the second week
+    const TEACHING: &str = r#"
+        version = 2
+        code = "COMP1000"
+        name = "Testing"
+        link = "https://example.edu/comp1000"
+
+        [[week]]
+        start = 2024-02-12T00:00:00+11:00
+
+        [[week]]
+        start = 2024-02-19T00:00:00+11:00
+
+        [[session]]
+        first = 2024-02-12T10:00:00+11:00
+        kind = "lecture"
+        location = "Quad 1001"
+        presenters = ["Ada"]
+        duration = 3600
+        weeks = [1, 2]
+
+        [[session]]
+        first = 2024-02-13T10:00:00+11:00
+        kind = "tutorial"
+        stream = "T10A"
+        location = "Quad G040"
+        presenters = ["Grace"]
+        duration = 3600
+        weeks = [1, 2]
+
+        [[session]]
+        first = 2024-02-20T14:00:00+11:00
+        kind = "tutorial"
+        stream = "T14A"
+        location = "Quad G040"
+        presenters = ["Ada"]
+        duration = 3600
+        weeks = [2]
+
+        [[assignment]]
+        name = "Project"
+        link = "https://example.edu/project"
+
+        [[assignment.submission]]
+        time = 2024-02-16T17:00:00+11:00
+        name = "Plan"
+
+        [[assignment.presentation]]
+        name = "Pitch"
+        session = "tutorial"
+        weeks = [2]
+        slot = 1800
+        groups = ["Red", "Blue"]
+        "#;
+
+    /// The stem of each part, with the events in it
+    fn divide(split: Split, filter: &Filter) -> Vec<(String, Vec<String>)> {
+        let mut course = Course::from_value(toml::from_str(TEACHING).unwrap()).unwrap();
+        course.generate_repeats().unwrap();
+        parts(&course, filter, split)
+            .into_iter()
+            .map(|part| {
+                let events = course
+                    .events()
+                    .filter(|event| part.filters.iter().any(|filter| filter.matches(event)))
+                    .map(|event| format!("{} {}", event.name(), event.start().format("%a %-d %H:%M")))
+                    .collect();
+                (part.stem, events)
+            })
+            .collect()
+    }
+
+    fn events(parts: &[(String, Vec<String>)], stem: &str) -> Vec<String> {
+        parts.iter().find(|(part, _)| part == stem).map(|(_, events)| events.clone()).unwrap_or_default()
+    }
+
+    #[test]
+    fn splits_are_named() {
+        assert_eq!(Split::from_name("presenter").unwrap(), Split::Presenter);
+        assert_eq!(Split::Presenter.flag(), "--per-presenter");
+        assert_eq!(Split::from_name("tutor").unwrap_err().to_string(), "Unknown split tutor, expected group, presenter, room, week, kind, or stream");
+    }
+
+    #[test]
+    fn presenters_get_their_sessions_and_every_deadline() {
+        let parts = divide(Split::Presenter, &Filter::default());
+        assert_eq!(parts.iter().map(|(stem, _)| stem.as_str()).collect::<Vec<_>>(), ["Ada", "Grace"]);
+        assert_eq!(events(&parts, "Ada"), ["lecture Mon 12 10:00", "Project: Plan Fri 16 17:00", "lecture Mon 19 10:00", "tutorial Tue 20 14:00"]);
+
+        // Without submissions in the filter, presenters get only their sessions
+        let lectures = Filter { kinds: vec!["lecture".to_owned()], ..Filter::default() };
+        assert_eq!(divide(Split::Presenter, &lectures), [("Ada".to_owned(), vec!["lecture Mon 12 10:00".to_owned(), "lecture Mon 19 10:00".to_owned()])]);
+    }
+
+    #[test]
+    fn groups_miss_only_the_presentations_of_other_groups() {
+        let parts = divide(Split::Group, &Filter::default());
+        assert_eq!(parts.iter().map(|(stem, _)| stem.as_str()).collect::<Vec<_>>(), ["Red", "Blue"]);
+        let red = events(&parts, "Red");
+        assert!(red.contains(&"Project: Red Pitch Tue 20 10:00".to_owned()));
+        assert!(!red.iter().any(|event| event.contains("Blue")));
+        assert_eq!(red.len(), 7);
+    }
+
+    #[test]
+    fn rooms_hold_every_event_held_in_them() {
+        let parts = divide(Split::Room, &Filter::default());
+        assert_eq!(events(&parts, "Quad-1001"), ["lecture Mon 12 10:00", "lecture Mon 19 10:00"]);
+        assert_eq!(events(&parts, "Quad-G040").len(), 5);
+        assert_eq!(parts.len(), 2);
+    }
+
+    #[test]
+    fn names_are_sanitized_for_files() {
+        assert_eq!(sanitize("Quad G040/G041"), "Quad-G040-G041");
+        assert_eq!(sanitize("Ada_Lovelace-2"), "Ada_Lovelace-2");
+    }
+
+    #[test]
+    fn weeks_are_calendars_of_their_own() {
+        let parts = divide(Split::Week, &Filter::default());
+        assert_eq!(events(&parts, "week-01"), ["lecture Mon 12 10:00", "tutorial Tue 13 10:00", "Project: Plan Fri 16 17:00"]);
+        assert_eq!(events(&parts, "week-02").len(), 5);
+
+        let second = Filter { weeks: vec![(2, 3)], ..Filter::default() };
+        let parts = divide(Split::Week, &second);
+        assert_eq!(parts.iter().map(|(stem, _)| stem.as_str()).collect::<Vec<_>>(), ["week-02"]);
+    }
+
+    #[test]
+    fn assessments_share_a_calendar_apart_from_the_kinds_of_session() {
+        let parts = divide(Split::Kind, &Filter::default());
+        assert_eq!(parts.iter().map(|(stem, _)| stem.as_str()).collect::<Vec<_>>(), ["lecture", "tutorial", "deadlines"]);
+        assert_eq!(events(&parts, "tutorial"), ["tutorial Tue 13 10:00", "tutorial Tue 20 10:00", "tutorial Tue 20 14:00"]);
+        assert_eq!(events(&parts, "deadlines"), ["Project: Plan Fri 16 17:00", "Project: Red Pitch Tue 20 10:00", "Project: Blue Pitch Tue 20 10:30"]);
+
+        let sessions = Filter { assignments: Assignments::Exclude, ..Filter::default() };
+        assert_eq!(divide(Split::Kind, &sessions).len(), 2);
+    }
+
+    #[test]
+    fn streams_keep_the_events_outside_any_stream() {
+        let parts = divide(Split::Stream, &Filter::default());
+        assert_eq!(events(&parts, "T14A"), ["lecture Mon 12 10:00", "Project: Plan Fri 16 17:00", "lecture Mon 19 10:00", "tutorial Tue 20 14:00"]);
+        assert!(!events(&parts, "T10A").contains(&"tutorial Tue 20 14:00".to_owned()));
+    }
+
+    #[test]
+    fn file_names_fill_in_placeholders() {
+        let values = [("code", "COMP1000"), ("stream", "T10A/online")];
+        assert_eq!(file_name("{code}-{stream}.ics", &values).unwrap(), "COMP1000-T10A-online.ics");
+        assert_eq!(file_name("calendar.ics", &values).unwrap(), "calendar.ics");
+        assert_eq!(
+            file_name("{code}-{room}.ics", &values).unwrap_err().to_string(),
+            "Unknown placeholder {room} in file name template, expected one of {code}, {stream}"
+        );
+        assert_eq!(file_name("{code", &values).unwrap_err().to_string(), "Unclosed placeholder in file name template {code");
+    }
+}
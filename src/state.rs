@@ -0,0 +1,104 @@
+//! Revision tracking for events across repeated generation
+
+use failure::Error;
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fs::{read_to_string, write};
+use std::io::ErrorKind;
+use std::path::Path;
+
+/// The revisions of previously generated events, keyed by UID
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct State {
+    #[serde(rename = "event", default)]
+    events: BTreeMap<String, Revision>,
+    /// The revisions last sent to each destination of changes, such as a publishing target
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    delivered: BTreeMap<String, BTreeMap<String, Revision>>,
+}
+
+/// The most recently generated revision of an event
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Revision {
+    /// A hash of the content of the event
+    pub hash: String,
+    pub sequence: u32,
+    /// When the content last changed, in iCalendar UTC format
+    pub modified: String,
+    /// The summary of the event, kept so that it can be cancelled
+    #[serde(default)]
+    pub summary: String,
+    /// The start of the event in iCalendar UTC format
+    #[serde(default)]
+    pub start: String,
+}
+
+impl State {
+    /// Load a state file, or start afresh if it doesn't exist yet
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        match read_to_string(path) {
+            Ok(source) => Ok(toml::from_str(&source)?),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(State::default()),
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        write(path, toml::to_string(self)?)?;
+        Ok(())
+    }
+
+    /// The recorded revision of an event
+    pub fn revision(&self, uid: &str) -> Option<&Revision> {
+        self.events.get(uid)
+    }
+
+    /// Record the current revision of an event, returning whether it is new or changed
+    ///
+    /// The sequence of a changed event is bumped past its previous revision.
+    pub fn update(&mut self, uid: &str, mut revision: Revision) -> bool {
+        match self.events.get(uid) {
+            Some(previous) if previous.hash == revision.hash => false,
+            previous => {
+                revision.sequence = previous.map(|previous| previous.sequence + 1).unwrap_or(0);
+                self.events.insert(uid.to_owned(), revision);
+                true
+            }
+        }
+    }
+
+    /// The revisions last sent to a destination, keyed by UID
+    pub fn delivered(&self, destination: &str) -> impl Iterator<Item = (&String, &Revision)> {
+        self.delivered.get(destination).into_iter().flatten()
+    }
+
+    /// Record that a destination has the current revision of an event, or none if it was removed
+    pub fn deliver(&mut self, destination: &str, uid: &str) {
+        let delivered = self.delivered.entry(destination.to_owned()).or_default();
+        match self.events.get(uid) {
+            Some(revision) => {
+                delivered.insert(uid.to_owned(), revision.clone());
+            }
+            None => {
+                delivered.remove(uid);
+            }
+        }
+    }
+
+    /// Forget events other than those listed, returning the removed revisions
+    pub fn retain(&mut self, uids: &[String]) -> Vec<(String, Revision)> {
+        let (kept, removed) = std::mem::take(&mut self.events)
+            .into_iter()
+            .partition(|(uid, _)| uids.contains(uid));
+        self.events = kept;
+        removed.into_iter().collect()
+    }
+}
+
+/// A 64-bit FNV-1a hash, which unlike the standard hasher is stable across releases
+pub fn hash(text: &str) -> u64 {
+    text.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
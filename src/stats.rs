@@ -0,0 +1,327 @@
+//! Summary figures and workload of a course
+
+use crate::course::{Course, Event};
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// Totals across the events of a course
+#[derive(Debug, Clone, Default)]
+pub struct Stats {
+    /// Contact hours for each kind of session
+    pub hours: BTreeMap<String, f64>,
+    pub assessments: usize,
+    /// The sum of the values of the assignments that have one
+    pub weight: u64,
+    /// The week with the most contact hours, and those hours
+    pub busiest: Option<(usize, f64)>,
+    /// The number of sessions run by each presenter
+    pub presenters: BTreeMap<String, usize>,
+}
+
+impl Stats {
+    pub fn new(course: &Course, events: &[Event]) -> Self {
+        let mut stats = Stats::default();
+        let values = course.assignment_values();
+        stats.assessments = values.len();
+        stats.weight = values.iter().filter_map(|(_, value)| *value).sum();
+
+        let mut weeks = BTreeMap::new();
+        // Presentations are held during sessions, so only sessions are counted
+        for event in events.iter().filter(|event| !event.is_assignment()) {
+            for presenter in event.presenters() {
+                *stats.presenters.entry(presenter.to_owned()).or_insert(0) += 1;
+            }
+            let hours = contact_hours(event);
+            *stats.hours.entry(event.kind().to_owned()).or_insert(0.0) += hours;
+            if let Some(week) = event.week() {
+                *weeks.entry(week).or_insert(0.0) += hours;
+            }
+        }
+
+        // Prefer the earliest of equally busy weeks
+        stats.busiest = weeks.into_iter().fold(None, |busiest, (week, hours)| match busiest {
+            Some((_, most)) if most >= hours => busiest,
+            _ => Some((week, hours)),
+        });
+        stats
+    }
+}
+
+/// The load on students in each teaching week
+#[derive(Debug, Clone, Default)]
+pub struct Workload {
+    /// Contact hours and the number of deadlines in each week, by index
+    pub weeks: Vec<(f64, usize)>,
+}
+
+impl Workload {
+    pub fn new(course: &Course, events: &[Event]) -> Self {
+        let mut weeks = vec![(0.0, 0); course.week_count()];
+        for event in events {
+            let load = match event.week().and_then(|week| weeks.get_mut(week)) {
+                Some(load) => load,
+                None => continue,
+            };
+            if event.kind() == "submission" {
+                load.1 += 1;
+            } else if !event.is_assignment() {
+                load.0 += contact_hours(event);
+            }
+        }
+        Workload { weeks }
+    }
+}
+
+/// Limits beyond which a week is considered overloaded
+#[derive(Debug, Clone, Copy)]
+pub struct Thresholds {
+    pub hours: f64,
+    pub deadlines: usize,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Thresholds { hours: 20.0, deadlines: 2 }
+    }
+}
+
+impl Thresholds {
+    pub fn exceeded(&self, (hours, deadlines): (f64, usize)) -> bool {
+        hours > self.hours || deadlines > self.deadlines
+    }
+}
+
+/// The sessions needing each resource in each teaching week
+#[derive(Debug, Clone, Default)]
+pub struct Equipment<'c> {
+    /// Sessions by resource, for each week by index
+    pub weeks: Vec<BTreeMap<&'c str, Vec<Event<'c>>>>,
+}
+
+impl<'c> Equipment<'c> {
+    pub fn new(course: &Course, events: &[Event<'c>]) -> Self {
+        let mut weeks = vec![BTreeMap::new(); course.week_count()];
+        for event in events {
+            if let Some(needs) = event.week().and_then(|week| weeks.get_mut(week)) {
+                for resource in event.resources() {
+                    needs.entry(resource).or_insert_with(Vec::new).push(event.clone());
+                }
+            }
+        }
+        Equipment { weeks }
+    }
+}
+
+/// The length of an event in hours
+fn contact_hours(event: &Event) -> f64 {
+    event.duration().num_minutes() as f64 / 60.0
+}
+
+/// Figures for a particular course, for display
+pub struct Report<'c> {
+    pub course: &'c Course,
+    pub stats: Stats,
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let stats = &self.stats;
+        writeln!(f, "{} {}", self.course.code(), self.course.name())?;
+        writeln!(f, "\nContact hours")?;
+        for (kind, hours) in &stats.hours {
+            writeln!(f, "  {:<16} {:>6.1}", kind, hours)?;
+        }
+        writeln!(f, "  {:<16} {:>6.1}", "total", stats.hours.values().sum::<f64>())?;
+
+        writeln!(f, "\nAssessment items: {}", stats.assessments)?;
+        writeln!(f, "Assessment weight: {} of {}", stats.weight, self.course.assessment_total())?;
+        match stats.busiest {
+            Some((week, hours)) => writeln!(f, "Busiest week: {} ({:.1} hours)", self.course.week_number(week), hours)?,
+            None => writeln!(f, "Busiest week: none")?,
+        }
+
+        if !stats.presenters.is_empty() {
+            writeln!(f, "\nSessions per presenter")?;
+            for (presenter, count) in &stats.presenters {
+                writeln!(f, "  {:<24} {:>4}", presenter, count)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The equipment needed in each week, for teaching support staff
+pub struct EquipmentReport<'c> {
+    pub course: &'c Course,
+    pub equipment: Equipment<'c>,
+}
+
+impl fmt::Display for EquipmentReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (week, needs) in self.equipment.weeks.iter().enumerate().filter(|(_, needs)| !needs.is_empty()) {
+            writeln!(f, "Week {}", self.course.week_number(week))?;
+            for (resource, events) in needs {
+                writeln!(f, "  {}", resource)?;
+                for event in events {
+                    let location = event.room().or_else(|| event.location()).unwrap_or("no location");
+                    writeln!(f, "    {} {}  {}", event.start().format("%a %-d %b %H:%M"), event.title(), location)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The workload of each week of a course, flagging those over the thresholds
+pub struct WorkloadReport<'c> {
+    pub course: &'c Course,
+    pub workload: Workload,
+    pub thresholds: Thresholds,
+}
+
+impl fmt::Display for WorkloadReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:>4}  {:>6}  {:>9}", "Week", "Hours", "Deadlines")?;
+        for (week, load) in self.workload.weeks.iter().enumerate() {
+            let flag = if self.thresholds.exceeded(*load) { "  overloaded" } else { "" };
+            writeln!(f, "{:>4}  {:>6.1}  {:>9}{}", self.course.week_number(week), load.0, load.1, flag)?;
+        }
+        let overloaded = self.workload.weeks.iter().filter(|load| self.thresholds.exceeded(**load)).count();
+        writeln!(
+            f,
+            "{} weeks over {} hours or {} deadlines",
+            overloaded, self.thresholds.hours, self.thresholds.deadlines,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Three weeks in which the second is crowded with labs and deadlines
+    const TERM: &str = r#"
+        version = 2
+        code = "COMP1000"
+        name = "Testing"
+        link = "https://example.edu/comp1000"
+        assessment_total = 60
+
+        [[week]]
+        start = 2024-02-12T00:00:00+11:00
+
+        [[week]]
+        start = 2024-02-19T00:00:00+11:00
+
+        [[week]]
+        start = 2024-02-26T00:00:00+11:00
+
+        [[session]]
+        first = 2024-02-12T10:00:00+11:00
+        kind = "lecture"
+        presenters = ["Ada"]
+        duration = 7200
+        weeks = [1, 2, 3]
+
+        [[session]]
+        first = 2024-02-21T14:00:00+11:00
+        kind = "lab"
+        location = "K17 Lab 2"
+        presenters = ["Ada", "Grace"]
+        resources = ["oscilloscopes", "soldering irons"]
+        duration = 10800
+        weeks = [2]
+
+        [[assignment]]
+        name = "Essay"
+        link = "https://example.edu/essay"
+        value = 30
+
+        [[assignment.submission]]
+        time = 2024-02-20T17:00:00+11:00
+        name = "Draft"
+
+        [[assignment.submission]]
+        time = 2024-02-23T17:00:00+11:00
+        name = "Final"
+
+        [[assignment]]
+        name = "Participation"
+        link = "https://example.edu/participation"
+        "#;
+
+    fn term() -> Course {
+        let mut course = Course::from_value(toml::from_str(TERM).unwrap()).unwrap();
+        course.generate_repeats().unwrap();
+        course
+    }
+
+    #[test]
+    fn contact_hours_are_totalled_by_kind() {
+        let course = term();
+        let events = course.events().collect::<Vec<_>>();
+        let stats = Stats::new(&course, &events);
+        assert_eq!(stats.hours.get("lecture"), Some(&6.0));
+        assert_eq!(stats.hours.get("lab"), Some(&3.0));
+        assert_eq!((stats.assessments, stats.weight), (2, 30));
+        assert_eq!(stats.busiest, Some((1, 5.0)));
+        assert_eq!(stats.presenters.get("Ada"), Some(&4));
+
+        let report = Report { course: &course, stats }.to_string();
+        assert!(report.contains("  total               9.0\n"));
+        assert!(report.contains("Assessment weight: 30 of 60\n"));
+        assert!(report.contains("Busiest week: 2 (5.0 hours)\n"));
+        assert!(report.contains("  Grace                       1\n"));
+    }
+
+    #[test]
+    fn the_earliest_of_equally_busy_weeks_is_the_busiest() {
+        let course = term();
+        let lectures = course.events().filter(|event| event.kind() == "lecture").collect::<Vec<_>>();
+        assert_eq!(Stats::new(&course, &lectures).busiest, Some((0, 2.0)));
+
+        let stats = Stats::new(&course, &[]);
+        assert_eq!(stats.busiest, None);
+        assert!(Report { course: &course, stats }.to_string().contains("Busiest week: none\n"));
+    }
+
+    #[test]
+    fn weeks_over_either_threshold_are_overloaded() {
+        let course = term();
+        let events = course.events().collect::<Vec<_>>();
+        let workload = Workload::new(&course, &events);
+        assert_eq!(workload.weeks, [(2.0, 0), (5.0, 2), (2.0, 0)]);
+
+        let thresholds = Thresholds { hours: 4.0, deadlines: 2 };
+        assert!(thresholds.exceeded((5.0, 0)) && thresholds.exceeded((0.0, 3)) && !thresholds.exceeded((4.0, 2)));
+        let report = WorkloadReport { course: &course, workload, thresholds }.to_string();
+        assert_eq!(
+            report,
+            "Week   Hours  Deadlines\n   \
+             1     2.0          0\n   \
+             2     5.0          2  overloaded\n   \
+             3     2.0          0\n\
+             1 weeks over 4 hours or 2 deadlines\n"
+        );
+    }
+
+    #[test]
+    fn equipment_is_listed_by_week_and_resource() {
+        let course = term();
+        let events = course.events().collect::<Vec<_>>();
+        let equipment = Equipment::new(&course, &events);
+        assert!(equipment.weeks[0].is_empty() && equipment.weeks[2].is_empty());
+        assert_eq!(equipment.weeks[1].keys().collect::<Vec<_>>(), [&"oscilloscopes", &"soldering irons"]);
+
+        let report = EquipmentReport { course: &course, equipment }.to_string();
+        assert_eq!(
+            report,
+            "Week 2\n  \
+             oscilloscopes\n    \
+             Wed 21 Feb 14:00 (lab)  K17 Lab 2\n  \
+             soldering irons\n    \
+             Wed 21 Feb 14:00 (lab)  K17 Lab 2\n"
+        );
+    }
+}
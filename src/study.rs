@@ -0,0 +1,139 @@
+//! Time set aside to work on assignments before they are due
+
+use chrono::{DateTime, Duration, FixedOffset, Timelike};
+
+use crate::course::{Course, Event};
+use crate::filter::Filter;
+
+/// The earliest hour of the day at which study is scheduled
+const DAY_START: u32 = 9;
+/// The hour of the day by which study must finish
+const DAY_END: u32 = 21;
+/// How many days before a deadline to look for free time
+const HORIZON: i64 = 14;
+
+/// A block of time in which to work on a submission
+#[derive(Debug, Clone)]
+pub struct Block<'c> {
+    /// The submission being worked towards
+    pub submission: Event<'c>,
+    pub start: DateTime<FixedOffset>,
+    pub end: DateTime<FixedOffset>,
+}
+
+/// Schedule some hours of study before each submission that matches a filter
+///
+/// Study is placed an hour at a time between 9:00 and 21:00, as late as possible
+/// before the deadline, avoiding every session of the course and the study
+/// already scheduled for other submissions. Consecutive hours are joined into
+/// a single block.
+pub fn schedule<'c>(course: &'c Course, filter: &Filter, hours: f64) -> Vec<Block<'c>> {
+    let mut busy = course
+        .events()
+        .filter(|event| !event.is_assignment())
+        .map(|event| (event.start(), event.end()))
+        .collect::<Vec<_>>();
+    let mut blocks: Vec<Block> = Vec::new();
+
+    let submissions = course.events().filter(|event| event.kind() == "submission" && filter.matches(event));
+    for submission in submissions {
+        let deadline = submission.start();
+        let horizon = deadline - Duration::days(HORIZON);
+        let mut remaining = Duration::minutes((hours * 60.0).round() as i64);
+        let mut slot = deadline.date().and_hms(deadline.hour(), 0, 0) - Duration::hours(1);
+        let mut placed = Vec::new();
+
+        while remaining > Duration::zero() && slot >= horizon {
+            let length = remaining.min(Duration::hours(1));
+            let (start, end) = (slot + Duration::hours(1) - length, slot + Duration::hours(1));
+            let free = !busy.iter().any(|(from, to)| *from < end && start < *to);
+            if free && slot.hour() >= DAY_START && slot.hour() < DAY_END {
+                placed.push((start, end));
+                remaining = remaining - length;
+            }
+            slot = slot - Duration::hours(1);
+        }
+
+        placed.reverse();
+        busy.extend(placed.iter().cloned());
+        for (start, end) in placed {
+            match blocks.last_mut() {
+                Some(last) if last.end == start && last.submission == submission => last.end = end,
+                _ => blocks.push(Block { submission: submission.clone(), start, end }),
+            }
+        }
+    }
+
+    blocks.sort_by_key(|block| block.start);
+    blocks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The blocks scheduled before each deadline in a course with a lecture on Friday afternoon
+    fn blocks(submissions: &str, hours: f64) -> Vec<(String, String)> {
+        let spec = format!(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/comp1000"
+
+            [[week]]
+            start = 2024-02-12T00:00:00+11:00
+
+            [[session]]
+            first = 2024-02-16T14:00:00+11:00
+            kind = "lecture"
+            duration = 3600
+            weeks = [1]
+
+            [[assignment]]
+            name = "Assignment"
+            link = "https://example.edu/assignment"
+            {}
+            "#,
+            submissions
+        );
+        let mut course = Course::from_value(toml::from_str(&spec).unwrap()).unwrap();
+        course.generate_repeats().unwrap();
+        schedule(&course, &Filter::default(), hours)
+            .into_iter()
+            .map(|block| (block.start.format("%a %H:%M").to_string(), block.end.format("%a %H:%M").to_string()))
+            .collect()
+    }
+
+    fn times(blocks: &[(&str, &str)]) -> Vec<(String, String)> {
+        blocks.iter().map(|(start, end)| (start.to_string(), end.to_string())).collect()
+    }
+
+    const FRIDAY: &str = r#"
+        [[assignment.submission]]
+        time = 2024-02-16T17:00:00+11:00
+        name = "Final"
+        "#;
+
+    #[test]
+    fn study_is_as_late_as_possible_around_sessions() {
+        assert_eq!(blocks(FRIDAY, 3.0), times(&[("Fri 13:00", "Fri 14:00"), ("Fri 15:00", "Fri 17:00")]));
+    }
+
+    #[test]
+    fn study_stays_within_the_day() {
+        let monday = r#"
+            [[assignment.submission]]
+            time = 2024-02-12T10:30:00+11:00
+            name = "Plan"
+            "#;
+        // Part hours are taken from the end of a slot
+        assert_eq!(blocks(monday, 1.5), times(&[("Sun 20:30", "Sun 21:00"), ("Mon 09:00", "Mon 10:00")]));
+    }
+
+    #[test]
+    fn submissions_do_not_share_study() {
+        let both = format!("{}\n[[assignment.submission]]\ntime = 2024-02-16T17:00:00+11:00\nname = \"Report\"", FRIDAY);
+        assert_eq!(blocks(&both, 1.0), times(&[("Fri 15:00", "Fri 16:00"), ("Fri 16:00", "Fri 17:00")]));
+    }
+}
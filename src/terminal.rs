@@ -0,0 +1,284 @@
+//! Views of a course for reading in a terminal
+
+use chrono::{DateTime, Datelike, Duration, FixedOffset, Local, Timelike, Utc};
+
+use crate::course::{Course, Event};
+use crate::state;
+
+use std::env;
+use std::io::{stdout, IsTerminal};
+
+/// The width of each day in the grid
+const DAY_WIDTH: usize = 18;
+
+/// Background colours for kinds of events, chosen by hash
+const BACKGROUNDS: &[u8] = &[41, 42, 43, 44, 45, 46, 101, 102, 103, 104, 105, 106];
+
+/// Whether to colour the output, which is only when writing to a terminal
+pub fn use_colour() -> bool {
+    stdout().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+/// The week to show by default, which is the current week if the course is running
+pub fn current_week(course: &Course) -> usize {
+    let now = Utc::now().into();
+    match course.week_of(now) {
+        Some(week) if now < course.week_start(week).unwrap_or(now) + Duration::weeks(1) => week,
+        _ => 0,
+    }
+}
+
+/// A grid of the events in a week with days across and hours down
+pub fn grid(course: &Course, week: usize, events: &[Event], colour: bool) -> String {
+    let events = events.iter().filter(|event| event.week() == Some(week)).collect::<Vec<_>>();
+    let first_hour = events.iter().map(|event| event.start().hour()).min().unwrap_or(9);
+    let last_hour = events
+        .iter()
+        .map(|event| (event.end() - Duration::minutes(1)).hour() + 1)
+        .max()
+        .unwrap_or(17)
+        .max(first_hour + 1);
+    let weekend = events.iter().any(|event| event.start().weekday().num_days_from_monday() >= 5);
+    let days = if weekend { 7 } else { 5 };
+    let monday = course
+        .week_start(week)
+        .map(|start| start - Duration::days(start.weekday().num_days_from_monday() as i64));
+
+    let mut grid = format!("{} {}, week {}\n\n      ", course.code(), course.name(), course.week_number(week));
+    for day in 0..days {
+        let label = match monday {
+            Some(monday) => (monday + Duration::days(day as i64)).format("%a %-d %b").to_string(),
+            None => ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"][day].to_owned(),
+        };
+        grid.push_str(&format!("│{:<width$}", label, width = DAY_WIDTH));
+    }
+    grid.push('\n');
+
+    for hour in first_hour..last_hour {
+        grid.push_str(&format!("{:02}:00 ", hour));
+        for day in 0..days {
+            // Events that start within the hour are named, and those continuing are marked
+            let covering = events
+                .iter()
+                .filter(|event| event.start().weekday().num_days_from_monday() as usize == day)
+                .filter(|event| event.start().hour() <= hour && hour < (event.end() - Duration::minutes(1)).hour() + 1)
+                .collect::<Vec<_>>();
+            let text = covering
+                .iter()
+                .map(|event| if event.start().hour() == hour { event.name() } else { "┆".to_owned() })
+                .collect::<Vec<_>>()
+                .join(" / ");
+            let text = truncate(&text, DAY_WIDTH);
+            grid.push('│');
+            match covering.first() {
+                Some(event) if colour => grid.push_str(&format!(
+                    "\x1b[30;{}m{:<width$}\x1b[0m",
+                    background(event.kind()),
+                    text,
+                    width = DAY_WIDTH,
+                )),
+                _ => grid.push_str(&format!("{:<width$}", text, width = DAY_WIDTH)),
+            }
+        }
+        grid.push('\n');
+    }
+    grid
+}
+
+/// The events that start today in the local time zone
+pub fn today<'c>(events: &[Event<'c>]) -> Vec<Event<'c>> {
+    let today = Local::now().date().naive_local();
+    events
+        .iter()
+        .filter(|event| event.start().with_timezone(&Local).date().naive_local() == today)
+        .cloned()
+        .collect()
+}
+
+/// The first event that hasn't started yet
+pub fn next<'c>(events: &[Event<'c>]) -> Option<Event<'c>> {
+    let now: DateTime<FixedOffset> = Utc::now().into();
+    events.iter().filter(|event| event.start() > now).min_by_key(|event| event.start()).cloned()
+}
+
+/// A single line describing an event, with times in the local time zone
+pub fn summary(course: &Course, event: &Event, date: bool) -> String {
+    let start = event.start().with_timezone(&Local);
+    let time = if date { start.format("%a %-d %b %H:%M") } else { start.format("%H:%M") };
+    let mut line = match event.kind() {
+        "submission" => format!("{} {} {} due", time, course.code(), event.name()),
+        _ => format!("{}-{} {} {}", time, event.end().with_timezone(&Local).format("%H:%M"), course.code(), event.name()),
+    };
+    if let Some(location) = event.location() {
+        line.push_str(&format!(" ({})", location));
+    }
+    line
+}
+
+/// A chronological table of events with their kinds, locations, and presenters
+pub fn agenda(course: &Course, events: &[Event]) -> String {
+    let mut rows = vec![["When", "Event", "Kind", "Location", "Presenters"].map(str::to_owned)];
+    for event in events {
+        let start = event.start().with_timezone(&Local);
+        let when = match event.kind() {
+            "submission" => start.format("%a %-d %b %H:%M").to_string(),
+            _ => format!("{}-{}", start.format("%a %-d %b %H:%M"), event.end().with_timezone(&Local).format("%H:%M")),
+        };
+        rows.push([
+            when,
+            format!("{} {}", course.code(), event.name()),
+            event.kind().to_owned(),
+            event.location().unwrap_or_default().to_owned(),
+            event.presenters().collect::<Vec<_>>().join(", "),
+        ]);
+    }
+
+    let mut widths = [0; 5];
+    for row in &rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+    let mut table = String::new();
+    for row in &rows {
+        let cells = row.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width));
+        table.push_str(cells.collect::<Vec<_>>().join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+fn background(kind: &str) -> u8 {
+    BACKGROUNDS[(state::hash(kind) % BACKGROUNDS.len() as u64) as usize]
+}
+
+/// Shorten text to a number of characters, marking where it was cut
+pub fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        text.to_owned()
+    } else {
+        text.chars().take(width - 1).chain(Some('…')).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn course(year: i32) -> Course {
+        let spec = format!(
+            r#"
+            version = 2
+            code = "COMP1000"
+            name = "Testing"
+            link = "https://example.edu/comp1000"
+
+            [[week]]
+            start = {year}-02-12T00:00:00+11:00
+
+            [[session]]
+            first = {year}-02-12T10:00:00+11:00
+            kind = "lecture"
+            location = "Quad 1001"
+            presenters = ["Ada"]
+            duration = 7200
+            weeks = [1]
+
+            [[session]]
+            first = {year}-02-13T11:30:00+11:00
+            kind = "tutorial"
+            stream = "T11A"
+            duration = 3600
+            weeks = [1]
+
+            [[session]]
+            first = {year}-02-13T11:00:00+11:00
+            kind = "help session"
+            duration = 1800
+            weeks = [1]
+
+            [[assignment]]
+            name = "Assignment"
+            link = "https://example.edu/assignment"
+
+            [[assignment.submission]]
+            time = {year}-02-16T17:00:00+11:00
+            name = "Final"
+            "#,
+            year = year
+        );
+        let mut course = Course::from_value(toml::from_str(&spec).unwrap()).unwrap();
+        course.generate_repeats().unwrap();
+        course
+    }
+
+    #[test]
+    fn long_text_is_cut_short() {
+        assert_eq!(truncate("lecture", 18), "lecture");
+        assert_eq!(truncate("help session / tutorial", 18), "help session / tu…");
+    }
+
+    #[test]
+    fn weeks_are_drawn_hour_by_hour() {
+        let course = course(2024);
+        let events = course.events().collect::<Vec<_>>();
+        let grid = grid(&course, 0, &events, false);
+        let lines = grid.lines().collect::<Vec<_>>();
+        assert_eq!(lines[0], "COMP1000 Testing, week 1");
+        assert!(lines[2].starts_with("      │Mon 12 Feb        │Tue 13 Feb        │"));
+        assert!(lines[2].ends_with("│Fri 16 Feb        "));
+        assert!(lines[3].starts_with("10:00 │lecture           │                  │"));
+        // Overlapping sessions share the cell, and those continuing from the hour before are marked
+        assert!(lines[4].starts_with("11:00 │┆                 │help session / tu…│"));
+        assert!(lines[5].starts_with("12:00 │                  │┆                 │"));
+        assert!(!grid.contains('\x1b'));
+    }
+
+    #[test]
+    fn colour_follows_the_kind() {
+        let course = course(2024);
+        let events = course.events().collect::<Vec<_>>();
+        let grid = grid(&course, 0, &events, true);
+        let lecture = format!("│\x1b[30;{}mlecture           \x1b[0m│", background("lecture"));
+        assert!(grid.contains(&lecture));
+    }
+
+    #[test]
+    fn the_next_event_is_the_soonest_yet_to_start() {
+        let (past, future) = (course(2000), course(2999));
+        let events = past.events().chain(future.events()).collect::<Vec<_>>();
+        let upcoming = next(&events).unwrap();
+        assert_eq!((upcoming.name(), upcoming.start().to_rfc3339()), ("lecture".to_owned(), "2999-02-12T10:00:00+11:00".to_owned()));
+        assert!(today(&events).is_empty());
+        assert!(next(&past.events().collect::<Vec<_>>()).is_none());
+    }
+
+    #[test]
+    fn summaries_name_the_course_and_location() {
+        let course = course(2024);
+        let events = course.events().collect::<Vec<_>>();
+        let lecture = summary(&course, &events[0], false);
+        assert!(lecture.ends_with(" COMP1000 lecture (Quad 1001)"));
+        assert_eq!(lecture.len(), "00:00-00:00 COMP1000 lecture (Quad 1001)".len());
+        let submission = events.iter().find(|event| event.kind() == "submission").unwrap();
+        let deadline = summary(&course, submission, true);
+        assert!(deadline.ends_with(" COMP1000 Assignment: Final due"));
+        assert!(deadline.contains(" Feb "));
+    }
+
+    #[test]
+    fn agenda_columns_line_up() {
+        let course = course(2024);
+        let agenda = agenda(&course, &course.events().collect::<Vec<_>>());
+        let lines = agenda.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        let column = |line: &str, text: &str| line.find(text).unwrap();
+        let event = column(lines[0], "Event");
+        assert_eq!(column(lines[1], "COMP1000 lecture"), event);
+        assert_eq!(column(lines[4], "COMP1000 Assignment: Final"), event);
+        let location = column(lines[0], "Location");
+        assert_eq!(column(lines[1], "Quad 1001  Ada"), location);
+        // Trailing empty columns leave no trailing space
+        assert!(lines[4].ends_with("  submission"));
+    }
+}
@@ -0,0 +1,270 @@
+//! Checks for likely mistakes in course specifications
+
+use chrono::Duration;
+use failure::{bail, Error};
+use serde::{Deserialize, Serialize};
+
+use crate::clashes;
+use crate::course::Course;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// How serious a problem is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Warning => f.write_str("warning"),
+            Severity::Error => f.write_str("error"),
+        }
+    }
+}
+
+/// How a rule is applied
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Level {
+    /// The rule is not checked
+    Allow,
+    /// Violations are reported as warnings
+    Warn,
+    /// Violations are reported as errors
+    Deny,
+}
+
+impl Level {
+    fn severity(self) -> Option<Severity> {
+        match self {
+            Level::Allow => None,
+            Level::Warn => Some(Severity::Warning),
+            Level::Deny => Some(Severity::Error),
+        }
+    }
+}
+
+/// A problem found in a course
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}[{}]: {}", self.severity, self.rule, self.message)
+    }
+}
+
+/// A check producing messages describing each violation
+struct Rule {
+    name: &'static str,
+    level: Level,
+    check: fn(&Course) -> Vec<String>,
+}
+
+/// Rules checked before repeated sessions are generated
+const SPEC_RULES: &[Rule] = &[
+    Rule { name: "dangling-week", level: Level::Deny, check: dangling_week },
+];
+
+/// Rules checked against the generated events
+const EVENT_RULES: &[Rule] = &[
+    Rule { name: "overlapping-sessions", level: Level::Warn, check: overlapping_sessions },
+    Rule { name: "unknown-presentation-session", level: Level::Warn, check: unknown_presentation_session },
+    Rule { name: "submission-before-term", level: Level::Warn, check: submission_before_term },
+    Rule { name: "missing-location", level: Level::Warn, check: missing_location },
+    Rule { name: "session-outside-week", level: Level::Warn, check: session_outside_week },
+    Rule { name: "assessment-total", level: Level::Warn, check: assessment_total },
+    Rule { name: "unallocated-groups", level: Level::Warn, check: unallocated_groups },
+    Rule { name: "travel-time", level: Level::Warn, check: travel_time },
+];
+
+/// Check a course loaded without generated repeats for problems
+///
+/// Rule levels configured in the course are overridden by those in `levels`.
+pub fn validate(course: &Course, levels: &BTreeMap<String, Level>) -> Result<Vec<Diagnostic>, Error> {
+    for name in course.lints().keys().chain(levels.keys()) {
+        if !SPEC_RULES.iter().chain(EVENT_RULES).any(|rule| rule.name == name) {
+            bail!("Unknown validation rule {}", name);
+        }
+    }
+
+    let level = |rule: &Rule| {
+        levels.get(rule.name).or_else(|| course.lints().get(rule.name)).cloned().unwrap_or(rule.level)
+    };
+    let mut diagnostics = Vec::new();
+    let apply = |rule: &Rule, course: &Course, diagnostics: &mut Vec<Diagnostic>| {
+        if let Some(severity) = level(rule).severity() {
+            diagnostics.extend((rule.check)(course).into_iter().map(|message| Diagnostic {
+                rule: rule.name,
+                severity,
+                message,
+            }));
+        }
+    };
+
+    for rule in SPEC_RULES {
+        apply(rule, course, &mut diagnostics);
+    }
+
+    let mut generated = course.clone();
+    if let Err(error) = generated.generate_repeats() {
+        // Events can't be checked if they can't be generated
+        if diagnostics.iter().all(|diagnostic| diagnostic.severity != Severity::Error) {
+            diagnostics.push(Diagnostic {
+                rule: "dangling-week",
+                severity: Severity::Error,
+                message: error.to_string(),
+            });
+        }
+        return Ok(diagnostics);
+    }
+
+    for rule in EVENT_RULES {
+        apply(rule, &generated, &mut diagnostics);
+    }
+
+    Ok(diagnostics)
+}
+
+/// References to weeks that don't exist
+fn dangling_week(course: &Course) -> Vec<String> {
+    course.week_references()
+        .into_iter()
+        .filter(|(_, week)| *week >= course.week_count())
+        .map(|(what, week)| format!("{} refers to non-existent week {}", what, course.week_number(week)))
+        .collect()
+}
+
+/// Sessions in the same week that overlap, other than alternative streams
+fn overlapping_sessions(course: &Course) -> Vec<String> {
+    let events = course.events().map(|event| (course, event)).collect::<Vec<_>>();
+    let mut messages = Vec::new();
+
+    for ((_, first), (_, second)) in clashes::overlapping(&events) {
+        if first.week() != second.week() {
+            continue;
+        }
+
+        let alternatives = first.kind() == second.kind()
+            && first.stream().is_some()
+            && second.stream().is_some()
+            && first.stream() != second.stream();
+        if alternatives {
+            continue;
+        }
+
+        messages.push(format!(
+            "{} overlaps {}",
+            clashes::describe(course, first),
+            clashes::describe(course, second),
+        ));
+    }
+
+    messages
+}
+
+/// Presentations held in a kind of session that never occurs
+fn unknown_presentation_session(course: &Course) -> Vec<String> {
+    let kinds = course.events()
+        .filter(|event| !event.is_assignment())
+        .map(|event| event.kind())
+        .collect::<BTreeSet<_>>();
+
+    course.presentation_kinds()
+        .into_iter()
+        .filter(|(_, kind)| !kinds.contains(kind))
+        .map(|(what, kind)| format!("{} is held in {} sessions but there are none", what, kind))
+        .collect()
+}
+
+/// Submissions due before the first week of the course
+fn submission_before_term(course: &Course) -> Vec<String> {
+    let start = match course.term_start() {
+        Some(start) => start,
+        None => return Vec::new(),
+    };
+
+    course.events()
+        .filter(|event| event.kind() == "submission" && event.start() < start)
+        .map(|event| format!("{} is due before the term starts", clashes::describe(course, &event)))
+        .collect()
+}
+
+/// Sessions without a location
+fn missing_location(course: &Course) -> Vec<String> {
+    course.events()
+        .filter(|event| !event.is_assignment() && event.location().is_none())
+        .map(|event| format!("{} has no location", clashes::describe(course, &event)))
+        .collect()
+}
+
+/// Sessions that don't occur within the seven days after the start of their week
+fn session_outside_week(course: &Course) -> Vec<String> {
+    course.events()
+        .filter(|event| !event.is_assignment())
+        .filter_map(|event| {
+            let start = course.week_start(event.week()?)?;
+            if event.start() < start || event.start() >= start + Duration::days(7) {
+                Some(format!(
+                    "{} is outside its week, which starts {}",
+                    clashes::describe(course, &event),
+                    start.format("%a %Y-%m-%d"),
+                ))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Consecutive sessions in different buildings without enough time to travel between them
+fn travel_time(course: &Course) -> Vec<String> {
+    clashes::tight_transfers(course)
+        .into_iter()
+        .map(|(first, second)| {
+            format!(
+                "{} leaves {} minutes to get from {} to {}",
+                clashes::describe(course, &first),
+                (second.start() - first.end()).num_minutes(),
+                first.building().unwrap_or_default(),
+                second.building().unwrap_or_default(),
+            )
+        })
+        .collect()
+}
+
+/// Groups that don't fit in the slots of the sessions of their presentation
+fn unallocated_groups(course: &Course) -> Vec<String> {
+    course
+        .unallocated_groups()
+        .into_iter()
+        .map(|(presentation, groups)| format!("No slots in {} for {}", presentation, groups.join(", ")))
+        .collect()
+}
+
+/// Assignment values that don't add up to the expected total
+fn assessment_total(course: &Course) -> Vec<String> {
+    let values = course.assignment_values();
+    if values.iter().all(|(_, value)| value.is_none()) {
+        return Vec::new();
+    }
+
+    let mut messages = values
+        .iter()
+        .filter(|(_, value)| value.is_none())
+        .map(|(name, _)| format!("{} has no value", name))
+        .collect::<Vec<_>>();
+    let total = values.iter().filter_map(|(_, value)| *value).sum::<u64>();
+    if total != course.assessment_total() {
+        messages.push(format!("Assignment values add up to {} rather than {}", total, course.assessment_total()));
+    }
+    messages
+}
@@ -0,0 +1,139 @@
+//! Writing zip archives of uncompressed files
+
+use chrono::{Datelike, Local, Timelike};
+
+use std::io::{self, Write};
+
+/// Files to be written together as a zip archive
+#[derive(Debug, Clone, Default)]
+pub struct Archive {
+    files: Vec<(String, Vec<u8>)>,
+}
+
+impl Archive {
+    pub fn add(&mut self, name: String, contents: Vec<u8>) {
+        self.files.push((name, contents));
+    }
+
+    /// Write the archive, storing every file without compression
+    pub fn write<W: Write>(&self, mut output: W) -> io::Result<()> {
+        let now = Local::now();
+        // MS-DOS times have a resolution of two seconds, and dates count years from 1980
+        let time = ((now.hour() << 11) | (now.minute() << 5) | (now.second() / 2)) as u16;
+        let date = ((((now.year() - 1980).max(0) as u32) << 9) | (now.month() << 5) | now.day()) as u16;
+
+        let mut central = Vec::new();
+        let mut offset = 0u32;
+        for (name, contents) in &self.files {
+            let crc = crc32(contents);
+            let size = contents.len() as u32;
+
+            let mut header = Vec::new();
+            header.extend(&0x0403_4b50u32.to_le_bytes());
+            header.extend(&common(time, date, crc, size, name));
+            header.extend(name.as_bytes());
+            output.write_all(&header)?;
+            output.write_all(contents)?;
+
+            central.extend(&0x0201_4b50u32.to_le_bytes());
+            central.extend(&20u16.to_le_bytes());
+            central.extend(&common(time, date, crc, size, name));
+            // No comment, on the first disk, with no attributes
+            central.extend(&[0; 10]);
+            central.extend(&offset.to_le_bytes());
+            central.extend(name.as_bytes());
+
+            offset += (header.len() + contents.len()) as u32;
+        }
+        output.write_all(&central)?;
+
+        let count = self.files.len() as u16;
+        let mut end = Vec::new();
+        end.extend(&0x0605_4b50u32.to_le_bytes());
+        end.extend(&[0; 4]);
+        end.extend(&count.to_le_bytes());
+        end.extend(&count.to_le_bytes());
+        end.extend(&(central.len() as u32).to_le_bytes());
+        end.extend(&offset.to_le_bytes());
+        end.extend(&0u16.to_le_bytes());
+        output.write_all(&end)?;
+        output.flush()
+    }
+}
+
+/// The fields shared by local and central headers, from the version needed to the extra length
+fn common(time: u16, date: u16, crc: u32, size: u32, name: &str) -> Vec<u8> {
+    let mut fields = Vec::new();
+    fields.extend(&20u16.to_le_bytes());
+    // Names are UTF-8
+    fields.extend(&0x0800u16.to_le_bytes());
+    // Stored without compression
+    fields.extend(&0u16.to_le_bytes());
+    fields.extend(&time.to_le_bytes());
+    fields.extend(&date.to_le_bytes());
+    fields.extend(&crc.to_le_bytes());
+    fields.extend(&size.to_le_bytes());
+    fields.extend(&size.to_le_bytes());
+    fields.extend(&(name.len() as u16).to_le_bytes());
+    fields.extend(&0u16.to_le_bytes());
+    fields
+}
+
+/// The CRC-32 checksum used by zip
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= u32::from(*byte);
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn u16_at(bytes: &[u8], at: usize) -> usize {
+        u16::from_le_bytes([bytes[at], bytes[at + 1]]) as usize
+    }
+
+    fn u32_at(bytes: &[u8], at: usize) -> u32 {
+        u32::from_le_bytes([bytes[at], bytes[at + 1], bytes[at + 2], bytes[at + 3]])
+    }
+
+    #[test]
+    fn checksums_match_the_standard() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn central_directory_lists_every_file() {
+        let mut archive = Archive::default();
+        archive.add("a.ics".to_owned(), b"first".to_vec());
+        archive.add("wéek/b.ics".to_owned(), b"second file".to_vec());
+        let mut bytes = Vec::new();
+        archive.write(&mut bytes).unwrap();
+
+        let end = bytes.len() - 22;
+        assert_eq!(u32_at(&bytes, end), 0x0605_4b50);
+        assert_eq!(u16_at(&bytes, end + 10), 2);
+        let mut central = u32_at(&bytes, end + 16) as usize;
+        assert_eq!(central + u32_at(&bytes, end + 12) as usize, end);
+
+        for (name, contents) in &archive.files {
+            assert_eq!(u32_at(&bytes, central), 0x0201_4b50);
+            let name_length = u16_at(&bytes, central + 28);
+            assert_eq!(&bytes[central + 46..central + 46 + name_length], name.as_bytes());
+            assert_eq!(u32_at(&bytes, central + 16), crc32(contents));
+
+            let local = u32_at(&bytes, central + 42) as usize;
+            assert_eq!(u32_at(&bytes, local), 0x0403_4b50);
+            let start = local + 30 + u16_at(&bytes, local + 26);
+            assert_eq!(&bytes[start..start + contents.len()], &contents[..]);
+            central += 46 + name_length;
+        }
+    }
+}